@@ -0,0 +1,239 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{FritzError, Result};
+
+/// Actor identification number (AIN) that identifies a device, group or
+/// template, e.g. `11630 0123723`.
+///
+/// The fritz box writes AINs of DECT devices with a space after the fifth
+/// digit but accepts them without it, so whitespace is not significant:
+/// `"116300123723"` and `"11630 0123723"` are the same [Ain]. HAN-FUN units
+/// append a unit number to the AIN of their device (`11630 0123723-1`), groups
+/// and templates use prefixed ids such as `grp424E2B-3D5C11C33`.
+///
+/// ```
+/// let ain: fritzapi::Ain = "116300123723".parse().unwrap();
+/// assert_eq!(ain.to_string(), "11630 0123723");
+/// assert_eq!(ain, "11630 0123723");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ain(String);
+
+impl Ain {
+    /// Parses `input`, ignoring all whitespace. Only ASCII letters, digits and
+    /// `-` are allowed.
+    pub fn parse(input: &str) -> Result<Self> {
+        let compact: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+        if compact.is_empty() {
+            return Err(FritzError::ParserError("ain is empty".to_string()));
+        }
+        if let Some(c) = compact
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '-'))
+        {
+            return Err(FritzError::ParserError(format!(
+                "invalid character {:?} in ain {:?}",
+                c, input
+            )));
+        }
+        Ok(Ain(compact))
+    }
+
+    /// The AIN without any whitespace, the form the AHA interface expects.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The AIN percent-encoded for use in a URL query.
+    pub fn url_encoded(&self) -> String {
+        self.0
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    }
+
+    /// The unit number of a HAN-FUN unit, e.g. `1` for `11630 0123723-1`.
+    pub fn unit(&self) -> Option<u32> {
+        self.split_unit().map(|(_, unit)| unit)
+    }
+
+    /// For a HAN-FUN unit the AIN of the device it belongs to, otherwise the
+    /// AIN itself.
+    pub fn device(&self) -> Ain {
+        match self.split_unit() {
+            Some((device, _)) => Ain(device.to_string()),
+            None => self.clone(),
+        }
+    }
+
+    fn split_unit(&self) -> Option<(&str, u32)> {
+        let (device, unit) = self.0.rsplit_once('-')?;
+        if device.is_empty() || !device.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        unit.parse().ok().map(|unit| (device, unit))
+    }
+}
+
+impl std::fmt::Display for Ain {
+    /// Canonical form: 12 digit AINs are written as `ddddd ddddddd`, with an
+    /// optional unit suffix. Everything else is written as is.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (device, unit) = match self.0.rsplit_once('-') {
+            Some((device, unit)) => (device, Some(unit)),
+            None => (self.0.as_str(), None),
+        };
+        if device.len() != 12 || !device.chars().all(|c| c.is_ascii_digit()) {
+            return write!(f, "{}", self.0);
+        }
+        write!(f, "{} {}", &device[..5], &device[5..])?;
+        if let Some(unit) = unit {
+            write!(f, "-{}", unit)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Ain {
+    type Err = FritzError;
+
+    fn from_str(input: &str) -> Result<Self> {
+        Ain::parse(input)
+    }
+}
+
+impl TryFrom<&str> for Ain {
+    type Error = FritzError;
+
+    fn try_from(input: &str) -> Result<Self> {
+        Ain::parse(input)
+    }
+}
+
+impl TryFrom<String> for Ain {
+    type Error = FritzError;
+
+    fn try_from(input: String) -> Result<Self> {
+        Ain::parse(&input)
+    }
+}
+
+impl AsRef<str> for Ain {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for Ain {
+    fn eq(&self, other: &str) -> bool {
+        Ain::parse(other).is_ok_and(|other| *self == other)
+    }
+}
+
+impl PartialEq<&str> for Ain {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<String> for Ain {
+    fn eq(&self, other: &String) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl Serialize for Ain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ain::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Deserializes the identifier of an item the fritz box lists. An identifier
+/// that is not a valid [Ain] is kept as written instead of failing the whole
+/// list.
+pub(crate) fn deserialize_lenient<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Ain, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Ok(Ain::parse(&s).unwrap_or_else(|_| Ain(s.trim().to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ain;
+
+    #[test]
+    fn parse_and_format() {
+        let ain: Ain = "11630 0123723".parse().unwrap();
+        assert_eq!(ain.as_str(), "116300123723");
+        assert_eq!(ain.to_string(), "11630 0123723");
+        assert_eq!(
+            "116300123723".parse::<Ain>().unwrap().to_string(),
+            "11630 0123723"
+        );
+        assert_eq!(" 11630  0123723\n".parse::<Ain>().unwrap(), ain);
+
+        assert!("".parse::<Ain>().is_err());
+        assert!("  ".parse::<Ain>().is_err());
+        assert!("11630/0123723".parse::<Ain>().is_err());
+    }
+
+    #[test]
+    fn hanfun_units() {
+        let unit: Ain = "11630 0123723-1".parse().unwrap();
+        assert_eq!(unit.to_string(), "11630 0123723-1");
+        assert_eq!(unit.unit(), Some(1));
+        assert_eq!(unit.device(), "11630 0123723");
+        assert_ne!(unit, "11630 0123723");
+
+        let device: Ain = "11630 0123723".parse().unwrap();
+        assert_eq!(device.unit(), None);
+        assert_eq!(device.device(), device);
+
+        let nested: Ain = "116300123723-1-2".parse().unwrap();
+        assert_eq!(nested.to_string(), "116300123723-1-2");
+        assert_eq!(nested.unit(), None);
+    }
+
+    #[test]
+    fn deserialize_lenient() {
+        use serde::de::{value::StrDeserializer, IntoDeserializer};
+
+        let lenient = |input: &'static str| {
+            let deserializer: StrDeserializer<serde::de::value::Error> = input.into_deserializer();
+            super::deserialize_lenient(deserializer).unwrap()
+        };
+        assert_eq!(lenient("116300123723"), "11630 0123723");
+        assert_eq!(lenient("11630/0123723").as_str(), "11630/0123723");
+        assert_eq!(lenient("11630/0123723").to_string(), "11630/0123723");
+    }
+
+    #[test]
+    fn groups_and_templates() {
+        let group: Ain = "grp424E2B-3D5C11C33".parse().unwrap();
+        assert_eq!(group.to_string(), "grp424E2B-3D5C11C33");
+        assert_eq!(group.unit(), None);
+        assert_eq!(group.url_encoded(), "grp424E2B-3D5C11C33");
+    }
+
+    #[test]
+    fn compare_with_strings() {
+        let ain: Ain = "11630 0123723".parse().unwrap();
+        assert_eq!(ain, "116300123723");
+        assert_eq!(ain, "11630 0123723".to_string());
+        assert_ne!(ain, "11630 0123724");
+        assert_ne!(ain, "not an ain!");
+    }
+}
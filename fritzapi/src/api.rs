@@ -1,6 +1,6 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use reqwest::blocking::{get as GET, Client, Response};
+use reqwest::blocking::{Client, ClientBuilder, Response};
 
 use crate::error::{FritzError, Result};
 use crate::fritz_xml as xml;
@@ -29,10 +29,88 @@ fn request_response(password: impl AsRef<str>, challenge: impl AsRef<str>) -> St
 
 const DEFAULT_SID: &str = "0000000000000000";
 
+pub(crate) const DEFAULT_HOST: &str = "http://fritz.box";
+
+/// Where and how to reach the fritz box.
+#[derive(Clone, Debug)]
+pub(crate) struct Connection {
+    /// Scheme and host without trailing slash, e.g. `http://fritz.box`.
+    pub base_url: String,
+    /// Timeout of each request, reqwest's default if `None`.
+    pub timeout: Option<std::time::Duration>,
+    #[cfg(feature = "tls")]
+    pub accept_invalid_certs: bool,
+    #[cfg(feature = "tls")]
+    pub root_certificates: Vec<reqwest::Certificate>,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Connection {
+            base_url: DEFAULT_HOST.to_string(),
+            timeout: None,
+            #[cfg(feature = "tls")]
+            accept_invalid_certs: false,
+            #[cfg(feature = "tls")]
+            root_certificates: Vec::new(),
+        }
+    }
+}
+
+impl Connection {
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    /// A client builder with the timeout and tls settings applied.
+    pub fn client_builder(&self) -> ClientBuilder {
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        #[cfg(feature = "tls")]
+        {
+            builder = builder.danger_accept_invalid_certs(self.accept_invalid_certs);
+            for cert in &self.root_certificates {
+                builder = builder.add_root_certificate(cert.clone());
+            }
+        }
+        builder
+    }
+
+    pub fn client(&self) -> Result<Client> {
+        Ok(self.client_builder().build()?)
+    }
+}
+
+/// Turns `host` into a base url: `fritz.box` and `192.168.178.1:8080` get an
+/// `http://` scheme, urls with a scheme are taken as they are.
+pub(crate) fn base_url(host: &str) -> Result<String> {
+    let host = host.trim().trim_end_matches('/');
+    if host.is_empty() {
+        return Err(FritzError::ParserError("host is empty".to_string()));
+    }
+    match host.split_once("://") {
+        Some(("http" | "https", rest)) if !rest.is_empty() => Ok(host.to_string()),
+        Some((scheme, _)) => Err(FritzError::ParserError(format!(
+            "unsupported scheme {:?} in host {:?}",
+            scheme, host
+        ))),
+        None => Ok(format!("http://{}", host)),
+    }
+}
+
 /// Requests a temporary token (session id = sid) from the fritz box using user
 /// name and password.
-pub fn get_sid(user: impl AsRef<str>, password: impl AsRef<str>) -> Result<String> {
-    let res: Response = GET("http://fritz.box/login_sid.lua")?
+pub fn get_sid(
+    connection: &Connection,
+    user: impl AsRef<str>,
+    password: impl AsRef<str>,
+) -> Result<String> {
+    let client = connection.client()?;
+    let res: Response = client
+        .get(connection.url("login_sid.lua"))
+        .send()?
         .error_for_status()
         .inspect_err(|err| {
             eprintln!(
@@ -47,12 +125,11 @@ pub fn get_sid(user: impl AsRef<str>, password: impl AsRef<str>) -> Result<Strin
         return Ok(info.sid);
     }
     let response = request_response(password, &info.challenge);
-    let url = format!(
-        "http://fritz.box/login_sid.lua?username={}&response={}",
-        user.as_ref(),
-        response
-    );
-    let login: Response = GET(url)?.error_for_status()?;
+    let login: Response = client
+        .get(connection.url("login_sid.lua"))
+        .query(&[("username", user.as_ref()), ("response", response.as_str())])
+        .send()?
+        .error_for_status()?;
     let info = xml::parse_session_info(&login.text()?)?;
 
     if DEFAULT_SID == info.sid {
@@ -68,37 +145,139 @@ pub fn get_sid(user: impl AsRef<str>, password: impl AsRef<str>) -> Result<Strin
 #[derive(Clone, Debug)]
 pub(crate) enum Commands {
     GetDeviceListInfos,
-    GetBasicDeviceStats { ain: String },
+    GetBasicDeviceStats {
+        ain: String,
+    },
     // GetSwitchPower,
     // GetSwitchEnergy,
     // GetSwitchName,
-    // GetTemplateListInfos,
-    SetSwitchOff { ain: String },
-    SetSwitchOn { ain: String },
-    SetSwitchToggle { ain: String },
+    GetTemplateListInfos,
+    ApplyTemplate {
+        ain: String,
+    },
+    SetSwitchOff {
+        ain: String,
+    },
+    SetSwitchOn {
+        ain: String,
+    },
+    SetSwitchToggle {
+        ain: String,
+    },
+    SetName {
+        ain: String,
+        name: String,
+    },
+    /// `param` is the raw target temperature, see
+    /// [crate::devices::ThermostatTarget::to_raw].
+    SetHkrTsoll {
+        ain: String,
+        param: u32,
+    },
+    SetLevelPercentage {
+        ain: String,
+        level: u8,
+    },
+    SetColor {
+        ain: String,
+        hue: u16,
+        saturation: u8,
+    },
+    /// `temperature` in Kelvin.
+    SetColorTemperature {
+        ain: String,
+        temperature: u16,
+    },
+    /// `target` is `open`, `close` or `stop`.
+    SetBlind {
+        ain: String,
+        target: &'static str,
+    },
+    GetTriggerListInfos,
+    SetTriggerActive {
+        ain: String,
+        active: bool,
+    },
+    /// Any `switchcmd`, see [FritzClient::raw_command].
+    Raw {
+        switchcmd: String,
+        ain: Option<String>,
+        params: Vec<(String, String)>,
+    },
 }
 
 /// Sends raw HTTP requests to the fritz box.
-pub(crate) fn request(cmd: Commands, sid: impl AsRef<str>) -> Result<String> {
+pub(crate) fn request(
+    connection: &Connection,
+    cmd: Commands,
+    sid: impl AsRef<str>,
+) -> Result<String> {
     use Commands::*;
-    let (cmd, ain) = match cmd {
-        GetDeviceListInfos => ("getdevicelistinfos", None),
-        GetBasicDeviceStats { ain } => ("getbasicdevicestats", Some(ain)),
+    let mut extra_params = Vec::new();
+    let (cmd, ain, params) = match &cmd {
+        GetDeviceListInfos => ("getdevicelistinfos", None, &[][..]),
+        GetBasicDeviceStats { ain } => ("getbasicdevicestats", Some(ain), &[][..]),
         // GetSwitchPower => "getswitchpower",
         // GetSwitchEnergy => "getswitchenergy",
         // GetSwitchName => "getswitchname",
-        // GetTemplateListInfos => "gettemplatelistinfos",
-        SetSwitchOff { ain } => ("setswitchoff", Some(ain)),
-        SetSwitchOn { ain } => ("setswitchon", Some(ain)),
-        SetSwitchToggle { ain } => ("setswitchtoggle", Some(ain)),
+        GetTemplateListInfos => ("gettemplatelistinfos", None, &[][..]),
+        ApplyTemplate { ain } => ("applytemplate", Some(ain), &[][..]),
+        SetSwitchOff { ain } => ("setswitchoff", Some(ain), &[][..]),
+        SetSwitchOn { ain } => ("setswitchon", Some(ain), &[][..]),
+        SetSwitchToggle { ain } => ("setswitchtoggle", Some(ain), &[][..]),
+        SetName { ain, name } => {
+            extra_params.push(("name".to_string(), name.clone()));
+            ("setname", Some(ain), &extra_params[..])
+        }
+        SetHkrTsoll { ain, param } => {
+            extra_params.push(("param".to_string(), param.to_string()));
+            ("sethkrtsoll", Some(ain), &extra_params[..])
+        }
+        SetLevelPercentage { ain, level } => {
+            extra_params.push(("level".to_string(), level.to_string()));
+            ("setlevelpercentage", Some(ain), &extra_params[..])
+        }
+        SetColor {
+            ain,
+            hue,
+            saturation,
+        } => {
+            extra_params.push(("hue".to_string(), hue.to_string()));
+            extra_params.push(("saturation".to_string(), saturation.to_string()));
+            extra_params.push(("duration".to_string(), "0".to_string()));
+            ("setcolor", Some(ain), &extra_params[..])
+        }
+        SetColorTemperature { ain, temperature } => {
+            extra_params.push(("temperature".to_string(), temperature.to_string()));
+            extra_params.push(("duration".to_string(), "0".to_string()));
+            ("setcolortemperature", Some(ain), &extra_params[..])
+        }
+        SetBlind { ain, target } => {
+            extra_params.push(("target".to_string(), target.to_string()));
+            ("setblind", Some(ain), &extra_params[..])
+        }
+        GetTriggerListInfos => ("gettriggerlistinfos", None, &[][..]),
+        SetTriggerActive { ain, active } => {
+            extra_params.push(("active".to_string(), (*active as u8).to_string()));
+            ("settriggeractive", Some(ain), &extra_params[..])
+        }
+        Raw {
+            switchcmd,
+            ain,
+            params,
+        } => (switchcmd.as_str(), ain.as_ref(), params.as_slice()),
     };
-    let url = "http://fritz.box/webservices/homeautoswitch.lua";
-    let mut client = Client::new()
+    let url = connection.url("webservices/homeautoswitch.lua");
+    let mut client = connection
+        .client()?
         .get(url)
         .query(&[("switchcmd", cmd), ("sid", sid.as_ref())]);
     if let Some(ain) = ain {
         client = client.query(&[("ain", ain)]);
     }
+    if !params.is_empty() {
+        client = client.query(params);
+    }
     let response = client.send()?;
     let status = response.status();
     let status_message = format!(
@@ -129,4 +308,17 @@ mod tests {
         let response = super::request_response("mühe", "foo");
         assert_eq!(response, "foo-442e12bbceabd35c66964c913a316451");
     }
+
+    #[test]
+    fn base_url() {
+        use super::base_url;
+        assert_eq!(base_url("fritz.box").unwrap(), "http://fritz.box");
+        assert_eq!(
+            base_url("192.168.178.1:8080/").unwrap(),
+            "http://192.168.178.1:8080"
+        );
+        assert_eq!(base_url("https://fritz.box/").unwrap(), "https://fritz.box");
+        assert!(base_url("").is_err());
+        assert!(base_url("ftp://fritz.box").is_err());
+    }
 }
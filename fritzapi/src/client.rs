@@ -1,25 +1,132 @@
+use crate::ain::Ain;
 use crate::api;
+use crate::devices::{BlindTarget, Color, DeviceGroup, ThermostatTarget};
 use crate::error::{FritzError, Result};
 use crate::fritz_xml;
+use crate::templates::Template;
+use crate::triggers::Trigger;
 use crate::AVMDevice;
+use std::sync::{Arc, Mutex};
+
+/// Query parameters of AHA requests that [FritzClient::raw_command] sets itself.
+const RESERVED_PARAMS: [&str; 3] = ["sid", "switchcmd", "ain"];
 
 /// The main interface to get data from the fritz box API.
+///
+/// By default the box is reached at `http://fritz.box`, use
+/// [FritzClient::with_host] for other addresses:
+///
+/// ```no_run
+/// # fn main() -> fritzapi::Result<()> {
+/// let client = fritzapi::FritzClient::new("user", "password").with_host("192.168.178.1")?;
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Clone)]
 pub struct FritzClient {
     user: String,
-    password: String,
+    password: Password,
     sid: Option<String>,
+    /// Whether a new session is started when there is none or it expired.
+    login: bool,
+    connection: api::Connection,
 }
 
 impl FritzClient {
     pub fn new(user: impl ToString, password: impl ToString) -> Self {
         FritzClient {
             user: user.to_string(),
-            password: password.to_string(),
+            password: Password::Known(password.to_string()),
             sid: None,
+            login: true,
+            connection: api::Connection::default(),
+        }
+    }
+
+    /// Like [FritzClient::new] but the password is only obtained from
+    /// `password` when the client has to log in, e.g. not at all if a session
+    /// id passed to [FritzClient::with_sid] is still valid. Clones of the
+    /// client share the password, `password` is called at most once
+    /// successfully.
+    pub fn with_password_fn(
+        user: impl ToString,
+        password: impl Fn() -> Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        FritzClient {
+            password: Password::Lazy(Arc::new(LazyPassword {
+                read: Box::new(password),
+                value: Mutex::new(None),
+            })),
+            ..FritzClient::new(user, "")
         }
     }
 
+    /// Connects to `host` instead of `fritz.box`. `host` is a host name or
+    /// address, optionally with port, or an `http://` / `https://` url. https
+    /// needs the `tls` feature.
+    pub fn with_host(mut self, host: impl AsRef<str>) -> Result<Self> {
+        self.connection.base_url = api::base_url(host.as_ref())?;
+        self.sid = None;
+        Ok(self)
+    }
+
+    /// Gives up on requests that take longer than `timeout`, e.g. when the
+    /// box is not reachable.
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connection.timeout = Some(timeout);
+        self
+    }
+
+    /// Accepts any certificate for https connections. The fritz box uses a
+    /// self-signed certificate by default, prefer
+    /// [FritzClient::add_root_certificate_pem] with the certificate exported
+    /// from the box.
+    #[cfg(feature = "tls")]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.connection.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Trusts the PEM encoded certificate `pem` for https connections.
+    #[cfg(feature = "tls")]
+    pub fn add_root_certificate_pem(mut self, pem: &[u8]) -> Result<Self> {
+        let cert = reqwest::Certificate::from_pem(pem)?;
+        self.connection.root_certificates.push(cert);
+        Ok(self)
+    }
+
+    /// Uses the session id `sid` of an earlier login, e.g. one that was cached
+    /// between program runs, instead of logging in on the first request. If
+    /// the session has expired the fritz box answers with 403 and the client
+    /// logs in again.
+    pub fn with_sid(mut self, sid: impl ToString) -> Self {
+        self.sid = Some(sid.to_string());
+        self
+    }
+
+    /// Never logs in: requests only use the session id passed to
+    /// [FritzClient::with_sid] and fail with [FritzError::Forbidden] once it
+    /// expired. For callers that don't know the password, a login with a wrong
+    /// password makes the fritz box refuse logins for a while.
+    pub fn without_login(mut self) -> Self {
+        self.login = false;
+        self
+    }
+
+    /// The current session id, `None` before the first request.
+    pub fn sid(&self) -> Option<&str> {
+        self.sid.as_deref()
+    }
+
+    /// The url of the fritz box, e.g. `http://fritz.box`.
+    pub fn host(&self) -> &str {
+        &self.connection.base_url
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
     /// Returns list of all smart home devices. See [devices::AVMDevice].
     pub fn list_devices(&mut self) -> Result<Vec<AVMDevice>> {
         let xml = self.request(api::Commands::GetDeviceListInfos)?;
@@ -30,30 +137,196 @@ impl FritzClient {
             .collect())
     }
 
-    pub fn device_stats(&mut self, ain: impl ToString) -> Result<Vec<crate::stats::DeviceStats>> {
-        let ain = ain.to_string();
+    /// Returns the device groups configured on the fritz box. See
+    /// [crate::devices::DeviceGroup::contains] to find the member devices.
+    pub fn list_groups(&mut self) -> Result<Vec<DeviceGroup>> {
+        let xml = self.request(api::Commands::GetDeviceListInfos)?;
+        fritz_xml::parse_device_groups(xml)
+    }
+
+    /// [FritzClient::list_devices] and [FritzClient::list_groups] with a
+    /// single request.
+    pub fn list_devices_and_groups(&mut self) -> Result<(Vec<AVMDevice>, Vec<DeviceGroup>)> {
+        let xml = self.request(api::Commands::GetDeviceListInfos)?;
+        let (devices, groups) = fritz_xml::parse_device_list(xml)?;
+        let devices = devices
+            .into_iter()
+            .map(AVMDevice::from_xml_device)
+            .collect();
+        Ok((devices, groups))
+    }
+
+    pub fn device_stats(&mut self, ain: &Ain) -> Result<Vec<crate::stats::DeviceStats>> {
+        let ain = ain.as_str().to_string();
         let xml = self.request(api::Commands::GetBasicDeviceStats { ain })?;
         fritz_xml::parse_device_stats(xml)
     }
 
-    pub fn turn_on(&mut self, ain: impl ToString) -> Result<()> {
-        let ain = ain.to_string();
-        self.request(api::Commands::SetSwitchOn { ain })?;
+    /// Turns the switch `ain` on. Returns the new state as reported by the
+    /// fritz box, fails with [FritzError::DeviceLocked] if the switch stayed
+    /// off.
+    pub fn turn_on(&mut self, ain: &Ain) -> Result<bool> {
+        let response = self.request(api::Commands::SetSwitchOn {
+            ain: ain.as_str().to_string(),
+        })?;
+        match fritz_xml::parse_switch_state(ain, &response)? {
+            false => Err(FritzError::DeviceLocked(ain.clone())),
+            on => Ok(on),
+        }
+    }
+
+    /// Turns the switch `ain` off. Returns the new state as reported by the
+    /// fritz box, fails with [FritzError::DeviceLocked] if the switch stayed
+    /// on.
+    pub fn turn_off(&mut self, ain: &Ain) -> Result<bool> {
+        let response = self.request(api::Commands::SetSwitchOff {
+            ain: ain.as_str().to_string(),
+        })?;
+        match fritz_xml::parse_switch_state(ain, &response)? {
+            true => Err(FritzError::DeviceLocked(ain.clone())),
+            on => Ok(on),
+        }
+    }
+
+    /// Toggles the switch `ain`. Returns the new state as reported by the
+    /// fritz box.
+    pub fn toggle(&mut self, ain: &Ain) -> Result<bool> {
+        let response = self.request(api::Commands::SetSwitchToggle {
+            ain: ain.as_str().to_string(),
+        })?;
+        fritz_xml::parse_switch_state(ain, &response)
+    }
+
+    /// Renames the device `ain`. The name is checked with
+    /// [crate::devices::validate_name] before it is sent. Returns the name as
+    /// reported back by the fritz box.
+    pub fn set_name(&mut self, ain: &Ain, name: impl AsRef<str>) -> Result<String> {
+        let ain = ain.as_str().to_string();
+        let name = name.as_ref();
+        crate::devices::validate_name(name)?;
+        let name = name.to_string();
+        let response = self.request(api::Commands::SetName { ain, name })?;
+        Ok(response.trim().to_string())
+    }
+
+    /// Sets the target temperature of the radiator thermostat `ain`.
+    pub fn set_thermostat_target(&mut self, ain: &Ain, target: ThermostatTarget) -> Result<()> {
+        let ain = ain.as_str().to_string();
+        let param = target.to_raw();
+        self.request(api::Commands::SetHkrTsoll { ain, param })?;
+        Ok(())
+    }
+
+    /// Returns the templates configured on the fritz box.
+    pub fn list_templates(&mut self) -> Result<Vec<Template>> {
+        let xml = self.request(api::Commands::GetTemplateListInfos)?;
+        fritz_xml::parse_templates(xml)
+    }
+
+    /// Applies the template with the identifier `ain`, see
+    /// [Template::identifier].
+    pub fn apply_template(&mut self, ain: &Ain) -> Result<()> {
+        let ain = ain.as_str().to_string();
+        self.request(api::Commands::ApplyTemplate { ain })?;
+        Ok(())
+    }
+
+    /// Sets the brightness of a dimmable light or the position of a blind to
+    /// `percent` (0 to 100, larger values are sent as 100).
+    pub fn set_level_percentage(&mut self, ain: &Ain, percent: u8) -> Result<()> {
+        let ain = ain.as_str().to_string();
+        let level = percent.min(100);
+        self.request(api::Commands::SetLevelPercentage { ain, level })?;
+        Ok(())
+    }
+
+    /// Sets the color of the light `ain`.
+    pub fn set_color(&mut self, ain: &Ain, color: Color) -> Result<()> {
+        let ain = ain.as_str().to_string();
+        self.request(api::Commands::SetColor {
+            ain,
+            hue: color.hue,
+            saturation: color.saturation,
+        })?;
+        Ok(())
+    }
+
+    /// Sets the white color temperature of the light `ain` in Kelvin, see
+    /// [crate::devices::light::validate_color_temperature].
+    pub fn set_color_temperature(&mut self, ain: &Ain, kelvin: u16) -> Result<()> {
+        crate::devices::light::validate_color_temperature(kelvin)?;
+        let ain = ain.as_str().to_string();
+        self.request(api::Commands::SetColorTemperature {
+            ain,
+            temperature: kelvin,
+        })?;
         Ok(())
     }
 
-    pub fn turn_off(&mut self, ain: impl ToString) -> Result<()> {
-        let ain = ain.to_string();
-        self.request(api::Commands::SetSwitchOff { ain })?;
+    /// Opens, closes or stops the blind `ain`.
+    pub fn set_blind(&mut self, ain: &Ain, target: BlindTarget) -> Result<()> {
+        let ain = ain.as_str().to_string();
+        let target = target.as_str();
+        self.request(api::Commands::SetBlind { ain, target })?;
         Ok(())
     }
 
-    pub fn toggle(&mut self, ain: impl ToString) -> Result<()> {
-        let ain = ain.to_string();
-        self.request(api::Commands::SetSwitchToggle { ain })?;
+    /// Returns the triggers configured on the fritz box.
+    pub fn list_triggers(&mut self) -> Result<Vec<Trigger>> {
+        let xml = self.request(api::Commands::GetTriggerListInfos)?;
+        fritz_xml::parse_triggers(xml)
+    }
+
+    /// Enables or disables the trigger with the identifier `ain`, see
+    /// [Trigger::identifier].
+    pub fn set_trigger_active(&mut self, ain: &Ain, active: bool) -> Result<()> {
+        let ain = ain.as_str().to_string();
+        self.request(api::Commands::SetTriggerActive { ain, active })?;
         Ok(())
     }
 
+    /// Sends an arbitrary `switchcmd` to the AHA interface and returns the raw
+    /// response body. Useful for commands that are not (yet) wrapped by this
+    /// client. The request uses the same session handling as all other
+    /// methods, i.e. it will log in if needed and retry once on a 403.
+    /// `extra_params` must not contain `sid`, `switchcmd` or `ain`, these are
+    /// set by the client.
+    ///
+    /// ```no_run
+    /// # fn main() -> fritzapi::Result<()> {
+    /// let mut client = fritzapi::FritzClient::new("user", "password");
+    /// let ain: fritzapi::Ain = "11630 0123723".parse()?;
+    /// let name = client.raw_command("getswitchname", Some(&ain), &[])?;
+    /// let xml = client.raw_command("gettemplatelistinfos", None, &[])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_command(
+        &mut self,
+        switchcmd: impl ToString,
+        ain: Option<&Ain>,
+        extra_params: &[(&str, &str)],
+    ) -> Result<String> {
+        if let Some((key, _)) = extra_params.iter().find(|(key, _)| {
+            RESERVED_PARAMS
+                .iter()
+                .any(|reserved| key.eq_ignore_ascii_case(reserved))
+        }) {
+            return Err(FritzError::InvalidParameter(format!(
+                "`{}` is set by the client and cannot be passed as extra parameter",
+                key
+            )));
+        }
+        self.request(api::Commands::Raw {
+            switchcmd: switchcmd.to_string(),
+            ain: ain.map(|ain| ain.as_str().to_string()),
+            params: extra_params
+                .iter()
+                .map(|(key, val)| (key.to_string(), val.to_string()))
+                .collect(),
+        })
+    }
+
     // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 
     /// Triggers a higher refresh rate for smart plugs (Fritz!Dect 2xx).
@@ -86,23 +359,37 @@ impl FritzClient {
     /// ```
     ///
     /// This function performs basically the same request as the `curl` command above.
+    ///
+    /// An expired session makes the box answer with a redirect to the login
+    /// page or 403, the client logs in again then like for other requests.
     pub fn trigger_high_refresh_rate(&mut self) -> Result<()> {
-        let sid = match self.sid.clone().or_else(|| self.update_sid().ok()) {
-            None => return Err(FritzError::Forbidden),
+        self.trigger_high_refresh_rate_attempt(0)
+    }
+
+    fn trigger_high_refresh_rate_attempt(&mut self, request_count: usize) -> Result<()> {
+        let sid = match self.sid.clone() {
             Some(sid) => sid,
+            None => self.update_sid()?,
         };
         let mut params = std::collections::HashMap::new();
         params.insert("sid", sid.as_ref());
         params.insert("c", "smarthome");
         params.insert("a", "getData");
-        let client = reqwest::blocking::Client::builder()
+        let client = self
+            .connection
+            .client_builder()
             .redirect(reqwest::redirect::Policy::none())
             .build()?
-            .post("http://fritz.box/myfritz/api/data.lua")
+            .post(self.connection.url("myfritz/api/data.lua"))
             .form(&params);
         let response = client.send()?;
         let status = response.status();
 
+        let expired = status == reqwest::StatusCode::FORBIDDEN || status.is_redirection();
+        if expired && request_count == 0 {
+            self.update_sid()?;
+            return self.trigger_high_refresh_rate_attempt(request_count + 1);
+        }
         if status != 200 {
             return Err(FritzError::TriggerHighRefreshRateError(status));
         }
@@ -112,7 +399,11 @@ impl FritzClient {
     // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 
     fn update_sid(&mut self) -> Result<String> {
-        let sid = api::get_sid(&self.user, &self.password)?;
+        if !self.login {
+            return Err(FritzError::Forbidden);
+        }
+        let password = self.password.get()?;
+        let sid = api::get_sid(&self.connection, &self.user, password)?;
         self.sid = Some(sid.clone());
         Ok(sid)
     }
@@ -124,16 +415,115 @@ impl FritzClient {
 
     #[instrument(level = "trace", skip(self))]
     fn request_attempt(&mut self, cmd: api::Commands, request_count: usize) -> Result<String> {
-        let sid = match self.sid.clone().or_else(|| self.update_sid().ok()) {
-            None => return Err(FritzError::Forbidden),
+        let sid = match self.sid.clone() {
             Some(sid) => sid,
+            None => self.update_sid()?,
         };
-        match api::request(cmd.clone(), sid) {
+        match api::request(&self.connection, cmd.clone(), sid) {
             Err(FritzError::Forbidden) if request_count == 0 => {
-                let _ = self.update_sid();
+                self.update_sid()?;
                 self.request_attempt(cmd, request_count + 1)
             }
             result => result,
         }
     }
 }
+
+/// The password of a [FritzClient], either given directly or obtained on the
+/// first login.
+#[derive(Clone)]
+enum Password {
+    Known(String),
+    Lazy(Arc<LazyPassword>),
+}
+
+struct LazyPassword {
+    read: Box<dyn Fn() -> Result<String> + Send + Sync>,
+    value: Mutex<Option<String>>,
+}
+
+impl Password {
+    fn get(&self) -> Result<String> {
+        match self {
+            Password::Known(password) => Ok(password.clone()),
+            Password::Lazy(lazy) => {
+                // hold the lock while reading so that clones used by other
+                // threads don't ask for the password a second time
+                let mut value = lazy.value.lock().unwrap_or_else(|err| err.into_inner());
+                if let Some(password) = value.as_ref() {
+                    return Ok(password.clone());
+                }
+                let password = (lazy.read)()?;
+                *value = Some(password.clone());
+                Ok(password)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_login() {
+        // fails before any request is sent
+        let mut client = FritzClient::new("user", "")
+            .with_host("192.0.2.1")
+            .unwrap()
+            .without_login();
+        assert!(matches!(client.list_devices(), Err(FritzError::Forbidden)));
+        assert_eq!(client.sid(), None);
+    }
+
+    #[test]
+    fn raw_command_reserved_params() {
+        // fails before any request is sent
+        let mut client = FritzClient::new("user", "")
+            .with_host("192.0.2.1")
+            .unwrap()
+            .without_login();
+        for key in ["sid", "switchcmd", "ain", "SID"] {
+            assert!(matches!(
+                client.raw_command("getswitchname", None, &[("param", "1"), (key, "x")]),
+                Err(FritzError::InvalidParameter(_))
+            ));
+        }
+        assert!(matches!(
+            client.raw_command("getswitchname", None, &[("param", "1")]),
+            Err(FritzError::Forbidden)
+        ));
+    }
+
+    #[test]
+    fn password_fn() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let client = FritzClient::with_password_fn("user", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err(FritzError::Password("no terminal".to_string()))
+        })
+        .with_host("192.0.2.1")
+        .unwrap();
+
+        // not asked for before a login is needed
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        // the error is reported before any request is sent
+        assert!(matches!(
+            client.clone().list_devices(),
+            Err(FritzError::Password(_))
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // clones share the password once it was read
+        let counter = calls.clone();
+        let client = FritzClient::with_password_fn("user", move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok("secret".to_string())
+        });
+        assert_eq!(client.clone().password.get().unwrap(), "secret");
+        assert_eq!(client.password.get().unwrap(), "secret");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
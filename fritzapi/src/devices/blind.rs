@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{FritzError, Result};
+
+/// What a blind ("Rollladen") should do, see
+/// [crate::FritzClient::set_blind].
+///
+/// ```
+/// use fritzapi::BlindTarget;
+/// assert_eq!("Open".parse::<BlindTarget>().unwrap(), BlindTarget::Open);
+/// assert!("half".parse::<BlindTarget>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlindTarget {
+    Open,
+    Close,
+    Stop,
+}
+
+impl BlindTarget {
+    /// The `target` parameter of `setblind`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlindTarget::Open => "open",
+            BlindTarget::Close => "close",
+            BlindTarget::Stop => "stop",
+        }
+    }
+}
+
+impl std::str::FromStr for BlindTarget {
+    type Err = FritzError;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "open" | "up" => Ok(BlindTarget::Open),
+            "close" | "down" => Ok(BlindTarget::Close),
+            "stop" => Ok(BlindTarget::Stop),
+            _ => Err(FritzError::ParserError(format!(
+                "invalid blind target {:?}, expected open, close or stop",
+                input
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for BlindTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -1,5 +1,5 @@
-use super::{AVMDevice, Device, FritzDect2XX, PowerMeter, Switch, Temperature};
-use crate::error::Result;
+use super::{AVMDevice, Device, FritzDect2XX, PowerMeter, Switch, SwitchMode, Temperature};
+use crate::error::{FritzError, Result};
 use crate::FritzClient;
 
 impl AVMDevice {
@@ -7,9 +7,18 @@ impl AVMDevice {
         match device {
             Device {
                 identifier,
+                id,
                 productname,
                 name,
-                switch: Some(Switch { state, .. }),
+                present,
+                txbusy,
+                switch:
+                    Some(Switch {
+                        state,
+                        lock,
+                        devicelock,
+                        mode,
+                    }),
                 powermeter:
                     Some(PowerMeter {
                         energy,
@@ -24,9 +33,15 @@ impl AVMDevice {
             {
                 AVMDevice::FritzDect2XX(FritzDect2XX {
                     identifier,
+                    id,
                     productname,
                     name,
+                    present,
+                    txbusy,
                     on: state,
+                    lock,
+                    devicelock,
+                    mode: SwitchMode::from(mode.as_str()),
                     millivolts: voltage,
                     milliwatts: power,
                     energy_in_watt_h: energy,
@@ -45,15 +60,49 @@ impl AVMDevice {
         client.device_stats(self.id())
     }
 
-    pub fn turn_on(&mut self, client: &mut FritzClient) -> Result<()> {
-        client.turn_on(self.id())
+    /// Turns the device on, see [FritzClient::turn_on]. Updates the cached
+    /// state of the device with the state reported by the fritz box. Fails
+    /// with [FritzError::DeviceNotPresent] without sending a request if the
+    /// device is known to be absent.
+    pub fn turn_on(&mut self, client: &mut FritzClient) -> Result<bool> {
+        self.ensure_present()?;
+        let on = client.turn_on(self.id())?;
+        self.set_on(on);
+        Ok(on)
+    }
+
+    /// Turns the device off, see [FritzClient::turn_off].
+    pub fn turn_off(&mut self, client: &mut FritzClient) -> Result<bool> {
+        self.ensure_present()?;
+        let on = client.turn_off(self.id())?;
+        self.set_on(on);
+        Ok(on)
+    }
+
+    /// Toggles the device, see [FritzClient::toggle].
+    pub fn toggle(&mut self, client: &mut FritzClient) -> Result<bool> {
+        self.ensure_present()?;
+        let on = client.toggle(self.id())?;
+        self.set_on(on);
+        Ok(on)
     }
 
-    pub fn turn_off(&mut self, client: &mut FritzClient) -> Result<()> {
-        client.turn_off(self.id())
+    fn ensure_present(&self) -> Result<()> {
+        if self.is_present() {
+            Ok(())
+        } else {
+            Err(FritzError::DeviceNotPresent(self.id().clone()))
+        }
     }
 
-    pub fn toggle(&mut self, client: &mut FritzClient) -> Result<()> {
-        client.toggle(self.id())
+    fn set_on(&mut self, state: bool) {
+        match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { on, .. }) => *on = state,
+            AVMDevice::Other(Device {
+                switch: Some(switch),
+                ..
+            }) => switch.state = state,
+            AVMDevice::Other(_) => {}
+        }
     }
 }
@@ -1,13 +1,37 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+use super::SwitchMode;
+use crate::ain::Ain;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FritzDect2XX {
-    pub identifier: String,
+    #[serde(deserialize_with = "crate::ain::deserialize_lenient")]
+    pub identifier: Ain,
+    /// Internal id, see [super::AVMDevice::internal_id].
+    pub id: String,
     pub name: String,
     pub productname: String,
+    /// Whether the device is connected to the fritz box. The readings of an
+    /// absent device are stale, they are the last values the box received.
+    pub present: bool,
+    /// Whether a command to the device is still pending.
+    pub txbusy: bool,
     pub on: bool,
+    /// Switching via UI / API is locked.
+    pub lock: bool,
+    /// The button on the device is locked.
+    pub devicelock: bool,
+    pub mode: SwitchMode,
     pub millivolts: u32,
     pub milliwatts: u32,
     pub energy_in_watt_h: u32,
     pub celsius: f32,
 }
+
+impl FritzDect2XX {
+    /// Readings (on, millivolts, milliwatts, ...) are not current when the device
+    /// is not present.
+    pub fn has_stale_readings(&self) -> bool {
+        !self.present
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{FritzError, Result};
+
+/// Lowest color temperature in Kelvin a light accepts.
+pub const MIN_COLOR_TEMPERATURE: u16 = 2700;
+/// Highest color temperature in Kelvin a light accepts.
+pub const MAX_COLOR_TEMPERATURE: u16 = 6500;
+
+/// Color of a light (e.g. FRITZ!DECT 500) for `setcolor`: the hue in degrees
+/// (0 to 359) and the saturation (0 to 255).
+///
+/// ```
+/// use fritzapi::Color;
+/// assert_eq!("120,200".parse::<Color>().unwrap(), Color { hue: 120, saturation: 200 });
+/// assert!("360,200".parse::<Color>().is_err());
+/// assert!("120".parse::<Color>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color {
+    pub hue: u16,
+    pub saturation: u8,
+}
+
+impl std::str::FromStr for Color {
+    type Err = FritzError;
+
+    /// `HUE,SATURATION`, e.g. `35,214`.
+    fn from_str(input: &str) -> Result<Self> {
+        let err = || {
+            FritzError::ParserError(format!(
+                "invalid color {:?}, expected HUE,SATURATION with a hue from 0 to 359 and a saturation from 0 to 255",
+                input
+            ))
+        };
+        let (hue, saturation) = input.split_once(',').ok_or_else(err)?;
+        let hue = hue.trim().parse::<u16>().map_err(|_| err())?;
+        let saturation = saturation.trim().parse::<u8>().map_err(|_| err())?;
+        if hue > 359 {
+            return Err(err());
+        }
+        Ok(Color { hue, saturation })
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.hue, self.saturation)
+    }
+}
+
+/// Checks that `kelvin` is a color temperature a light accepts.
+pub fn validate_color_temperature(kelvin: u16) -> Result<()> {
+    if !(MIN_COLOR_TEMPERATURE..=MAX_COLOR_TEMPERATURE).contains(&kelvin) {
+        return Err(FritzError::ParserError(format!(
+            "color temperature {} K is not between {} and {} K",
+            kelvin, MIN_COLOR_TEMPERATURE, MAX_COLOR_TEMPERATURE
+        )));
+    }
+    Ok(())
+}
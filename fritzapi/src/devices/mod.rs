@@ -1,11 +1,46 @@
+pub mod blind;
 #[cfg(not(target_family = "wasm"))]
 mod device_impl;
 pub mod fritz_dect_2xx;
+pub mod light;
+pub mod thermostat;
 
+pub use blind::BlindTarget;
 pub use fritz_dect_2xx::FritzDect2XX;
+pub use light::Color;
 use serde::{Deserialize, Deserializer, Serialize};
+pub use thermostat::{Hkr, ThermostatTarget};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+use crate::ain::Ain;
+use crate::error::{FritzError, Result};
+
+/// Maximum number of characters the fritz box accepts for a device name.
+pub const MAX_NAME_LEN: usize = 40;
+
+/// Checks that `name` can be used as device name via `setname`: It must not be
+/// blank, must be at most [MAX_NAME_LEN] characters long and must not contain
+/// control characters.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(FritzError::InvalidName("name is empty".to_string()));
+    }
+    let len = name.chars().count();
+    if len > MAX_NAME_LEN {
+        return Err(FritzError::InvalidName(format!(
+            "{:?} has {} characters, at most {} are allowed",
+            name, len, MAX_NAME_LEN
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(FritzError::InvalidName(format!(
+            "{:?} contains control characters",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
 pub enum AVMDevice {
     FritzDect2XX(FritzDect2XX),
@@ -35,13 +70,22 @@ impl std::fmt::Display for AVMDevice {
 }
 
 impl AVMDevice {
-    pub fn id(&self) -> &str {
+    pub fn id(&self) -> &Ain {
         match self {
             AVMDevice::FritzDect2XX(dev @ FritzDect2XX { .. }) => &dev.identifier,
             AVMDevice::Other(dev) => &dev.identifier,
         }
     }
 
+    /// The internal device id of the fritz box, used to reference group
+    /// members. Use [AVMDevice::id] to address the device.
+    pub fn internal_id(&self) -> &str {
+        match self {
+            AVMDevice::FritzDect2XX(dev @ FritzDect2XX { .. }) => &dev.id,
+            AVMDevice::Other(dev) => &dev.id,
+        }
+    }
+
     pub fn name(&self) -> &str {
         match self {
             AVMDevice::FritzDect2XX(dev @ FritzDect2XX { .. }) => &dev.name,
@@ -56,16 +100,86 @@ impl AVMDevice {
         }
     }
 
+    /// Whether the device is connected to the fritz box.
+    pub fn is_present(&self) -> bool {
+        match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { present, .. }) => *present,
+            AVMDevice::Other(Device { present, .. }) => *present,
+        }
+    }
+
+    /// Whether a command sent to the device has not been transmitted yet.
+    pub fn is_tx_busy(&self) -> bool {
+        match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { txbusy, .. }) => *txbusy,
+            AVMDevice::Other(Device { txbusy, .. }) => *txbusy,
+        }
+    }
+
+    /// Whether the readings of the device are outdated because it is not
+    /// present.
+    pub fn has_stale_readings(&self) -> bool {
+        !self.is_present()
+    }
+
+    /// Whether the device is switched on. Absent devices are never on.
     pub fn is_on(&self) -> bool {
         match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { present: false, .. }) => false,
             AVMDevice::FritzDect2XX(FritzDect2XX { on, .. }) => *on,
             // TODO
             AVMDevice::Other(_) => false,
         }
     }
 
+    /// Whether switching via UI / API is locked. A locked device ignores
+    /// [crate::FritzClient::turn_on] and friends.
+    pub fn is_locked(&self) -> bool {
+        match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { lock, .. }) => *lock,
+            AVMDevice::Other(Device {
+                switch: Some(Switch { lock, .. }),
+                ..
+            }) => *lock,
+            AVMDevice::Other(_) => false,
+        }
+    }
+
+    /// Whether the button on the device itself is locked.
+    pub fn is_device_locked(&self) -> bool {
+        match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { devicelock, .. }) => *devicelock,
+            AVMDevice::Other(Device {
+                switch: Some(Switch { devicelock, .. }),
+                ..
+            }) => *devicelock,
+            AVMDevice::Other(_) => false,
+        }
+    }
+
+    pub fn switch_mode(&self) -> Option<SwitchMode> {
+        match self {
+            AVMDevice::FritzDect2XX(FritzDect2XX { mode, .. }) => Some(*mode),
+            AVMDevice::Other(Device {
+                switch: Some(Switch { mode, .. }),
+                ..
+            }) => Some(SwitchMode::from(mode.as_str())),
+            AVMDevice::Other(_) => None,
+        }
+    }
+
+    /// The thermostat of the device, `None` if it is no radiator
+    /// controller.
+    pub fn thermostat(&self) -> Option<&Hkr> {
+        match self {
+            AVMDevice::FritzDect2XX(_) => None,
+            AVMDevice::Other(dev) => dev.hkr.as_ref(),
+        }
+    }
+
     pub fn state(&self) -> &str {
         match self {
+            _ if !self.is_present() => "not present",
             AVMDevice::FritzDect2XX(FritzDect2XX { on: true, .. }) => "on",
             AVMDevice::FritzDect2XX(FritzDect2XX { on: false, .. }) => "off",
             AVMDevice::Other(_) => "",
@@ -80,9 +194,10 @@ pub enum DeviceOrGroup {
     Group(DeviceGroup),
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Device {
-    pub identifier: String,
+    #[serde(deserialize_with = "crate::ain::deserialize_lenient")]
+    pub identifier: Ain,
     pub id: String,
     pub functionbitmask: String,
     pub fwversion: String,
@@ -97,12 +212,15 @@ pub struct Device {
     pub simpleonoff: Option<SimpleOnOff>,
     pub powermeter: Option<PowerMeter>,
     pub temperature: Option<Temperature>,
+    #[serde(default)]
+    pub hkr: Option<Hkr>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DeviceGroup {
     pub synchronized: bool,
-    pub identifier: String,
+    #[serde(deserialize_with = "crate::ain::deserialize_lenient")]
+    pub identifier: Ain,
     pub id: String,
     pub functionbitmask: String,
     pub fwversion: String,
@@ -113,7 +231,34 @@ pub struct DeviceGroup {
     pub switch: Option<Switch>,
     pub simpleonoff: Option<SimpleOnOff>,
     pub powermeter: Option<PowerMeter>,
-    // groupinfo: ... // TODO
+    pub groupinfo: Option<GroupInfo>,
+}
+
+impl DeviceGroup {
+    /// Internal ids ([Device::id]) of the devices in this group.
+    pub fn member_ids(&self) -> Vec<&str> {
+        match &self.groupinfo {
+            Some(info) => info
+                .members
+                .split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether `device` is a member of this group.
+    pub fn contains(&self, device: &AVMDevice) -> bool {
+        self.member_ids().contains(&device.internal_id())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GroupInfo {
+    pub masterdeviceid: String,
+    /// Comma separated internal ids of the member devices.
+    pub members: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -123,20 +268,57 @@ pub struct DeviceList {
     // pub devices: Vec<Device>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Switch {
+    /// Empty (= `false`) when unknown or on error.
+    #[serde(deserialize_with = "deserialize_maybe_bool")]
     pub state: bool,
+    #[serde(deserialize_with = "deserialize_maybe_bool")]
     pub lock: bool,
+    #[serde(deserialize_with = "deserialize_maybe_bool")]
     pub devicelock: bool,
     pub mode: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Whether a switch is controlled manually or by a timer / schedule
+/// (`mode` of [Switch]).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchMode {
+    /// "manuell"
+    Manual,
+    /// "auto"
+    Auto,
+    Unknown,
+}
+
+impl From<&str> for SwitchMode {
+    fn from(mode: &str) -> Self {
+        match mode.trim() {
+            "manuell" => SwitchMode::Manual,
+            "auto" => SwitchMode::Auto,
+            _ => SwitchMode::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for SwitchMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwitchMode::Manual => write!(f, "manual"),
+            SwitchMode::Auto => write!(f, "auto"),
+            SwitchMode::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SimpleOnOff {
+    #[serde(deserialize_with = "deserialize_maybe_bool")]
     pub state: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PowerMeter {
     /// Wert in 0,001 V (aktuelle Spannung, wird etwa alle 2 Minuten aktualisiert)
     #[serde(deserialize_with = "deserialize_maybe_u32")]
@@ -151,19 +333,130 @@ pub struct PowerMeter {
 
 /// celsius: Wert in 0,1 °C, negative und positive Werte möglich
 /// offset: Wert in 0,1 °C, negative und positive Werte möglich
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Temperature {
     pub celsius: String,
     pub offset: String,
 }
 
+/// A value as the fritz box writes it, the text of an element or attribute
+/// that may be empty, or as it is serialized e.g. to JSON.
+enum Scalar {
+    Bool(bool),
+    Number(u64),
+    Text(String),
+}
+
+struct ScalarVisitor;
+
+impl<'de> serde::de::Visitor<'de> for ScalarVisitor {
+    type Value = Scalar;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a boolean, a number or a string")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> std::result::Result<Scalar, E> {
+        Ok(Scalar::Bool(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> std::result::Result<Scalar, E> {
+        Ok(Scalar::Number(value))
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, value: i64) -> std::result::Result<Scalar, E> {
+        u64::try_from(value)
+            .map(Scalar::Number)
+            .map_err(|_| E::custom(format!("invalid number {}", value)))
+    }
+
+    fn visit_str<E>(self, value: &str) -> std::result::Result<Scalar, E> {
+        Ok(Scalar::Text(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> std::result::Result<Scalar, E> {
+        Ok(Scalar::Text(value))
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Scalar, E> {
+        Ok(Scalar::Text(String::new()))
+    }
+
+    /// serde-xml-rs passes elements as map, the text is the `$value` entry
+    /// and missing for empty elements.
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<Scalar, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut text = String::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value: String = map.next_value()?;
+            if key == "$value" {
+                text = value;
+            }
+        }
+        Ok(Scalar::Text(text))
+    }
+}
+
+/// `0` / `1` as written by the fritz box, empty (= `false`) when unknown, or
+/// a boolean.
+pub(crate) fn deserialize_maybe_bool<'de, D>(d: D) -> std::result::Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match d.deserialize_any(ScalarVisitor)? {
+        Scalar::Bool(value) => Ok(value),
+        Scalar::Number(0) => Ok(false),
+        Scalar::Number(1) => Ok(true),
+        Scalar::Text(text) => match text.trim() {
+            "" | "0" | "false" => Ok(false),
+            "1" | "true" => Ok(true),
+            other => Err(serde::de::Error::custom(format!(
+                "invalid boolean {:?}",
+                other
+            ))),
+        },
+        Scalar::Number(other) => Err(serde::de::Error::custom(format!(
+            "invalid boolean {}",
+            other
+        ))),
+    }
+}
+
+/// A number as written by the fritz box, empty (= 0) when unknown.
 fn deserialize_maybe_u32<'de, D>(d: D) -> std::result::Result<u32, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s = String::deserialize(d)?;
-    match &s[..] {
-        "" => Ok(0),
-        _ => Ok(s.parse::<u32>().unwrap()),
+    let number = match d.deserialize_any(ScalarVisitor)? {
+        Scalar::Number(number) => number,
+        Scalar::Text(text) if text.trim().is_empty() => 0,
+        Scalar::Text(text) => text
+            .trim()
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid number {:?}", text)))?,
+        Scalar::Bool(value) => {
+            return Err(serde::de::Error::custom(format!(
+                "invalid number {}",
+                value
+            )))
+        }
+    };
+    u32::try_from(number)
+        .map_err(|_| serde::de::Error::custom(format!("number {} is too large", number)))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn validate_name() {
+        assert!(super::validate_name("FRITZ!DECT 200 Laufband").is_ok());
+        assert!(super::validate_name("Küche / Kühlschrank").is_ok());
+        assert!(super::validate_name(&"ä".repeat(40)).is_ok());
+        assert!(super::validate_name(&"a".repeat(41)).is_err());
+        assert!(super::validate_name("").is_err());
+        assert!(super::validate_name("   ").is_err());
+        assert!(super::validate_name("foo\nbar").is_err());
     }
 }
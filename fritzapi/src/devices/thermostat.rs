@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+use super::{deserialize_maybe_bool, deserialize_maybe_u32};
+use crate::error::{FritzError, Result};
+
+/// Lowest target temperature a thermostat accepts.
+pub const MIN_CELSIUS: f32 = 8.0;
+/// Highest target temperature a thermostat accepts.
+pub const MAX_CELSIUS: f32 = 28.0;
+
+const RAW_OFF: u32 = 253;
+const RAW_ON: u32 = 254;
+
+/// Radiator thermostat ("Heizkörperregler", e.g. FRITZ!DECT 301) of a
+/// [super::Device]. The temperatures are raw values in 0.5 °C steps, see
+/// [Hkr::current_celsius] and [Hkr::target].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Hkr {
+    /// Measured temperature.
+    #[serde(deserialize_with = "deserialize_maybe_u32")]
+    pub tist: u32,
+    /// Target temperature.
+    #[serde(deserialize_with = "deserialize_maybe_u32")]
+    pub tsoll: u32,
+    /// Reduced ("Absenk") temperature.
+    #[serde(default, deserialize_with = "deserialize_maybe_u32")]
+    pub absenk: u32,
+    /// Comfort temperature.
+    #[serde(default, deserialize_with = "deserialize_maybe_u32")]
+    pub komfort: u32,
+    #[serde(default, deserialize_with = "deserialize_maybe_bool")]
+    pub lock: bool,
+    #[serde(default, deserialize_with = "deserialize_maybe_bool")]
+    pub devicelock: bool,
+}
+
+impl Hkr {
+    /// The measured temperature, `None` if unknown.
+    pub fn current_celsius(&self) -> Option<f32> {
+        match self.tist {
+            0 => None,
+            raw => Some(raw as f32 / 2.0),
+        }
+    }
+
+    /// The temperature the thermostat regulates to.
+    pub fn target(&self) -> Option<ThermostatTarget> {
+        ThermostatTarget::from_raw(self.tsoll)
+    }
+
+    pub fn comfort(&self) -> Option<ThermostatTarget> {
+        ThermostatTarget::from_raw(self.komfort)
+    }
+
+    pub fn reduced(&self) -> Option<ThermostatTarget> {
+        ThermostatTarget::from_raw(self.absenk)
+    }
+}
+
+/// Target of a thermostat: a temperature between [MIN_CELSIUS] and
+/// [MAX_CELSIUS] in 0.5 °C steps, or off / fully open.
+///
+/// ```
+/// use fritzapi::ThermostatTarget;
+/// assert_eq!("21.5".parse::<ThermostatTarget>().unwrap(), ThermostatTarget::Celsius(21.5));
+/// assert_eq!("off".parse::<ThermostatTarget>().unwrap(), ThermostatTarget::Off);
+/// assert!("30".parse::<ThermostatTarget>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThermostatTarget {
+    Off,
+    On,
+    Celsius(f32),
+}
+
+impl ThermostatTarget {
+    /// `value` rounded to 0.5 °C, fails if it is outside of the accepted
+    /// range.
+    pub fn celsius(value: f32) -> Result<Self> {
+        if !(MIN_CELSIUS..=MAX_CELSIUS).contains(&value) {
+            return Err(FritzError::ParserError(format!(
+                "thermostat temperature {} is not between {} and {} °C",
+                value, MIN_CELSIUS, MAX_CELSIUS
+            )));
+        }
+        Ok(ThermostatTarget::Celsius((value * 2.0).round() / 2.0))
+    }
+
+    /// Parses the `tsoll` / `komfort` / `absenk` values of the AHA interface.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            RAW_OFF => Some(ThermostatTarget::Off),
+            RAW_ON => Some(ThermostatTarget::On),
+            16..=56 => Some(ThermostatTarget::Celsius(raw as f32 / 2.0)),
+            _ => None,
+        }
+    }
+
+    /// The value for `sethkrtsoll`.
+    pub fn to_raw(self) -> u32 {
+        match self {
+            ThermostatTarget::Off => RAW_OFF,
+            ThermostatTarget::On => RAW_ON,
+            ThermostatTarget::Celsius(celsius) => {
+                (celsius.clamp(MIN_CELSIUS, MAX_CELSIUS) * 2.0).round() as u32
+            }
+        }
+    }
+
+    /// Raises (or lowers, for negative `delta`) the temperature by `delta`
+    /// °C within the accepted range. Off starts at [MIN_CELSIUS], on at
+    /// [MAX_CELSIUS].
+    pub fn adjust(self, delta: f32) -> Self {
+        let celsius = match self {
+            ThermostatTarget::Off => MIN_CELSIUS - delta.min(0.0),
+            ThermostatTarget::On => MAX_CELSIUS - delta.max(0.0),
+            ThermostatTarget::Celsius(celsius) => celsius,
+        };
+        ThermostatTarget::Celsius(
+            ((celsius + delta).clamp(MIN_CELSIUS, MAX_CELSIUS) * 2.0).round() / 2.0,
+        )
+    }
+}
+
+impl std::str::FromStr for ThermostatTarget {
+    type Err = FritzError;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim();
+        match input.to_lowercase().as_str() {
+            "off" => Ok(ThermostatTarget::Off),
+            "on" => Ok(ThermostatTarget::On),
+            other => {
+                let celsius = other
+                    .trim_end_matches("°c")
+                    .trim_end_matches('c')
+                    .trim()
+                    .replace(',', ".")
+                    .parse::<f32>()
+                    .map_err(|_| {
+                        FritzError::ParserError(format!(
+                            "invalid thermostat target {:?}, expected off, on or a temperature",
+                            input
+                        ))
+                    })?;
+                ThermostatTarget::celsius(celsius)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ThermostatTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThermostatTarget::Off => write!(f, "off"),
+            ThermostatTarget::On => write!(f, "on"),
+            ThermostatTarget::Celsius(celsius) => write!(f, "{:.1} °C", celsius),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThermostatTarget;
+
+    #[test]
+    fn raw_values() {
+        assert_eq!(
+            ThermostatTarget::from_raw(40),
+            Some(ThermostatTarget::Celsius(20.0))
+        );
+        assert_eq!(ThermostatTarget::from_raw(253), Some(ThermostatTarget::Off));
+        assert_eq!(ThermostatTarget::from_raw(254), Some(ThermostatTarget::On));
+        assert_eq!(ThermostatTarget::from_raw(0), None);
+        assert_eq!(ThermostatTarget::Celsius(21.5).to_raw(), 43);
+        assert_eq!(ThermostatTarget::Off.to_raw(), 253);
+        assert_eq!(
+            ThermostatTarget::celsius(21.3).unwrap(),
+            ThermostatTarget::Celsius(21.5)
+        );
+        assert!(ThermostatTarget::celsius(7.5).is_err());
+    }
+
+    #[test]
+    fn parse_and_adjust() {
+        assert_eq!(
+            "21,5 °C".parse::<ThermostatTarget>().unwrap(),
+            ThermostatTarget::Celsius(21.5)
+        );
+        assert_eq!(
+            "ON".parse::<ThermostatTarget>().unwrap(),
+            ThermostatTarget::On
+        );
+        assert!("warm".parse::<ThermostatTarget>().is_err());
+
+        assert_eq!(
+            ThermostatTarget::Celsius(20.0).adjust(0.5),
+            ThermostatTarget::Celsius(20.5)
+        );
+        assert_eq!(
+            ThermostatTarget::Celsius(28.0).adjust(0.5),
+            ThermostatTarget::Celsius(28.0)
+        );
+        assert_eq!(
+            ThermostatTarget::Off.adjust(0.5),
+            ThermostatTarget::Celsius(8.5)
+        );
+        assert_eq!(
+            ThermostatTarget::On.adjust(-0.5),
+            ThermostatTarget::Celsius(27.5)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::ain::Ain;
+
 #[derive(thiserror::Error, Debug)]
 pub enum FritzError {
     #[cfg(not(target_family = "wasm"))]
@@ -13,6 +15,9 @@ pub enum FritzError {
     #[error("fritz login error: `{0}`")]
     LoginError(String),
 
+    #[error("cannot get the password: {0}")]
+    Password(String),
+
     #[cfg(not(target_family = "wasm"))]
     #[error("cannot parse xml: `{0}`")]
     XMLParseError(#[from] serde_xml_rs::Error),
@@ -20,6 +25,18 @@ pub enum FritzError {
     #[error("parser error: `{0}")]
     ParserError(String),
 
+    #[error("device `{0}` is locked, switching it had no effect")]
+    DeviceLocked(Ain),
+
+    #[error("device `{0}` is not present or the ain is invalid")]
+    DeviceNotPresent(Ain),
+
+    #[error("invalid device name: {0}")]
+    InvalidName(String),
+
+    #[error("invalid parameter: {0}")]
+    InvalidParameter(String),
+
     #[error("status code mismatch while triggering high refresh rate. Expected 200, got `{0}`")]
     TriggerHighRefreshRateError(reqwest::StatusCode),
 
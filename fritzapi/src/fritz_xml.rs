@@ -1,8 +1,11 @@
 #![allow(dead_code)]
 
-use crate::devices::{Device, DeviceList, DeviceOrGroup};
-use crate::error::Result;
+use crate::ain::Ain;
+use crate::devices::{Device, DeviceGroup, DeviceList, DeviceOrGroup};
+use crate::error::{FritzError, Result};
 use crate::stats::{DeviceStatValues, DeviceStats, DeviceStatsKind, RawDeviceStats, RawManyStats};
+use crate::templates::{Template, TemplateList};
+use crate::triggers::{Trigger, TriggerList};
 use serde::Deserialize;
 
 // response of login_sid.lua
@@ -49,6 +52,79 @@ pub fn parse_device_infos(xml: String) -> Result<Vec<Device>> {
         })
 }
 
+/// Parses the [`DeviceGroup`]s of a `getdevicelistinfos` response.
+pub fn parse_device_groups(xml: String) -> Result<Vec<DeviceGroup>> {
+    serde_xml_rs::from_str::<DeviceList>(xml.as_str())
+        .map(|list| {
+            list.list
+                .into_iter()
+                .filter_map(|item| match item {
+                    DeviceOrGroup::Device(_) => None,
+                    DeviceOrGroup::Group(group) => Some(group),
+                })
+                .collect()
+        })
+        .map_err(|err| {
+            eprintln!("cannot parse device groups: {err}");
+            err.into()
+        })
+}
+
+/// Parses the raw [`Device`]s and the [`DeviceGroup`]s of a
+/// `getdevicelistinfos` response.
+pub fn parse_device_list(xml: String) -> Result<(Vec<Device>, Vec<DeviceGroup>)> {
+    serde_xml_rs::from_str::<DeviceList>(xml.as_str())
+        .map(|list| {
+            let mut devices = Vec::new();
+            let mut groups = Vec::new();
+            for item in list.list {
+                match item {
+                    DeviceOrGroup::Device(device) => devices.push(device),
+                    DeviceOrGroup::Group(group) => groups.push(group),
+                }
+            }
+            (devices, groups)
+        })
+        .map_err(|err| {
+            eprintln!("cannot parse device list: {err}");
+            err.into()
+        })
+}
+
+/// Parses the response of the `setswitch*` commands: `1` / `0` for the new
+/// state or `inval` if the device is unknown or not present.
+pub fn parse_switch_state(ain: &Ain, response: &str) -> Result<bool> {
+    match response.trim() {
+        "1" => Ok(true),
+        "0" => Ok(false),
+        "inval" => Err(FritzError::DeviceNotPresent(ain.clone())),
+        other => Err(FritzError::ParserError(format!(
+            "unexpected switch state {:?} for device {}",
+            other, ain
+        ))),
+    }
+}
+
+/// Parses the response of `gettemplatelistinfos`.
+pub fn parse_templates(xml: String) -> Result<Vec<Template>> {
+    serde_xml_rs::from_str::<TemplateList>(xml.as_str())
+        .map(|list| list.templates)
+        .map_err(|err| {
+            eprintln!("cannot parse templates: {err}");
+            err.into()
+        })
+}
+
+/// Parses the response of `gettriggerlistinfos`.
+pub fn parse_triggers(xml: String) -> Result<Vec<Trigger>> {
+    serde_xml_rs::from_str::<TriggerList>(xml.as_str())
+        .map(|list| list.triggers)
+        .map_err(|err| {
+            eprintln!("cannot parse triggers: {err}");
+            err.into()
+        })
+}
+
 // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
 // features
 
@@ -163,6 +239,18 @@ mod tests {
         assert_eq!(info.sid, "0000000000000000");
     }
 
+    #[test]
+    fn parse_switch_state() {
+        let ain: Ain = "11630 0123723".parse().unwrap();
+        assert!(super::parse_switch_state(&ain, "1\n").unwrap());
+        assert!(!super::parse_switch_state(&ain, "0\n").unwrap());
+        assert!(matches!(
+            super::parse_switch_state(&ain, "inval\n"),
+            Err(FritzError::DeviceNotPresent(not_present)) if not_present == ain
+        ));
+        assert!(super::parse_switch_state(&ain, "").is_err());
+    }
+
     #[test]
     fn parse_device_stat_kind() {
         assert_eq!(
@@ -199,6 +287,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_absent_device() -> Result<()> {
+        let xml = r##"
+<devicelist version="1" fwversion="7.57">
+  <device identifier="11630 0123723" id="20" functionbitmask="35712" fwversion="04.16" manufacturer="AVM" productname="FRITZ!DECT 200">
+    <present>0</present>
+    <txbusy>1</txbusy>
+    <name>FRITZ!DECT 200 Laufband hinten</name>
+    <switch>
+      <state></state>
+      <mode>manuell</mode>
+      <lock></lock>
+      <devicelock></devicelock>
+    </switch>
+    <simpleonoff>
+      <state></state>
+    </simpleonoff>
+    <powermeter>
+      <voltage>234877</voltage>
+      <power>4570</power>
+      <energy>43714</energy>
+    </powermeter>
+    <temperature>
+      <celsius>195</celsius>
+      <offset>0</offset>
+    </temperature>
+  </device>
+</devicelist>
+"##;
+
+        let mut devices = parse_device_infos(xml.to_string())?
+            .into_iter()
+            .map(crate::AVMDevice::from_xml_device);
+        let device = devices.next().unwrap();
+        assert!(matches!(device, crate::AVMDevice::FritzDect2XX(_)));
+        assert!(!device.is_present());
+        assert!(device.is_tx_busy());
+        assert!(device.has_stale_readings());
+        assert!(!device.is_on());
+        assert_eq!(device.state(), "not present");
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_device_with_odd_identifier() -> Result<()> {
+        let xml = r##"
+<devicelist version="1" fwversion="7.57">
+  <device identifier="11630 0123723" id="20" functionbitmask="1024" fwversion="04.16" manufacturer="AVM" productname="FRITZ!DECT Repeater 100">
+    <present>1</present>
+    <txbusy>0</txbusy>
+    <name>Repeater</name>
+  </device>
+  <device identifier="12345 678/9" id="21" functionbitmask="1024" fwversion="01.00" manufacturer="ACME" productname="Other">
+    <present>1</present>
+    <txbusy>0</txbusy>
+    <name>Odd</name>
+  </device>
+</devicelist>
+"##;
+
+        let devices = parse_device_infos(xml.to_string())?;
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].identifier, "11630 0123723");
+        assert_eq!(devices[1].identifier.as_str(), "12345 678/9");
+
+        Ok(())
+    }
+
     #[test]
     fn parse_devices() -> Result<()> {
         let xml = r##"
@@ -486,8 +643,99 @@ mod tests {
 </devicelist>
 "##;
 
-        let _ = parse_device_infos(xml.to_string())?;
+        let devices = parse_device_infos(xml.to_string())?;
+        for device in devices
+            .iter()
+            .cloned()
+            .map(crate::AVMDevice::from_xml_device)
+        {
+            let json = serde_json::to_string(&device).unwrap();
+            let parsed: crate::AVMDevice = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, device);
+        }
 
+        let hkr = devices
+            .iter()
+            .find_map(|device| device.hkr.as_ref())
+            .unwrap();
+        assert_eq!(hkr.current_celsius(), Some(24.0));
+        assert_eq!(
+            hkr.target(),
+            Some(crate::devices::ThermostatTarget::Celsius(20.0))
+        );
+        assert_eq!(
+            hkr.reduced(),
+            Some(crate::devices::ThermostatTarget::Celsius(17.0))
+        );
+
+        let groups = parse_device_groups(xml.to_string())?;
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "Alles in Bernau");
+        assert_eq!(groups[0].member_ids(), vec!["16", "17", "18", "20", "22"]);
+
+        let (both_devices, both_groups) = parse_device_list(xml.to_string())?;
+        assert_eq!(
+            (both_devices.len(), both_groups.len()),
+            (devices.len(), groups.len())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_templates() -> Result<()> {
+        let xml = r##"<templatelist version="1">
+  <template identifier="tmp6F0093-391363146" id="60000" functionbitmask="6784" applymask="64">
+    <name>Heizung aus</name>
+    <devices>
+      <device identifier="09995 0335100" />
+    </devices>
+    <applymask><hkr_temperature /></applymask>
+  </template>
+  <template identifier="tmp6F0093-39136314A" id="60001" functionbitmask="320" applymask="2">
+    <name>Alles aus</name>
+    <devices>
+      <device identifier="11630 0123723" />
+      <device identifier="11630 0266726" />
+    </devices>
+  </template>
+</templatelist>"##;
+
+        let templates = super::parse_templates(xml.to_string())?;
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].identifier.as_str(), "tmp6F0093-391363146");
+        assert_eq!(templates[0].name, "Heizung aus");
+        assert_eq!(templates[1].id, "60001");
+
+        assert!(
+            super::parse_templates("<templatelist version=\"1\"></templatelist>".to_string())?
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_triggers() -> Result<()> {
+        let xml = r##"<triggerlist version="1">
+  <trigger active="1" identifier="trd1D3BA0-3E9E73F2D">
+    <name>Bewegung Flur</name>
+  </trigger>
+  <trigger active="0" identifier="trd1D3BA0-3E9E73F31">
+    <name>Taster Garten</name>
+  </trigger>
+</triggerlist>"##;
+
+        let triggers = super::parse_triggers(xml.to_string())?;
+        assert_eq!(triggers.len(), 2);
+        assert_eq!(triggers[0].identifier.as_str(), "trd1D3BA0-3E9E73F2D");
+        assert_eq!(triggers[0].name, "Bewegung Flur");
+        assert!(triggers[0].active);
+        assert!(!triggers[1].active);
+
+        assert!(
+            super::parse_triggers("<triggerlist version=\"1\"></triggerlist>".to_string())?
+                .is_empty()
+        );
         Ok(())
     }
 }
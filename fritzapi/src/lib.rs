@@ -24,9 +24,12 @@
 #[macro_use]
 extern crate tracing;
 
+pub mod ain;
 pub mod devices;
 pub mod error;
 pub mod stats;
+pub mod templates;
+pub mod triggers;
 
 #[cfg(not(target_family = "wasm"))]
 pub(crate) mod api;
@@ -35,9 +38,14 @@ pub(crate) mod client;
 #[cfg(not(target_family = "wasm"))]
 pub(crate) mod fritz_xml;
 
-pub use devices::{AVMDevice, FritzDect2XX};
+pub use ain::Ain;
+pub use devices::{
+    AVMDevice, BlindTarget, Color, DeviceGroup, FritzDect2XX, Hkr, SwitchMode, ThermostatTarget,
+};
 pub use error::{FritzError, Result};
 pub use stats::{DeviceStats, DeviceStatsKind, Unit};
+pub use templates::Template;
+pub use triggers::Trigger;
 
 #[cfg(not(target_family = "wasm"))]
 pub use client::FritzClient;
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct RawDeviceStats {
@@ -29,6 +29,13 @@ pub enum Unit {
     Volt,
 }
 
+/// Serialized as its symbol, e.g. `"°C"`.
+impl Serialize for Unit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl std::fmt::Display for Unit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -41,7 +48,8 @@ impl std::fmt::Display for Unit {
 }
 
 /// Category of measurements that the fritz devices may provide.
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceStatsKind {
     Temperature,
     Voltage,
@@ -92,13 +100,15 @@ impl std::str::FromStr for DeviceStatsKind {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct DeviceStats {
     pub kind: DeviceStatsKind,
     pub values: Vec<DeviceStatValues>,
 }
 
-#[derive(Debug)]
+/// Measurements of one series, the newest first. `grid` is the interval
+/// between two values in seconds.
+#[derive(Debug, Serialize)]
 pub struct DeviceStatValues {
     pub values: Vec<f32>,
     pub grid: usize,
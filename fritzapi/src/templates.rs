@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ain::Ain;
+
+/// A template configured on the fritz box, applying it sets the state of
+/// several devices at once. See [crate::FritzClient::list_templates] and
+/// [crate::FritzClient::apply_template].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Template {
+    #[serde(deserialize_with = "crate::ain::deserialize_lenient")]
+    pub identifier: Ain,
+    pub id: String,
+    #[serde(default)]
+    pub functionbitmask: String,
+    pub name: String,
+}
+
+/// Response of `gettemplatelistinfos`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TemplateList {
+    #[serde(rename = "template", default)]
+    pub templates: Vec<Template>,
+}
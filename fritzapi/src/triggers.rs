@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ain::Ain;
+use crate::devices::deserialize_maybe_bool;
+
+/// A trigger ("Auslöser") configured on the fritz box, e.g. a button or a
+/// motion detector that switches devices. See
+/// [crate::FritzClient::list_triggers] and
+/// [crate::FritzClient::set_trigger_active].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Trigger {
+    #[serde(deserialize_with = "crate::ain::deserialize_lenient")]
+    pub identifier: Ain,
+    /// Whether the trigger is enabled.
+    #[serde(default, deserialize_with = "deserialize_maybe_bool")]
+    pub active: bool,
+    pub name: String,
+}
+
+/// Response of `gettriggerlistinfos`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TriggerList {
+    #[serde(rename = "trigger", default)]
+    pub triggers: Vec<Trigger>,
+}
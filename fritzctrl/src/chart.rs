@@ -0,0 +1,207 @@
+//! Renders device statistics as text charts for `fritzctrl list --chart`.
+
+use crate::output::StatSample;
+use fritzapi::DeviceStatsKind;
+
+pub const CHART_STYLES: [&str; 2] = ["bars", "line"];
+
+/// Rows of the plot area.
+pub const DEFAULT_HEIGHT: usize = 10;
+
+/// Unicode block elements, one eighth of a row per step.
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Value of `--chart`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartStyle {
+    /// Bar chart drawn with Unicode block elements.
+    Bars,
+    /// Line chart in plain ASCII, for terminals and fonts without the block
+    /// elements.
+    Line,
+}
+
+impl std::str::FromStr for ChartStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "bars" | "bar" => Ok(ChartStyle::Bars),
+            "line" | "ascii" => Ok(ChartStyle::Line),
+            _ => Err(anyhow::anyhow!("unknown chart style {:?}", input)),
+        }
+    }
+}
+
+/// Width of the terminal, 80 columns if stdout is no terminal.
+pub fn terminal_width() -> usize {
+    ratatui::crossterm::terminal::size()
+        .map(|(width, _)| width as usize)
+        .unwrap_or(80)
+}
+
+/// Draws the `samples` of one series (newest first, as returned by
+/// [StatSample::from_series]) into `width` columns including the axis
+/// labels. Samples are averaged if there are more of them than columns.
+pub fn render(samples: &[StatSample], style: ChartStyle, width: usize, height: usize) -> String {
+    let Some(first) = samples.first() else {
+        return "no data\n".to_string();
+    };
+    let chronological: Vec<&StatSample> = samples.iter().rev().collect();
+    let values: Vec<f32> = chronological.iter().map(|sample| sample.value).collect();
+    let height = height.max(2);
+
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    // power and energy bars start at zero, temperatures at the minimum
+    let lower = match first.kind {
+        DeviceStatsKind::Power | DeviceStatsKind::Energy if min >= 0.0 => 0.0,
+        _ => min,
+    };
+    let upper = if max <= lower { lower + 1.0 } else { max };
+
+    let labels: Vec<String> = [upper, (upper + lower) / 2.0, lower]
+        .iter()
+        .map(|value| format!("{:.1}", value))
+        .collect();
+    let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+    let columns = downsample(&values, width.saturating_sub(label_width + 1).max(1));
+    let fraction = |value: f32| ((value - lower) / (upper - lower)).clamp(0.0, 1.0);
+
+    // grid[row][column], row 0 is the bottom
+    let mut grid = vec![vec![' '; columns.len()]; height];
+    match style {
+        ChartStyle::Bars => {
+            for (column, value) in columns.iter().enumerate() {
+                let eighths = (fraction(*value) * (height * 8) as f32).round() as usize;
+                for (row, line) in grid.iter_mut().enumerate() {
+                    line[column] = BLOCKS[eighths.saturating_sub(row * 8).min(8)];
+                }
+            }
+        }
+        ChartStyle::Line => {
+            let mut previous: Option<usize> = None;
+            for (column, value) in columns.iter().enumerate() {
+                let row = (fraction(*value) * (height - 1) as f32).round() as usize;
+                if let Some(previous) = previous.filter(|previous| previous.abs_diff(row) > 1) {
+                    for line in &mut grid[row.min(previous) + 1..row.max(previous)] {
+                        line[column] = '|';
+                    }
+                }
+                grid[row][column] = '*';
+                previous = Some(row);
+            }
+        }
+    }
+
+    let (tick, axis, corner, rule) = match style {
+        ChartStyle::Bars => ('┤', '│', '└', '─'),
+        ChartStyle::Line => ('+', '|', '+', '-'),
+    };
+    let mut out = String::new();
+    for (i, line) in grid.iter().rev().enumerate() {
+        let label = match i {
+            0 => labels[0].as_str(),
+            i if i == height / 2 && height >= 3 => labels[1].as_str(),
+            i if i == height - 1 => labels[2].as_str(),
+            _ => "",
+        };
+        let marker = if label.is_empty() { axis } else { tick };
+        out.push_str(&format!("{:>width$}{}", label, marker, width = label_width));
+        let line: String = line.iter().collect();
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.push_str(&" ".repeat(label_width));
+    out.push(corner);
+    out.extend(std::iter::repeat_n(rule, columns.len()));
+    out.push('\n');
+
+    let from = chronological.first().unwrap().time;
+    let to = chronological.last().unwrap().time;
+    let time_format = match (to - from).num_hours() {
+        hours if hours >= 24 * 60 => "%Y-%m-%d",
+        hours if hours >= 24 => "%m-%d %H:%M",
+        _ => "%H:%M",
+    };
+    let from = from.format(time_format).to_string();
+    let to = to.format(time_format).to_string();
+    let gap = columns.len().saturating_sub(from.len() + to.len()).max(1);
+    out.push_str(&" ".repeat(label_width + 1));
+    out.push_str(&format!("{}{}{}\n", from, " ".repeat(gap), to));
+    out
+}
+
+/// Averages `values` into at most `width` columns.
+fn downsample(values: &[f32], width: usize) -> Vec<f32> {
+    if values.len() <= width {
+        return values.to_vec();
+    }
+    (0..width)
+        .map(|column| {
+            let start = column * values.len() / width;
+            let end = ((column + 1) * values.len() / width).max(start + 1);
+            values[start..end].iter().sum::<f32>() / (end - start) as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+
+    fn samples(kind: DeviceStatsKind, values: &[f32]) -> Vec<StatSample> {
+        let now = Local.with_ymd_and_hms(2025, 12, 24, 18, 0, 0).unwrap();
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| StatSample {
+                device: "11630 0123723".to_string(),
+                kind,
+                unit: kind.unit(),
+                time: now - chrono::Duration::minutes(15 * i as i64),
+                value: *value,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bars() {
+        // newest first
+        let samples = samples(DeviceStatsKind::Power, &[40.0, 30.0, 20.0, 10.0, 0.0]);
+        let chart = render(&samples, ChartStyle::Bars, 20, 2);
+        assert_eq!(
+            chart.lines().collect::<Vec<_>>(),
+            ["40.0┤   ▄█", " 0.0┤ ▄███", "    └─────", "     17:00 18:00"]
+        );
+    }
+
+    #[test]
+    fn line() {
+        let samples = samples(DeviceStatsKind::Temperature, &[22.0, 20.0, 20.0, 18.0]);
+        let chart = render(&samples, ChartStyle::Line, 30, 3);
+        assert_eq!(
+            chart.lines().collect::<Vec<_>>(),
+            [
+                "22.0+   *",
+                "20.0+ **",
+                "18.0+*",
+                "    +----",
+                "     17:15 18:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn averages_to_fit() {
+        assert_eq!(
+            downsample(&[1.0, 3.0, 5.0, 7.0, 9.0, 11.0], 3),
+            vec![2.0, 6.0, 10.0]
+        );
+        assert_eq!(downsample(&[1.0, 2.0], 3), vec![1.0, 2.0]);
+        let samples = samples(DeviceStatsKind::Power, &[1.0; 200]);
+        let chart = render(&samples, ChartStyle::Bars, 40, 4);
+        assert!(chart.lines().all(|line| line.chars().count() <= 40));
+    }
+}
@@ -0,0 +1,127 @@
+//! Shell completion: `fritzctrl completions SHELL` prints a script that calls
+//! back into fritzctrl (with `FRITZCTRL_COMPLETE` set) whenever the shell
+//! completes a fritzctrl command line. Values of `--device` are completed with
+//! the names and AINs of the devices on the box, using the cached session.
+
+use crate::config::Profile;
+use clap::ArgMatches;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{CompleteEnv, Shells};
+use std::time::Duration;
+
+/// Environment variable that switches fritzctrl into completion mode.
+const COMPLETE_VAR: &str = "FRITZCTRL_COMPLETE";
+
+/// How long completing devices waits for the fritz box.
+const DEVICE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Answers the completion request of the shell and exits if there is one.
+pub fn complete_if_requested(cli: fn() -> clap::Command) {
+    CompleteEnv::with_factory(cli).var(COMPLETE_VAR).complete();
+}
+
+/// Names of the supported shells.
+pub fn shells() -> Vec<&'static str> {
+    Shells::builtins().names().collect()
+}
+
+pub fn completions(args: &ArgMatches) -> anyhow::Result<()> {
+    let name = args.get_one::<String>("shell").unwrap();
+    let shells = Shells::builtins();
+    let shell = shells
+        .completer(name)
+        .ok_or_else(|| anyhow::anyhow!("unknown shell {:?}", name))?;
+
+    // call back into this binary unless it was started from the PATH
+    let bin = env!("CARGO_PKG_NAME");
+    let completer = match std::env::args_os().next() {
+        Some(arg0) if std::path::Path::new(&arg0).components().count() > 1 => {
+            std::env::current_exe()?.to_string_lossy().into_owned()
+        }
+        _ => bin.to_string(),
+    };
+    shell.write_registration(
+        COMPLETE_VAR,
+        bin,
+        bin,
+        &completer,
+        &mut std::io::stdout().lock(),
+    )?;
+    Ok(())
+}
+
+/// Candidates for `--device`: names, AINs and groups of the devices on the
+/// box and the aliases of the profile. Empty if the box can't be asked
+/// without a password prompt.
+pub fn device_candidates() -> Vec<CompletionCandidate> {
+    // the matches of a command line without arguments carry the settings from
+    // the environment, e.g. FRITZ_PROFILE and FRITZ_HOST
+    let Ok(args) = crate::cli().try_get_matches_from([env!("CARGO_PKG_NAME"), "list"]) else {
+        return Vec::new();
+    };
+    let args = args.subcommand_matches("list").unwrap();
+    let Ok(profile) = Profile::from_args(args) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<CompletionCandidate> = profile
+        .aliases
+        .iter()
+        .map(|(alias, selector)| CompletionCandidate::new(alias).help(Some(selector.into())))
+        .collect();
+
+    let mut client = match profile.client_without_prompt(args, DEVICE_TIMEOUT) {
+        Ok(client) => client,
+        Err(err) => {
+            debug!("cannot complete devices: {}", err);
+            return candidates;
+        }
+    };
+    match client.list_devices_and_groups() {
+        Ok((devices, groups)) => {
+            for device in &devices {
+                let about = format!("{} ({})", device.name(), device.productname());
+                candidates.push(CompletionCandidate::new(device.name()).help(Some(
+                    format!("{} ({})", device.id(), device.productname()).into(),
+                )));
+                candidates
+                    .push(CompletionCandidate::new(device.id().as_str()).help(Some(about.into())));
+            }
+            candidates.extend(groups.iter().map(|group| {
+                CompletionCandidate::new(format!("group:{}", group.name))
+                    .help(Some(format!("group {}", group.identifier).into()))
+            }));
+        }
+        Err(err) => debug!("cannot complete devices: {}", err),
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registration() {
+        assert!(["bash", "zsh", "fish"]
+            .iter()
+            .all(|shell| shells().contains(shell)));
+        crate::cli().debug_assert();
+
+        let mut script = Vec::new();
+        let shells = Shells::builtins();
+        shells
+            .completer("bash")
+            .unwrap()
+            .write_registration(
+                COMPLETE_VAR,
+                "fritzctrl",
+                "fritzctrl",
+                "fritzctrl",
+                &mut script,
+            )
+            .unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("FRITZCTRL_COMPLETE=\"bash\""));
+    }
+}
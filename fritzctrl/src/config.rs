@@ -0,0 +1,515 @@
+//! The configuration file `~/.config/fritzctrl/config.toml` with named
+//! profiles, one per fritz box:
+//!
+//! ```toml
+//! default_profile = "home"
+//!
+//! [profiles.home]
+//! user = "fritz1234"
+//! password_command = "pass show fritz/home"
+//! location = { latitude = 52.52, longitude = 13.40 }
+//!
+//! [profiles.home.aliases]
+//! kaffee = "11630 0123723"
+//! wohnzimmer = "group:Wohnzimmer"
+//!
+//! [profiles.office]
+//! host = "https://192.168.10.1"
+//! user = "office"
+//! keyring = "fritzctrl-office"
+//! tls = { ca_cert = "~/.config/fritzctrl/office.pem" }
+//! ```
+//!
+//! Command line arguments take precedence over the profile, environment
+//! variables (`FRITZ_HOST`, `FRITZ_USER`, `FRITZ_PASSWORD`, ...) are only used
+//! for settings the profile does not contain. Without any password source
+//! the password is asked for.
+
+use crate::credentials::PasswordSource;
+use crate::daylight::Location;
+use crate::selector::DeviceSelector;
+use crate::session::{Session, SessionCache};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use fritzapi::{FritzClient, FritzError};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile to use when `--profile` is not given.
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Host name, address or url of the fritz box, `fritz.box` by default.
+    pub host: Option<String>,
+    pub user: Option<String>,
+    /// Shell command that prints the password, e.g. `pass show fritz`.
+    pub password_command: Option<String>,
+    /// File that contains the password.
+    pub password_file: Option<PathBuf>,
+    /// Name of the keyring service the password is stored under, needs the
+    /// `keyring` feature.
+    pub keyring: Option<String>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Default location for `fritzctrl daylight` and sunrise / sunset
+    /// schedule entries.
+    pub location: Option<ProfileLocation>,
+    /// Alternative device names, mapped to device selectors.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Accept any certificate, the fritz box uses a self-signed one.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// PEM file with the certificate of the fritz box.
+    pub ca_cert: Option<PathBuf>,
+}
+
+/// `location = { latitude = 52.52, longitude = 13.40, altitude = 34 }` or
+/// `location = "Berlin"` / `location = "52.52,13.40"`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileLocation {
+    Coordinates {
+        latitude: f64,
+        longitude: f64,
+        #[serde(default)]
+        altitude: i64,
+    },
+    /// Parsed by [Location::from_str].
+    Name(String),
+}
+
+impl TryFrom<&ProfileLocation> for Location {
+    type Error = anyhow::Error;
+
+    fn try_from(loc: &ProfileLocation) -> anyhow::Result<Self> {
+        match loc {
+            ProfileLocation::Coordinates {
+                latitude,
+                longitude,
+                altitude,
+            } => Ok(Location {
+                altitude: *altitude,
+                ..Location::new(*latitude, *longitude)
+            }),
+            ProfileLocation::Name(name) => name.parse(),
+        }
+    }
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+impl Config {
+    /// `~/.config/fritzctrl/config.toml` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("fritzctrl").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("cannot read config {}: {}", path.display(), err))?;
+        content.parse()
+    }
+
+    /// Loads the file passed with `--config`, or the default config if it
+    /// exists.
+    pub fn from_args(args: &ArgMatches) -> anyhow::Result<Self> {
+        if let Some(path) = args.get_one::<PathBuf>("config") {
+            return Self::load(path);
+        }
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// The profile `name`, or the default profile. Without either an empty
+    /// profile is returned.
+    pub fn profile(&self, name: Option<&str>) -> anyhow::Result<Profile> {
+        let name = match name.or(self.default_profile.as_deref()) {
+            None => return Ok(Profile::default()),
+            Some(name) => name,
+        };
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            let known = self.profiles.keys().cloned().collect::<Vec<_>>();
+            anyhow::anyhow!(
+                "unknown profile {:?}, known profiles: {}",
+                name,
+                known.join(", ")
+            )
+        })
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(input)?;
+        for (name, profile) in &config.profiles {
+            for (alias, selector) in &profile.aliases {
+                selector.parse::<DeviceSelector>().map_err(|err| {
+                    anyhow::anyhow!("profile {:?}, alias {:?}: {}", name, alias, err)
+                })?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Profile {
+    /// The profile selected with `--profile`, `FRITZ_PROFILE` or
+    /// `default_profile`.
+    pub fn from_args(args: &ArgMatches) -> anyhow::Result<Self> {
+        let config = Config::from_args(args)?;
+        config.profile(args.get_one::<String>("profile").map(String::as_str))
+    }
+
+    /// Replaces a selector that is an alias with the selector it stands for.
+    pub fn expand_alias(&self, selector: DeviceSelector) -> DeviceSelector {
+        match &selector {
+            DeviceSelector::Plain(name) => match self.aliases.get(name) {
+                // aliases are validated when the config is loaded
+                Some(target) => target.parse().unwrap_or(selector),
+                None => selector,
+            },
+            _ => selector,
+        }
+    }
+
+    pub fn expand_aliases(&self, selectors: Vec<DeviceSelector>) -> Vec<DeviceSelector> {
+        selectors
+            .into_iter()
+            .map(|selector| self.expand_alias(selector))
+            .collect()
+    }
+
+    /// The location of the profile, fails if it names an unknown city.
+    pub fn location(&self) -> anyhow::Result<Option<Location>> {
+        self.location.as_ref().map(Location::try_from).transpose()
+    }
+
+    /// The host of the fritz box, from `args` or the profile.
+    pub fn host(&self, args: &ArgMatches) -> String {
+        arg_or(args, "host", self.host.clone()).unwrap_or_else(|| "fritz.box".to_string())
+    }
+
+    /// A client for the fritz box of this profile, with host, user and
+    /// password taken from `args` if given there. Unless `--no-session-cache`
+    /// is set, the session of an earlier invocation is reused.
+    pub fn client(&self, args: &ArgMatches) -> anyhow::Result<Session> {
+        self.connect(args, true, None)
+    }
+
+    /// Like [Profile::client] but never asks for the password: if it would
+    /// have to be entered, read from the keyring or printed by a password
+    /// command (all of which may ask, e.g. gpg for its passphrase) only the
+    /// cached session is used. For shell completion, requests give
+    /// up after `timeout`.
+    pub fn client_without_prompt(
+        &self,
+        args: &ArgMatches,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<Session> {
+        self.connect(args, false, Some(timeout))
+    }
+
+    fn connect(
+        &self,
+        args: &ArgMatches,
+        interactive: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> anyhow::Result<Session> {
+        let host = arg_or(args, "host", self.host.clone());
+        let user = arg_or(args, "user", self.user.clone()).ok_or_else(|| {
+            anyhow::anyhow!("no user given, use --user, FRITZ_USER or set user in the profile")
+        })?;
+        let source = self.password_source(args, &user, host.as_deref().unwrap_or("fritz.box"));
+        // the password is only read when the cached session is missing or
+        // expired, so a password command or the keyring isn't asked for nothing
+        let mut client = match source {
+            PasswordSource::Plain(password) => FritzClient::new(user, password),
+            PasswordSource::Prompt { .. }
+            | PasswordSource::Keyring { .. }
+            | PasswordSource::Command(_)
+                if !interactive =>
+            {
+                FritzClient::new(user, "").without_login()
+            }
+            source => FritzClient::with_password_fn(user, move || {
+                source
+                    .read()
+                    .map_err(|err| FritzError::Password(err.to_string()))
+            }),
+        };
+        if let Some(host) = host {
+            client = client.with_host(host)?;
+        }
+        if let Some(timeout) = timeout {
+            client = client.with_timeout(timeout);
+        }
+        let client = self.tls.apply(client)?;
+
+        let cache = match args.try_get_one::<bool>("no-session-cache") {
+            Ok(Some(true)) => None,
+            _ => SessionCache::default_location(),
+        };
+        Ok(Session::new(client, cache))
+    }
+
+    /// The password given on the command line, else the one of the profile,
+    /// else the one from the environment, else a prompt.
+    pub fn password_source(&self, args: &ArgMatches, user: &str, host: &str) -> PasswordSource {
+        self.choose_password_source(
+            password_from_args(args, ValueSource::CommandLine),
+            password_from_args(args, ValueSource::EnvVariable),
+            user,
+            host,
+        )
+    }
+
+    fn choose_password_source(
+        &self,
+        command_line: Option<PasswordSource>,
+        environment: Option<PasswordSource>,
+        user: &str,
+        host: &str,
+    ) -> PasswordSource {
+        let from_profile = || {
+            if let Some(command) = &self.password_command {
+                Some(PasswordSource::Command(command.clone()))
+            } else if let Some(path) = &self.password_file {
+                Some(PasswordSource::File(expand_home(path)))
+            } else {
+                self.keyring
+                    .as_ref()
+                    .map(|service| PasswordSource::Keyring {
+                        service: service.clone(),
+                        user: user.to_string(),
+                    })
+            }
+        };
+        command_line
+            .or_else(from_profile)
+            .or(environment)
+            .unwrap_or_else(|| PasswordSource::Prompt {
+                user: user.to_string(),
+                host: host.to_string(),
+            })
+    }
+}
+
+impl TlsConfig {
+    #[cfg(feature = "tls")]
+    fn apply(&self, client: FritzClient) -> anyhow::Result<FritzClient> {
+        let mut client = client.danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(path) = &self.ca_cert {
+            let path = expand_home(path);
+            let pem = std::fs::read(&path)
+                .map_err(|err| anyhow::anyhow!("cannot read {}: {}", path.display(), err))?;
+            client = client.add_root_certificate_pem(&pem)?;
+        }
+        Ok(client)
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn apply(&self, client: FritzClient) -> anyhow::Result<FritzClient> {
+        if self.accept_invalid_certs || self.ca_cert.is_some() {
+            return Err(anyhow::anyhow!(
+                "tls settings need fritzctrl to be built with the tls feature"
+            ));
+        }
+        Ok(client)
+    }
+}
+
+/// Value of `id` if it was passed on the command line, else `fallback`, else
+/// the value from the environment.
+fn arg_or(args: &ArgMatches, id: &str, fallback: Option<String>) -> Option<String> {
+    arg_from_command_line(args, id)
+        .or(fallback)
+        .or_else(|| args.get_one::<String>(id).cloned())
+}
+
+/// The password source passed with `--password`, `--password-file` or
+/// `--password-command` from `source`, the command line or the environment.
+/// Each source is looked at on its own, so `FRITZ_PASSWORD` can be overridden
+/// with `--password-file`.
+fn password_from_args(args: &ArgMatches, source: ValueSource) -> Option<PasswordSource> {
+    let given = |id: &str| args.value_source(id) == Some(source);
+    if given("password") {
+        args.get_one::<String>("password")
+            .cloned()
+            .map(PasswordSource::Plain)
+    } else if given("password-file") {
+        args.get_one::<PathBuf>("password-file")
+            .cloned()
+            .map(PasswordSource::File)
+    } else if given("password-command") {
+        args.get_one::<String>("password-command")
+            .cloned()
+            .map(PasswordSource::Command)
+    } else {
+        None
+    }
+}
+
+fn arg_from_command_line(args: &ArgMatches, id: &str) -> Option<String> {
+    match args.value_source(id) {
+        Some(ValueSource::CommandLine) => args.get_one::<String>(id).cloned(),
+        _ => None,
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_profile = "home"
+
+[profiles.home]
+user = "fritz1234"
+password_command = "echo secret"
+location = { latitude = 52.52, longitude = 13.40 }
+
+[profiles.home.aliases]
+kaffee = "11630 0123723"
+wohnzimmer = "group:Wohnzimmer"
+
+[profiles.office]
+host = "https://192.168.10.1"
+user = "office"
+tls = { accept_invalid_certs = true }
+keyring = "fritzctrl-office"
+location = "Wien"
+"#;
+
+    #[test]
+    fn profiles() {
+        let config: Config = CONFIG.parse().unwrap();
+        let home = config.profile(None).unwrap();
+        assert_eq!(home.user.as_deref(), Some("fritz1234"));
+        assert_eq!(home.location().unwrap().unwrap().latitude, 52.52);
+
+        let office = config.profile(Some("office")).unwrap();
+        assert_eq!(office.host.as_deref(), Some("https://192.168.10.1"));
+        assert!(office.tls.accept_invalid_certs);
+        assert_eq!(office.keyring.as_deref(), Some("fritzctrl-office"));
+        assert_eq!(office.location().unwrap().unwrap().altitude, 190);
+
+        let err = config.profile(Some("cottage")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown profile \"cottage\", known profiles: home, office"
+        );
+
+        assert!(Config::default().profile(None).is_ok());
+        assert!("[profiles.home]\npasword = \"typo\""
+            .parse::<Config>()
+            .is_err());
+        assert!("[profiles.home.aliases]\nbad = \"re:(\""
+            .parse::<Config>()
+            .is_err());
+    }
+
+    #[test]
+    fn aliases() {
+        let config: Config = CONFIG.parse().unwrap();
+        let home = config.profile(Some("home")).unwrap();
+        let expand = |input: &str| home.expand_alias(input.parse().unwrap()).to_string();
+        assert_eq!(expand("kaffee"), "11630 0123723");
+        assert_eq!(expand("wohnzimmer"), "group:Wohnzimmer");
+        assert_eq!(expand("Laufband"), "Laufband");
+        assert_eq!(expand("name:kaffee"), "name:kaffee");
+    }
+
+    #[test]
+    fn password_precedence() {
+        use clap::{Arg, Command};
+        let command = Command::new("test")
+            .arg(Arg::new("password").long("password"))
+            .arg(
+                Arg::new("password-file")
+                    .long("password-file")
+                    .value_parser(clap::value_parser!(PathBuf)),
+            )
+            .arg(Arg::new("password-command").long("password-command"));
+        let config: Config = CONFIG.parse().unwrap();
+        let home = config.profile(Some("home")).unwrap();
+        let office = config.profile(Some("office")).unwrap();
+
+        let args = command
+            .clone()
+            .get_matches_from(["test", "--password-file", "/run/pw"]);
+        assert_eq!(
+            home.password_source(&args, "u", "fritz.box"),
+            PasswordSource::File("/run/pw".into())
+        );
+
+        let args = command.get_matches_from(["test"]);
+        assert_eq!(
+            home.password_source(&args, "u", "fritz.box"),
+            PasswordSource::Command("echo secret".to_string())
+        );
+        assert_eq!(
+            office.password_source(&args, "office", "fritz.box"),
+            PasswordSource::Keyring {
+                service: "fritzctrl-office".to_string(),
+                user: "office".to_string()
+            }
+        );
+        assert_eq!(
+            Profile::default().password_source(&args, "u", "fritz.box"),
+            PasswordSource::Prompt {
+                user: "u".to_string(),
+                host: "fritz.box".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn password_from_environment() {
+        let plain = |password: &str| Some(PasswordSource::Plain(password.to_string()));
+        let choose = |profile: &Profile, command_line, environment| {
+            profile.choose_password_source(command_line, environment, "u", "fritz.box")
+        };
+        let config: Config = CONFIG.parse().unwrap();
+        let home = config.profile(Some("home")).unwrap();
+
+        // the command line wins over the environment, the profile as well
+        assert_eq!(
+            choose(&home, plain("cli"), plain("env")),
+            plain("cli").unwrap()
+        );
+        assert_eq!(
+            choose(&home, None, plain("env")),
+            PasswordSource::Command("echo secret".to_string())
+        );
+        assert_eq!(
+            choose(&Profile::default(), None, plain("env")),
+            plain("env").unwrap()
+        );
+    }
+}
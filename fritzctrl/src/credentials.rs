@@ -0,0 +1,172 @@
+//! Where the password for the fritz box comes from. Passing it with
+//! `--password` leaks it into the shell history and the process list, so it
+//! can also be read from a file, printed by a command (`pass show fritz`),
+//! looked up in the OS keyring (with the `keyring` feature) or typed in.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordSource {
+    Plain(String),
+    /// First line of a file.
+    File(PathBuf),
+    /// First line printed by a shell command.
+    Command(String),
+    /// Entry of the OS keyring / Secret Service.
+    Keyring {
+        service: String,
+        user: String,
+    },
+    /// Hidden interactive prompt.
+    Prompt {
+        user: String,
+        host: String,
+    },
+}
+
+impl PasswordSource {
+    pub fn read(&self) -> anyhow::Result<String> {
+        match self {
+            PasswordSource::Plain(password) => Ok(password.clone()),
+            PasswordSource::File(path) => password_from_file(path),
+            PasswordSource::Command(command) => password_from_command(command),
+            PasswordSource::Keyring { service, user } => password_from_keyring(service, user),
+            PasswordSource::Prompt { user, host } => {
+                rpassword::prompt_password(format!("Password for {} at {}: ", user, host))
+                    .map_err(|err| {
+                        anyhow::anyhow!(
+                            "no password given and cannot prompt for it ({}), use --password-file, --password-command or a profile",
+                            err
+                        )
+                    })
+            }
+        }
+    }
+}
+
+pub fn password_from_file(path: &Path) -> anyhow::Result<String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("cannot read password file {}: {}", path.display(), err))?;
+    warn_if_readable_by_others(path);
+    first_line(&content).ok_or_else(|| anyhow::anyhow!("password file {} is empty", path.display()))
+}
+
+/// Runs `command` with the shell and returns its first line of output.
+pub fn password_from_command(command: &str) -> anyhow::Result<String> {
+    let output = if cfg!(windows) {
+        std::process::Command::new("cmd")
+            .args(["/C", command])
+            .output()
+    } else {
+        std::process::Command::new("sh")
+            .args(["-c", command])
+            .output()
+    }
+    .map_err(|err| anyhow::anyhow!("cannot run password command {:?}: {}", command, err))?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "password command {:?} failed with {}",
+            command,
+            output.status
+        ));
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    first_line(&stdout)
+        .ok_or_else(|| anyhow::anyhow!("password command {:?} printed no password", command))
+}
+
+/// Reads the password of `user` stored under `service`. If there is none yet
+/// it is asked for and stored.
+#[cfg(feature = "keyring")]
+pub fn password_from_keyring(service: &str, user: &str) -> anyhow::Result<String> {
+    let entry = keyring::Entry::new(service, user)?;
+    match entry.get_password() {
+        Ok(password) => Ok(password),
+        Err(keyring::Error::NoEntry) => {
+            let password = rpassword::prompt_password(format!(
+                "No password for {} in keyring {:?}, password to store: ",
+                user, service
+            ))
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "no password for {} in keyring {:?} and cannot prompt for it: {}",
+                    user,
+                    service,
+                    err
+                )
+            })?;
+            entry.set_password(&password)?;
+            Ok(password)
+        }
+        Err(err) => Err(anyhow::anyhow!(
+            "cannot read password for {} from keyring {:?}: {}",
+            user,
+            service,
+            err
+        )),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn password_from_keyring(service: &str, _user: &str) -> anyhow::Result<String> {
+    Err(anyhow::anyhow!(
+        "keyring {:?} is configured but fritzctrl was built without the keyring feature",
+        service
+    ))
+}
+
+fn first_line(content: &str) -> Option<String> {
+    content
+        .lines()
+        .next()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(unix)]
+fn warn_if_readable_by_others(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(meta) = std::fs::metadata(path) {
+        if meta.permissions().mode() & 0o077 != 0 {
+            eprintln!(
+                "warning: password file {} is accessible by other users, consider chmod 600",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_readable_by_others(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn password_command() {
+        assert_eq!(password_from_command("echo secret").unwrap(), "secret");
+        assert_eq!(
+            password_from_command("printf 'with space\\nsecond'").unwrap(),
+            "with space"
+        );
+        assert!(password_from_command("exit 1").is_err());
+        assert!(password_from_command("true").is_err());
+    }
+
+    #[test]
+    fn password_file() {
+        let path = std::env::temp_dir().join(format!("fritzctrl-test-{}", std::process::id()));
+        std::fs::write(&path, "secret\r\n").unwrap();
+        let password = PasswordSource::File(path.clone()).read();
+        std::fs::write(&path, "\n").unwrap();
+        let empty = password_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(password.unwrap(), "secret");
+        assert!(empty.is_err());
+        assert!(password_from_file(Path::new("/does/not/exist")).is_err());
+    }
+}
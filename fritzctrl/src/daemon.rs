@@ -0,0 +1,306 @@
+//! `fritzctrl daemon`: runs a schedule file until it is stopped, reloading
+//! the file whenever it changes.
+
+use crate::config::Profile;
+use crate::daylight::Location;
+use crate::schedule::retry::Retries;
+use crate::schedule::timing::{self, Ticker};
+use crate::schedule::{enforce, Action, RunOptions, Schedule};
+use crate::switch::SwitchResult;
+use chrono::prelude::*;
+use clap::ArgMatches;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+pub fn daemon(args: &ArgMatches) -> anyhow::Result<()> {
+    let profile = Profile::from_args(args)?;
+    let location = crate::location(args, &profile)?;
+    let path = args.get_one::<PathBuf>("schedule").unwrap();
+    let options = RunOptions::from_args(args);
+
+    let mut file = ScheduleFile::new(path, location);
+    let mut schedule = file.load()?;
+    let signals = Signals::register()?;
+    let mut client = profile.client(args)?;
+
+    log(format!(
+        "started with {} ({} entries)",
+        path.display(),
+        schedule.actions.len() + schedule.rules.len()
+    ));
+    let mut ticker = Ticker::new(options.missed, Local::now(), Instant::now());
+    log_next(&schedule, ticker.checked());
+    let mut enforced: Option<Instant> = None;
+    let mut retries = Retries::new(options.retry.clone());
+
+    loop {
+        if signals.stop.load(Ordering::Relaxed) {
+            log("stopping".to_string());
+            return Ok(());
+        }
+
+        let reload = signals.reload.swap(false, Ordering::Relaxed);
+        if reload || file.changed() {
+            match file.load() {
+                Ok(reloaded) => {
+                    schedule = reloaded;
+                    log(format!(
+                        "reloaded {} ({} entries)",
+                        path.display(),
+                        schedule.actions.len() + schedule.rules.len()
+                    ));
+                    log_next(&schedule, ticker.checked());
+                    // the new schedule may expect other states
+                    enforced = None;
+                }
+                Err(err) => log_error(format!(
+                    "cannot reload {}, keeping the previous schedule: {}",
+                    path.display(),
+                    err
+                )),
+            }
+        }
+
+        if let Some(interval) = options.enforce {
+            if enforced.is_none_or(|enforced| enforced.elapsed() >= interval) {
+                enforce_current_state(&schedule, &ticker, &mut client, &profile, &mut retries);
+                enforced = Some(Instant::now());
+            }
+        }
+
+        let tick = ticker.tick(&schedule, Local::now(), Instant::now());
+        if let Some(jump) = tick.jump {
+            log(format!(
+                "clock jumped by {}, e.g. after a suspend",
+                timing::format_duration(jump)
+            ));
+        }
+        for (action, skipped) in &tick.skipped {
+            log_error(format!(
+                "{} {} at {}: skipped, {}",
+                action.device_id(),
+                action.command,
+                action.time().format("%Y-%m-%d %H:%M:%S"),
+                skipped
+            ));
+        }
+        for action in &tick.run {
+            run(action, &mut client, &profile, &mut retries);
+        }
+        for (action, result) in retries.run_due(&mut client, Instant::now(), log_error) {
+            report(&action, result);
+        }
+        client.persist();
+        if !tick.run.is_empty() || !tick.skipped.is_empty() {
+            log_next(&schedule, ticker.checked());
+        }
+
+        std::thread::sleep(timing::TICK);
+    }
+}
+
+fn run(
+    action: &Action,
+    client: &mut fritzapi::FritzClient,
+    profile: &Profile,
+    retries: &mut Retries,
+) {
+    if let Some(result) = retries.run(action, client, profile, log_error) {
+        report(action, result);
+    }
+}
+
+fn report(action: &Action, result: anyhow::Result<Vec<SwitchResult>>) {
+    let what = format!("{} {}", action.device_id(), action.command);
+    match result {
+        Ok(results) if results.is_empty() => log_error(format!("{}: no devices", what)),
+        Ok(results) => {
+            for result in results {
+                let message = format!(
+                    "{}: {} ({}) {}",
+                    what, result.name, result.id, result.outcome
+                );
+                if result.outcome.is_ok() {
+                    log(message);
+                } else {
+                    log_error(message);
+                }
+            }
+        }
+        Err(err) => log_error(format!("{}: {}", what, err)),
+    }
+}
+
+fn enforce_current_state(
+    schedule: &Schedule,
+    ticker: &Ticker,
+    client: &mut fritzapi::FritzClient,
+    profile: &Profile,
+    retries: &mut Retries,
+) {
+    match enforce::actions(schedule, ticker.checked(), client, profile, log_error) {
+        Ok(actions) => {
+            for action in actions {
+                log(format!(
+                    "enforcing {} {} (scheduled at {})",
+                    action.device_id(),
+                    action.command,
+                    action.time().format("%Y-%m-%d %H:%M:%S")
+                ));
+                run(&action, client, profile, retries);
+            }
+        }
+        Err(err) => log_error(format!("cannot enforce the current state: {}", err)),
+    }
+}
+
+fn log_next(schedule: &Schedule, at: DateTime<Local>) {
+    match schedule.next_action(at) {
+        Some(action) => log(format!(
+            "next action {} {} at {}",
+            action.device_id(),
+            action.command,
+            action.time().format("%Y-%m-%d %H:%M:%S %Z")
+        )),
+        None => log("no actions left, waiting for changes of the schedule".to_string()),
+    }
+}
+
+fn log(message: String) {
+    println!(
+        "{} [daemon] {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        message
+    );
+}
+
+fn log_error(message: String) {
+    eprintln!(
+        "{} [daemon] {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        message
+    );
+}
+
+/// The schedule file and the time it was last modified when it was loaded.
+struct ScheduleFile {
+    path: PathBuf,
+    location: Option<Location>,
+    modified: Option<SystemTime>,
+}
+
+impl ScheduleFile {
+    fn new(path: &Path, location: Option<Location>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            location,
+            modified: None,
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Whether the file was modified (or replaced) since it was last loaded.
+    /// A file that is missing, e.g. while an editor saves it, is not a change.
+    fn changed(&self) -> bool {
+        match self.modified() {
+            Some(modified) => Some(modified) != self.modified,
+            None => false,
+        }
+    }
+
+    /// Loads the schedule, fails if any line is invalid, see
+    /// [Schedule::from_string].
+    fn load(&mut self) -> anyhow::Result<Schedule> {
+        let modified = self.modified();
+        let content = std::fs::read_to_string(&self.path)
+            .map_err(|err| anyhow::anyhow!("cannot read {}: {}", self.path.display(), err))?;
+        // remember the time even if the schedule is invalid so that the
+        // error is not repeated until the file changes again
+        self.modified = modified;
+
+        let schedule = Schedule::from_string(content)
+            .map_err(|err| anyhow::anyhow!("cannot parse {}: {}", self.path.display(), err))?
+            .with_location(self.location);
+        schedule.check_location()?;
+        Ok(schedule)
+    }
+}
+
+/// Flags set by the signal handlers: SIGTERM and SIGINT stop the daemon after
+/// the current action, a second one stops it immediately. SIGHUP reloads the
+/// schedule file.
+struct Signals {
+    stop: Arc<AtomicBool>,
+    reload: Arc<AtomicBool>,
+}
+
+impl Signals {
+    fn register() -> anyhow::Result<Self> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::flag;
+
+        let signals = Self {
+            stop: Arc::new(AtomicBool::new(false)),
+            reload: Arc::new(AtomicBool::new(false)),
+        };
+        for signal in [SIGINT, SIGTERM] {
+            flag::register_conditional_shutdown(signal, 1, Arc::clone(&signals.stop))?;
+            flag::register(signal, Arc::clone(&signals.stop))?;
+        }
+        #[cfg(unix)]
+        flag::register(signal_hook::consts::SIGHUP, Arc::clone(&signals.reload))?;
+        Ok(signals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_on_change() {
+        let dir = std::env::temp_dir().join(format!("fritzctrl-daemon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("schedule.txt");
+        std::fs::write(&path, "2025-10-11 09:00:00 Kaffee on\n").unwrap();
+
+        let mut file = ScheduleFile::new(&path, None);
+        assert!(file.changed());
+        assert_eq!(file.load().unwrap().actions.len(), 1);
+        assert!(!file.changed());
+
+        let modified = file.modified.unwrap() + std::time::Duration::from_secs(1);
+        std::fs::write(&path, "every day sunset Licht on\n").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(file.changed());
+        // sunset needs a location
+        assert!(file.load().is_err());
+        assert!(!file.changed());
+
+        // a single invalid line rejects the whole file
+        std::fs::write(
+            &path,
+            "2025-10-11 09:00:00 Kaffee on\n2025-10-11 10:00:00 Kaffee of\n",
+        )
+        .unwrap();
+        let err = file.load().unwrap_err().to_string();
+        assert!(err.contains("line 2"), "{}", err);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(!file.changed());
+        assert!(file.load().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
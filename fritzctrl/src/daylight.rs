@@ -1,7 +1,14 @@
+use crate::gazetteer;
+use crate::list::create_table;
+use crate::output::{self, OutputFormat};
 use chrono::prelude::*;
-use chrono::Datelike;
+use prettytable::{format, Cell, Row};
+use serde::Serialize;
+use std::io::Write;
+use sunrise::{Coordinates, DawnType, SolarDay, SolarEvent};
 
 #[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Location {
     /// The latitude in degrees.
     pub latitude: f64,
@@ -25,69 +32,432 @@ impl Location {
             v_accuracy: 0,
         }
     }
+}
 
-    #[allow(dead_code)]
-    pub fn berlin() -> Self {
-        Self {
-            latitude: 52.520,
-            longitude: 13.4050,
-            altitude: 61,
-            h_accuracy: 0,
-            v_accuracy: 0,
+impl std::str::FromStr for Location {
+    type Err = anyhow::Error;
+
+    /// `LATITUDE,LONGITUDE` in degrees, optionally followed by `,ALTITUDE` in
+    /// meters, or the name of a city known to the [gazetteer].
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = input.split(',').map(str::trim).collect();
+        if let [latitude, longitude, altitude @ ..] = parts.as_slice() {
+            if let (Ok(latitude), Ok(longitude)) = (latitude.parse(), longitude.parse()) {
+                if Coordinates::new(latitude, longitude).is_none() {
+                    anyhow::bail!("invalid location {:?}, latitude must be between -90 and 90 and longitude between -180 and 180", input);
+                }
+                let altitude = match altitude {
+                    [] => 0,
+                    [altitude] => altitude
+                        .trim_end_matches('m')
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("invalid altitude {:?}", altitude))?,
+                    _ => anyhow::bail!(
+                        "invalid location {:?}, expected LATITUDE,LONGITUDE[,ALTITUDE]",
+                        input
+                    ),
+                };
+                return Ok(Location {
+                    altitude,
+                    ..Location::new(latitude, longitude)
+                });
+            }
+        }
+        gazetteer::lookup(input).ok_or_else(|| {
+            anyhow::anyhow!(
+                "unknown location {:?}, expected LATITUDE,LONGITUDE[,ALTITUDE] or a city",
+                input
+            )
+        })
+    }
+}
+
+/// How far the sun is below the horizon at dawn and dusk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Twilight {
+    /// 6° below the horizon, bright enough to be outside without lights.
+    Civil,
+    /// 12° below the horizon.
+    Nautical,
+    /// 18° below the horizon, the sky is fully dark.
+    Astronomical,
+}
+
+impl From<Twilight> for DawnType {
+    fn from(twilight: Twilight) -> Self {
+        match twilight {
+            Twilight::Civil => DawnType::Civil,
+            Twilight::Nautical => DawnType::Nautical,
+            Twilight::Astronomical => DawnType::Astronomical,
         }
     }
 }
 
-pub fn default_location() -> anyhow::Result<Location> {
-    Ok(Location::berlin())
+/// Days on which the sun does not rise or set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Polar {
+    /// The sun stays above the horizon.
+    Day,
+    /// The sun stays below the horizon.
+    Night,
+}
+
+/// The times of the sun at a location on one day. Events that don't happen on
+/// that day, e.g. sunrise during the polar night or astronomical dusk during
+/// the summer in northern Europe, are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DaylightTimes {
+    pub date: NaiveDate,
+    pub polar: Option<Polar>,
+    pub astronomical_dawn: Option<DateTime<Local>>,
+    pub nautical_dawn: Option<DateTime<Local>>,
+    pub civil_dawn: Option<DateTime<Local>>,
+    pub sunrise: Option<DateTime<Local>>,
+    /// When the sun is highest.
+    pub solar_noon: Option<DateTime<Local>>,
+    pub sunset: Option<DateTime<Local>>,
+    pub civil_dusk: Option<DateTime<Local>>,
+    pub nautical_dusk: Option<DateTime<Local>>,
+    pub astronomical_dusk: Option<DateTime<Local>>,
+    /// Time between sunrise and sunset, 24 hours during the polar day.
+    pub day_length_seconds: i64,
+}
+
+impl DaylightTimes {
+    pub fn new(location: &Location, date: NaiveDate) -> anyhow::Result<Self> {
+        let day = solar_day(location, date).ok_or_else(|| {
+            anyhow::anyhow!(
+                "invalid location {}, {}",
+                location.latitude,
+                location.longitude
+            )
+        })?;
+        let event = |event| valid_time(day.event_time(event));
+
+        let sunrise = event(SolarEvent::Sunrise);
+        let sunset = event(SolarEvent::Sunset);
+        let (solar_noon, elevation) = solar_noon(location, date).unzip();
+        let polar = match (sunrise, sunset, elevation) {
+            (Some(_), Some(_), _) | (_, _, None) => None,
+            // without sunrise and sunset the sun stays on the side of the
+            // horizon where it is at noon
+            (_, _, Some(elevation)) if elevation > -SUNRISE_DEPRESSION => Some(Polar::Day),
+            _ => Some(Polar::Night),
+        };
+        let day_length = match (sunrise, sunset, polar) {
+            (Some(sunrise), Some(sunset), _) => sunset - sunrise,
+            (_, _, Some(Polar::Day)) => chrono::Duration::days(1),
+            _ => chrono::Duration::zero(),
+        };
+
+        Ok(DaylightTimes {
+            date,
+            polar,
+            astronomical_dawn: event(SolarEvent::Dawn(DawnType::Astronomical)),
+            nautical_dawn: event(SolarEvent::Dawn(DawnType::Nautical)),
+            civil_dawn: event(SolarEvent::Dawn(DawnType::Civil)),
+            sunrise,
+            solar_noon,
+            sunset,
+            civil_dusk: event(SolarEvent::Dusk(DawnType::Civil)),
+            nautical_dusk: event(SolarEvent::Dusk(DawnType::Nautical)),
+            astronomical_dusk: event(SolarEvent::Dusk(DawnType::Astronomical)),
+            day_length_seconds: day_length.num_seconds(),
+        })
+    }
+
+    /// Start of the `twilight` in the morning.
+    pub fn dawn(&self, twilight: Twilight) -> Option<DateTime<Local>> {
+        match twilight {
+            Twilight::Civil => self.civil_dawn,
+            Twilight::Nautical => self.nautical_dawn,
+            Twilight::Astronomical => self.astronomical_dawn,
+        }
+    }
+
+    /// End of the `twilight` in the evening.
+    pub fn dusk(&self, twilight: Twilight) -> Option<DateTime<Local>> {
+        match twilight {
+            Twilight::Civil => self.civil_dusk,
+            Twilight::Nautical => self.nautical_dusk,
+            Twilight::Astronomical => self.astronomical_dusk,
+        }
+    }
+
+    pub fn day_length(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.day_length_seconds)
+    }
+}
+
+/// Depression of the sun (degrees below the horizon) at sunrise and sunset,
+/// as used by [SolarEvent::Sunrise].
+const SUNRISE_DEPRESSION: f64 = 5.0 / 6.0;
+
+/// The sunrise crate ends up at the unix epoch for events that don't happen.
+fn valid_time(time: DateTime<Utc>) -> Option<DateTime<Local>> {
+    (time != DateTime::UNIX_EPOCH).then(|| time.with_timezone(&Local))
 }
 
+/// The sun at `location` on `date`, `None` for invalid coordinates. The
+/// horizon is lower when looking from above, so the altitude is taken into
+/// account.
+fn solar_day(location: &Location, date: NaiveDate) -> Option<SolarDay> {
+    let coordinates = Coordinates::new(location.latitude, location.longitude)?;
+    Some(SolarDay::new(coordinates, date).with_altitude(location.altitude as f64))
+}
+
+/// When the sun is highest at `location` on `date` (the solar transit) and
+/// its elevation then in degrees. Uses the formulas of the sunrise crate, which
+/// only exposes the events around the transit.
+fn solar_noon(location: &Location, date: NaiveDate) -> Option<(DateTime<Local>, f64)> {
+    use std::f64::consts::PI;
+    const J2000: f64 = 2_451_545.0;
+    const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+    const SECONDS_PER_DAY: f64 = 86_400.0;
+
+    let noon = date.and_hms_opt(12, 0, 0)?.and_utc().timestamp() as f64;
+    let day = noon / SECONDS_PER_DAY + UNIX_EPOCH_JULIAN_DAY - location.longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * (day - J2000))
+        .to_radians()
+        .rem_euclid(2.0 * PI);
+    let center =
+        (1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin())
+            .to_radians();
+    let perihelion = (102.93005 + 0.3179526 * (day - J2000) / 36525.0).to_radians();
+    let ecliptic_longitude = (anomaly + center + perihelion % (2.0 * PI) + 3.0 * PI) % (2.0 * PI);
+    let transit = day + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let declination = (ecliptic_longitude.sin() * 0.39779).asin();
+
+    let timestamp = ((transit - UNIX_EPOCH_JULIAN_DAY) * SECONDS_PER_DAY).round() as i64;
+    let elevation = 90.0 - (location.latitude - declination.to_degrees()).abs();
+    Some((
+        DateTime::from_timestamp(timestamp, 0)?.with_timezone(&Local),
+        elevation,
+    ))
+}
+
+/// Sunrise and sunset at `location` on `date`, `None` if the sun does not
+/// rise or set on that day.
+pub fn sunrise_sunset(
+    location: &Location,
+    date: NaiveDate,
+) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    let day = solar_day(location, date)?;
+    Some((
+        valid_time(day.event_time(SolarEvent::Sunrise))?,
+        valid_time(day.event_time(SolarEvent::Sunset))?,
+    ))
+}
+
+/// Prints the daylight times from `from_date` to `to_date`. The table output
+/// lists sunrise and sunset, shifted by `sunrise_shift` / `sunset_shift`, and
+/// with `details` all [DaylightTimes].
 pub fn print_daylight_times(
     location: Location,
-    from_date: chrono::NaiveDate,
-    to_date: chrono::NaiveDate,
+    from_date: NaiveDate,
+    to_date: NaiveDate,
     sunrise_shift: Option<chrono::Duration>,
     sunset_shift: Option<chrono::Duration>,
-) {
+    format: OutputFormat,
+    details: bool,
+) -> anyhow::Result<()> {
     if to_date < from_date {
-        panic!("print_daylight_times: to_date is before from_date");
+        anyhow::bail!("to-date is before from-date");
+    }
+
+    let days = from_date
+        .iter_days()
+        .take_while(|date| *date <= to_date)
+        .map(|date| {
+            let mut times = DaylightTimes::new(&location, date)?;
+            times.sunrise = times
+                .sunrise
+                .map(|time| time + sunrise_shift.unwrap_or_default());
+            times.sunset = times
+                .sunset
+                .map(|time| time + sunset_shift.unwrap_or_default());
+            Ok(times)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut out = std::io::stdout().lock();
+    match format {
+        OutputFormat::Table if details => print_daylight_table(&days)?,
+        OutputFormat::Table => {
+            for times in &days {
+                match (times.sunrise, times.sunset, times.polar) {
+                    (Some(sunrise), Some(sunset), _) => {
+                        writeln!(out, "sunrise: {}", sunrise.format("%Y-%m-%d %H:%M:%S"))?;
+                        writeln!(out, "sunset: {}", sunset.format("%Y-%m-%d %H:%M:%S"))?;
+                    }
+                    (_, _, Some(Polar::Day)) => writeln!(out, "polar day: {}", times.date)?,
+                    (_, _, Some(Polar::Night)) => writeln!(out, "polar night: {}", times.date)?,
+                    _ => writeln!(out, "no sunrise / sunset: {}", times.date)?,
+                }
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &days)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => output::print_ndjson(&mut out, &days)?,
+        OutputFormat::Csv => output::print_csv(&mut out, &days)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, &days)?,
+    }
+    Ok(())
+}
+
+fn print_daylight_table(days: &[DaylightTimes]) -> anyhow::Result<()> {
+    let mut table = create_table();
+    table.set_titles(Row::new(
+        [
+            "date",
+            "astro. dawn",
+            "naut. dawn",
+            "civil dawn",
+            "sunrise",
+            "noon",
+            "sunset",
+            "civil dusk",
+            "naut. dusk",
+            "astro. dusk",
+            "day length",
+        ]
+        .into_iter()
+        .map(|title| Cell::new_align(title, format::Alignment::CENTER))
+        .collect(),
+    ));
+    for times in days {
+        let time = |time: Option<DateTime<Local>>| {
+            Cell::new(&time.map_or("-".to_string(), |time| time.format("%H:%M:%S").to_string()))
+        };
+        let day_length = times.day_length();
+        table.add_row(Row::new(vec![
+            Cell::new(&times.date.to_string()),
+            time(times.dawn(Twilight::Astronomical)),
+            time(times.dawn(Twilight::Nautical)),
+            time(times.dawn(Twilight::Civil)),
+            time(times.sunrise),
+            time(times.solar_noon),
+            time(times.sunset),
+            time(times.dusk(Twilight::Civil)),
+            time(times.dusk(Twilight::Nautical)),
+            time(times.dusk(Twilight::Astronomical)),
+            Cell::new_align(
+                &format!(
+                    "{}{}h {:02}m",
+                    match times.polar {
+                        Some(Polar::Day) => "polar day, ",
+                        Some(Polar::Night) => "polar night, ",
+                        None => "",
+                    },
+                    day_length.num_hours(),
+                    day_length.num_minutes() % 60
+                ),
+                format::Alignment::RIGHT,
+            ),
+        ]));
     }
+    table.print_tty(false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut date = from_date;
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
 
-    while date <= to_date {
-        let (sunrise, sunset) = sunrise::sunrise_sunset(
-            location.latitude,
-            location.longitude,
-            date.year(),
-            date.month(),
-            date.day(),
+    #[test]
+    fn twilight_order() {
+        let berlin: Location = "52.52, 13.405".parse().unwrap();
+        let times = DaylightTimes::new(&berlin, date("2025-03-20")).unwrap();
+        assert_eq!(times.polar, None);
+        let events = [
+            times.dawn(Twilight::Astronomical),
+            times.dawn(Twilight::Nautical),
+            times.dawn(Twilight::Civil),
+            times.sunrise,
+            times.solar_noon,
+            times.sunset,
+            times.dusk(Twilight::Civil),
+            times.dusk(Twilight::Nautical),
+            times.dusk(Twilight::Astronomical),
+        ]
+        .map(Option::unwrap);
+        assert!(
+            events.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            events
         );
 
-        let sunrise = Local.from_utc_datetime(
-            &chrono::DateTime::from_timestamp(sunrise, 0)
-                .unwrap()
-                .naive_utc(),
+        // around the equinox day and night are about equally long and noon is
+        // halfway between sunrise and sunset
+        let day_length = times.day_length();
+        assert!((11 * 60..=13 * 60).contains(&day_length.num_minutes()));
+        let (sunrise, sunset) = (times.sunrise.unwrap(), times.sunset.unwrap());
+        let noon = times.solar_noon.unwrap();
+        assert!(((noon - sunrise) - (sunset - noon)).num_seconds().abs() <= 2);
+        assert_eq!(noon.with_timezone(&Utc).hour(), 11);
+        assert_eq!(
+            sunrise_sunset(&berlin, date("2025-03-20")),
+            Some((sunrise, sunset))
         );
-        let sunrise = if let Some(shift) = sunrise_shift {
-            sunrise + shift
-        } else {
-            sunrise
-        };
-        println!("sunrise: {}", sunrise.format("%Y-%m-%d %H:%M:%S"));
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let svalbard = Location::new(78.22, 15.65);
 
-        let sunset = Local.from_utc_datetime(
-            &chrono::DateTime::from_timestamp(sunset, 0)
-                .unwrap()
-                .naive_utc(),
+        let winter = DaylightTimes::new(&svalbard, date("2025-12-21")).unwrap();
+        assert_eq!(winter.polar, Some(Polar::Night));
+        assert_eq!((winter.sunrise, winter.sunset), (None, None));
+        assert_eq!(winter.day_length(), chrono::Duration::zero());
+        // still astronomical twilight around noon
+        assert!(winter.astronomical_dawn.is_some());
+        assert!(winter.solar_noon.is_some());
+        assert_eq!(sunrise_sunset(&svalbard, date("2025-12-21")), None);
+
+        let summer = DaylightTimes::new(&svalbard, date("2025-06-21")).unwrap();
+        assert_eq!(summer.polar, Some(Polar::Day));
+        assert_eq!(summer.civil_dusk, None);
+        assert_eq!(summer.day_length(), chrono::Duration::days(1));
+        assert!(summer.solar_noon.is_some());
+
+        assert!(DaylightTimes::new(&Location::new(91.0, 0.0), date("2025-06-21")).is_err());
+    }
+
+    #[test]
+    fn parse_location() {
+        let location: Location = "52.52,13.40".parse().unwrap();
+        assert_eq!(
+            (location.latitude, location.longitude, location.altitude),
+            (52.52, 13.40, 0)
         );
-        let sunset = if let Some(shift) = sunset_shift {
-            sunset + shift
-        } else {
-            sunset
-        };
-        println!("sunset: {}", sunset.format("%Y-%m-%d %H:%M:%S"));
+        let location: Location = "47.42, 10.98, 2962m".parse().unwrap();
+        assert_eq!(location.altitude, 2962);
+        assert_eq!("Wien".parse::<Location>().unwrap().altitude, 190);
+        assert!("95,13.40".parse::<Location>().is_err());
+        assert!("52.52,13.40,high".parse::<Location>().is_err());
+        assert!("Atlantis".parse::<Location>().is_err());
+    }
 
-        date += chrono::Duration::days(1);
+    #[test]
+    fn altitude_extends_the_day() {
+        let date = date("2025-03-20");
+        let valley: Location = "47.42,10.98".parse().unwrap();
+        let summit = Location {
+            altitude: 2962,
+            ..valley
+        };
+        let valley = DaylightTimes::new(&valley, date).unwrap();
+        let summit = DaylightTimes::new(&summit, date).unwrap();
+        assert!(summit.sunrise.unwrap() < valley.sunrise.unwrap());
+        assert!(summit.sunset.unwrap() > valley.sunset.unwrap());
+        assert!(summit.day_length() > valley.day_length() + chrono::Duration::minutes(10));
     }
 }
@@ -0,0 +1,190 @@
+//! A small offline list of cities for `--location NAME`, mostly in the
+//! countries where FRITZ!Boxes are common.
+
+use crate::daylight::Location;
+use clap_complete::engine::CompletionCandidate;
+
+/// Name (and alternative names separated by `|`), ISO country code, latitude,
+/// longitude and altitude in meters.
+const CITIES: &[(&str, &str, f64, f64, i64)] = &[
+    // Germany
+    ("Berlin", "DE", 52.5200, 13.4050, 34),
+    ("Hamburg", "DE", 53.5511, 9.9937, 6),
+    ("München|Munich", "DE", 48.1351, 11.5820, 519),
+    ("Köln|Cologne", "DE", 50.9375, 6.9603, 53),
+    ("Frankfurt am Main|Frankfurt", "DE", 50.1109, 8.6821, 112),
+    ("Stuttgart", "DE", 48.7758, 9.1829, 245),
+    ("Düsseldorf", "DE", 51.2277, 6.7735, 38),
+    ("Leipzig", "DE", 51.3397, 12.3731, 113),
+    ("Dortmund", "DE", 51.5136, 7.4653, 86),
+    ("Essen", "DE", 51.4556, 7.0116, 116),
+    ("Bremen", "DE", 53.0793, 8.8017, 12),
+    ("Dresden", "DE", 51.0504, 13.7373, 113),
+    ("Hannover|Hanover", "DE", 52.3759, 9.7320, 55),
+    ("Nürnberg|Nuremberg", "DE", 49.4521, 11.0767, 302),
+    ("Duisburg", "DE", 51.4344, 6.7623, 31),
+    ("Bochum", "DE", 51.4818, 7.2162, 100),
+    ("Wuppertal", "DE", 51.2562, 7.1508, 160),
+    ("Bielefeld", "DE", 52.0302, 8.5325, 118),
+    ("Bonn", "DE", 50.7374, 7.0982, 60),
+    ("Münster", "DE", 51.9607, 7.6261, 60),
+    ("Mannheim", "DE", 49.4875, 8.4660, 97),
+    ("Karlsruhe", "DE", 49.0069, 8.4037, 115),
+    ("Augsburg", "DE", 48.3705, 10.8978, 494),
+    ("Wiesbaden", "DE", 50.0782, 8.2398, 115),
+    ("Kiel", "DE", 54.3233, 10.1228, 5),
+    ("Rostock", "DE", 54.0924, 12.0991, 13),
+    ("Freiburg im Breisgau|Freiburg", "DE", 47.9990, 7.8421, 278),
+    ("Erfurt", "DE", 50.9848, 11.0299, 195),
+    ("Mainz", "DE", 49.9929, 8.2473, 89),
+    ("Magdeburg", "DE", 52.1205, 11.6276, 55),
+    ("Saarbrücken", "DE", 49.2402, 6.9969, 230),
+    ("Potsdam", "DE", 52.3906, 13.0645, 35),
+    ("Schwerin", "DE", 53.6355, 11.4012, 38),
+    ("Regensburg", "DE", 49.0134, 12.1016, 343),
+    ("Würzburg", "DE", 49.7913, 9.9534, 177),
+    ("Kassel", "DE", 51.3127, 9.4797, 167),
+    ("Aachen", "DE", 50.7753, 6.0839, 173),
+    ("Braunschweig", "DE", 52.2689, 10.5268, 75),
+    ("Chemnitz", "DE", 50.8278, 12.9214, 296),
+    ("Halle (Saale)|Halle", "DE", 51.4969, 11.9688, 87),
+    ("Lübeck", "DE", 53.8655, 10.6866, 13),
+    ("Oldenburg", "DE", 53.1435, 8.2146, 4),
+    ("Osnabrück", "DE", 52.2799, 8.0472, 63),
+    ("Heidelberg", "DE", 49.3988, 8.6724, 114),
+    ("Ulm", "DE", 48.4011, 9.9876, 478),
+    ("Flensburg", "DE", 54.7937, 9.4470, 12),
+    ("Görlitz", "DE", 51.1528, 14.9873, 199),
+    ("Garmisch-Partenkirchen", "DE", 47.4921, 11.0958, 708),
+    // Austria
+    ("Wien|Vienna", "AT", 48.2082, 16.3738, 190),
+    ("Graz", "AT", 47.0707, 15.4395, 353),
+    ("Linz", "AT", 48.3069, 14.2858, 266),
+    ("Salzburg", "AT", 47.8095, 13.0550, 424),
+    ("Innsbruck", "AT", 47.2692, 11.4041, 574),
+    ("Klagenfurt", "AT", 46.6247, 14.3053, 446),
+    // Switzerland
+    ("Zürich|Zurich", "CH", 47.3769, 8.5417, 408),
+    ("Bern", "CH", 46.9480, 7.4474, 540),
+    ("Basel", "CH", 47.5596, 7.5886, 260),
+    ("Genf|Genève|Geneva", "CH", 46.2044, 6.1432, 375),
+    ("Lausanne", "CH", 46.5197, 6.6323, 495),
+    ("Luzern|Lucerne", "CH", 47.0502, 8.3093, 436),
+    // Europe
+    ("Amsterdam", "NL", 52.3676, 4.9041, 0),
+    ("Brüssel|Bruxelles|Brussels", "BE", 50.8503, 4.3517, 13),
+    ("Luxemburg|Luxembourg", "LU", 49.6116, 6.1319, 300),
+    ("Paris", "FR", 48.8566, 2.3522, 35),
+    ("London", "GB", 51.5074, -0.1278, 11),
+    ("Dublin", "IE", 53.3498, -6.2603, 20),
+    ("Kopenhagen|Copenhagen", "DK", 55.6761, 12.5683, 14),
+    ("Oslo", "NO", 59.9139, 10.7522, 23),
+    ("Stockholm", "SE", 59.3293, 18.0686, 28),
+    ("Helsinki", "FI", 60.1699, 24.9384, 17),
+    ("Reykjavík", "IS", 64.1466, -21.9426, 0),
+    ("Tromsø", "NO", 69.6492, 18.9553, 10),
+    ("Longyearbyen", "NO", 78.2232, 15.6267, 10),
+    ("Warschau|Warszawa|Warsaw", "PL", 52.2297, 21.0122, 100),
+    ("Prag|Praha|Prague", "CZ", 50.0755, 14.4378, 235),
+    ("Budapest", "HU", 47.4979, 19.0402, 102),
+    ("Rom|Roma|Rome", "IT", 41.9028, 12.4964, 21),
+    ("Mailand|Milano|Milan", "IT", 45.4642, 9.1900, 120),
+    ("Madrid", "ES", 40.4168, -3.7038, 657),
+    ("Barcelona", "ES", 41.3874, 2.1686, 12),
+    ("Lissabon|Lisboa|Lisbon", "PT", 38.7223, -9.1393, 2),
+    ("Athen|Athens", "GR", 37.9838, 23.7275, 70),
+    ("Istanbul", "TR", 41.0082, 28.9784, 39),
+    ("Moskau|Moscow", "RU", 55.7558, 37.6173, 156),
+    // world
+    ("New York", "US", 40.7128, -74.0060, 10),
+    ("Chicago", "US", 41.8781, -87.6298, 181),
+    ("San Francisco", "US", 37.7749, -122.4194, 16),
+    ("Los Angeles", "US", 34.0522, -118.2437, 71),
+    ("Toronto", "CA", 43.6532, -79.3832, 76),
+    ("Mexiko-Stadt|Mexico City", "MX", 19.4326, -99.1332, 2240),
+    ("São Paulo", "BR", -23.5505, -46.6333, 760),
+    ("Buenos Aires", "AR", -34.6037, -58.3816, 25),
+    ("Kapstadt|Cape Town", "ZA", -33.9249, 18.4241, 15),
+    ("Kairo|Cairo", "EG", 30.0444, 31.2357, 23),
+    ("Nairobi", "KE", -1.2921, 36.8219, 1795),
+    ("Dubai", "AE", 25.2048, 55.2708, 5),
+    ("Delhi", "IN", 28.7041, 77.1025, 216),
+    ("Singapur|Singapore", "SG", 1.3521, 103.8198, 15),
+    ("Peking|Beijing", "CN", 39.9042, 116.4074, 44),
+    ("Tokio|Tokyo", "JP", 35.6762, 139.6503, 40),
+    ("Sydney", "AU", -33.8688, 151.2093, 58),
+    ("Auckland", "NZ", -36.8485, 174.7633, 30),
+];
+
+/// The city called `name`. Case, umlauts and accents, spaces and punctuation
+/// don't matter, e.g. `muenchen`, `München` and `Munich` are the same city.
+pub fn lookup(name: &str) -> Option<Location> {
+    let name = normalize(name);
+    CITIES
+        .iter()
+        .find(|(names, ..)| {
+            names
+                .split('|')
+                .any(|candidate| normalize(candidate) == name)
+        })
+        .map(|(_, _, latitude, longitude, altitude)| Location {
+            altitude: *altitude,
+            ..Location::new(*latitude, *longitude)
+        })
+}
+
+/// City names for completing `--location`.
+pub fn candidates() -> Vec<CompletionCandidate> {
+    CITIES
+        .iter()
+        .map(|(names, country, latitude, longitude, _)| {
+            let name = names.split('|').next().unwrap();
+            CompletionCandidate::new(name).help(Some(
+                format!("{} ({:.2}, {:.2})", country, latitude, longitude).into(),
+            ))
+        })
+        .collect()
+}
+
+fn normalize(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.to_lowercase().chars() {
+        match c {
+            'ä' => normalized.push_str("ae"),
+            'ö' => normalized.push_str("oe"),
+            'ü' => normalized.push_str("ue"),
+            'ß' => normalized.push_str("ss"),
+            'á' | 'à' | 'â' | 'ã' | 'å' => normalized.push('a'),
+            'é' | 'è' | 'ê' => normalized.push('e'),
+            'í' => normalized.push('i'),
+            'ó' | 'ô' | 'ø' => normalized.push('o'),
+            'ú' => normalized.push('u'),
+            c if c.is_alphanumeric() => normalized.push(c),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_cities() {
+        let munich = lookup("München").unwrap();
+        assert_eq!((munich.latitude, munich.altitude), (48.1351, 519));
+        assert_eq!(lookup("muenchen"), Some(munich));
+        assert_eq!(lookup("MUNICH"), Some(munich));
+        assert_eq!(lookup("halle saale"), lookup("Halle"));
+        assert_eq!(lookup("Sao Paulo").unwrap().longitude, -46.6333);
+        assert_eq!(lookup("Atlantis"), None);
+
+        for (names, ..) in CITIES {
+            for name in names.split('|') {
+                assert!(lookup(name).is_some(), "{}", name);
+            }
+        }
+        assert_eq!(candidates().len(), CITIES.len());
+    }
+}
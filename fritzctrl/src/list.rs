@@ -1,31 +1,66 @@
+use crate::chart::{self, ChartStyle};
+use crate::config::Profile;
+use crate::output::{self, OutputFormat, SeriesSummary, StatSample};
+use crate::selector::Inventory;
 use clap::ArgMatches;
 use prettytable::{format, Cell, Row, Table};
 use std::collections::HashSet;
 
 pub(crate) fn list(args: &ArgMatches) -> anyhow::Result<()> {
-    let user = args.get_one::<String>("user").unwrap();
-    let password = args.get_one::<String>("password").unwrap();
-    let ain = args.get_one::<String>("device");
-    let kinds = args.get_one::<String>("kinds").map(|kinds| {
-        crate::parser::parse_kinds(kinds)
-            .unwrap_or_default()
-            .into_iter()
-            .collect()
-    });
-    let limit = args
-        .get_one::<String>("limit")
-        .map(|limit| limit.parse().unwrap_or_default());
-
-    let mut client = fritzapi::FritzClient::new(user, password);
-    let devices = client.list_devices()?;
+    let profile = Profile::from_args(args)?;
+    let selector = profile
+        .expand_aliases(crate::selector::from_args(args))
+        .into_iter()
+        .next();
+    let kinds: Option<HashSet<_>> = args
+        .get_one::<Vec<fritzapi::DeviceStatsKind>>("kinds")
+        .map(|kinds| kinds.iter().copied().collect());
+    let limit = args.get_one::<usize>("limit").copied();
+    let format = *args.get_one::<OutputFormat>("output").unwrap();
+    let chart = args.get_one::<ChartStyle>("chart").copied();
+    let summary = args.get_flag("summary");
 
-    if let Some(ain) = ain {
-        let device = match devices.into_iter().find(|dev| dev.id() == *ain) {
-            None => {
-                return Err(anyhow::anyhow!("Cannot find device with ain {:?}", ain));
+    if selector.is_none() {
+        for (given, arg) in [
+            (kinds.is_some(), "--kinds"),
+            (chart.is_some(), "--chart"),
+            (summary, "--summary"),
+        ] {
+            if given {
+                return Err(anyhow::anyhow!("{} requires --device", arg));
             }
-            Some(device) => device,
-        };
+        }
+    }
+    if chart.is_some() && format != OutputFormat::Table {
+        return Err(anyhow::anyhow!("--chart can't be combined with --output"));
+    }
+
+    let mut client = profile.client(args)?;
+
+    if let Some(selector) = selector {
+        let inventory = Inventory::fetch(&mut client, std::slice::from_ref(&selector))?;
+        let found = inventory.resolve(&selector)?;
+        if found.len() > 1 && kinds.is_none() && chart.is_none() && !summary {
+            let devices = found.into_iter().cloned().collect::<Vec<_>>();
+            return print_devices(format, &devices);
+        }
+        let device = inventory.resolve_one(&selector)?.clone();
+
+        if let Some(style) = chart {
+            return print_charts(&mut client, &device, &kinds, limit, style);
+        }
+        if summary {
+            let summaries = series_summaries(&mut client, &device, &kinds, limit)?;
+            return match format {
+                OutputFormat::Table => print_summary_table(&summaries),
+                format => output::print_summaries(format, &summaries),
+            };
+        }
+
+        if format != OutputFormat::Table {
+            let stats = device_stat_samples(&mut client, &device, &kinds, limit)?;
+            return output::print_device_stats(format, &device, &stats);
+        }
 
         let tables = device_detail_table(&mut client, &device, &kinds, limit)?
             .into_iter()
@@ -41,13 +76,20 @@ pub(crate) fn list(args: &ArgMatches) -> anyhow::Result<()> {
         return Ok(());
     }
 
+    let devices = client.list_devices()?;
     info!("found {} devices", devices.len());
-    print_device_table(&devices);
+    print_devices(format, &devices)
+}
 
+fn print_devices(format: OutputFormat, devices: &[fritzapi::AVMDevice]) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => print_device_table(devices),
+        format => output::print_devices(format, devices)?,
+    }
     Ok(())
 }
 
-fn create_table() -> Table {
+pub(crate) fn create_table() -> Table {
     let mut table = Table::new();
     let fmt = format::FormatBuilder::new()
         .padding(1, 1)
@@ -73,11 +115,17 @@ fn print_device_table(devices: &[fritzapi::AVMDevice]) {
     ]));
 
     for device in devices {
+        let id = device.id().to_string();
+        let state = if device.is_tx_busy() {
+            format!("{} (pending)", device.state())
+        } else {
+            device.state().to_string()
+        };
         table.add_row(Row::new(vec![
-            Cell::new(device.id()),
+            Cell::new(&id),
             Cell::new(device.productname()),
             Cell::new(device.name()),
-            Cell::new(device.state()),
+            Cell::new(&state),
         ]));
     }
     table.printstd();
@@ -107,28 +155,158 @@ fn device_detail_table(
                     format::Alignment::CENTER,
                 ),
             ]));
-            print_stat(&mut table, &stat, limit);
+            print_stat(&mut table, device, &stat, limit);
             Some(Ok(table))
         })
         .collect()
 }
 
-fn print_stat(table: &mut Table, stat: &fritzapi::DeviceStats, limit: Option<usize>) {
+fn print_stat(
+    table: &mut Table,
+    device: &fritzapi::AVMDevice,
+    stat: &fritzapi::DeviceStats,
+    limit: Option<usize>,
+) {
     let now = chrono::Local::now();
-    for values in &stat.values {
-        let mut n = 0;
-        let mut time = now;
-        for val in &values.values {
-            table.add_row(Row::new(vec![
-                Cell::new(&time.format("%Y-%m-%d %H:%M:%S").to_string()),
-                Cell::new_align(&format!("{:.1}", val), format::Alignment::RIGHT),
-            ]));
-            time -= chrono::Duration::seconds(values.grid as i64);
-            n += 1;
-            match limit {
-                Some(limit) if n > limit => break,
-                _ => continue,
-            }
+    for sample in StatSample::from_stats(device, stat, limit, now) {
+        table.add_row(Row::new(vec![
+            Cell::new(&sample.time.format("%Y-%m-%d %H:%M:%S").to_string()),
+            Cell::new_align(&format!("{:.1}", sample.value), format::Alignment::RIGHT),
+        ]));
+    }
+}
+
+pub(crate) fn device_stat_samples(
+    client: &mut fritzapi::FritzClient,
+    device: &fritzapi::AVMDevice,
+    kinds: &Option<HashSet<fritzapi::DeviceStatsKind>>,
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<StatSample>> {
+    let now = chrono::Local::now();
+    Ok(client
+        .device_stats(device.id())?
+        .into_iter()
+        .filter(|stat| match kinds {
+            Some(kinds) => kinds.contains(&stat.kind),
+            None => true,
+        })
+        .flat_map(|stat| StatSample::from_stats(device, &stat, limit, now))
+        .collect())
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// The samples of each series of `device`, with the interval between two
+/// samples in seconds.
+fn device_series(
+    client: &mut fritzapi::FritzClient,
+    device: &fritzapi::AVMDevice,
+    kinds: &Option<HashSet<fritzapi::DeviceStatsKind>>,
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<(usize, Vec<StatSample>)>> {
+    let now = chrono::Local::now();
+    Ok(client
+        .device_stats(device.id())?
+        .into_iter()
+        .filter(|stat| match kinds {
+            Some(kinds) => kinds.contains(&stat.kind),
+            None => true,
+        })
+        .flat_map(|stat| {
+            stat.values
+                .iter()
+                .map(|values| {
+                    let samples = StatSample::from_series(device, stat.kind, values, limit, now);
+                    (values.grid, samples)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+fn print_charts(
+    client: &mut fritzapi::FritzClient,
+    device: &fritzapi::AVMDevice,
+    kinds: &Option<HashSet<fritzapi::DeviceStatsKind>>,
+    limit: Option<usize>,
+    style: ChartStyle,
+) -> anyhow::Result<()> {
+    let width = chart::terminal_width();
+    for (i, (grid, samples)) in device_series(client, device, kinds, limit)?
+        .iter()
+        .enumerate()
+    {
+        let Some(first) = samples.first() else {
+            continue;
+        };
+        if i > 0 {
+            println!();
         }
+        println!(
+            "{:?} ({}), every {}",
+            first.kind,
+            first.unit,
+            format_grid(*grid)
+        );
+        print!(
+            "{}",
+            chart::render(samples, style, width, chart::DEFAULT_HEIGHT)
+        );
+    }
+    Ok(())
+}
+
+fn series_summaries(
+    client: &mut fritzapi::FritzClient,
+    device: &fritzapi::AVMDevice,
+    kinds: &Option<HashSet<fritzapi::DeviceStatsKind>>,
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<SeriesSummary>> {
+    Ok(device_series(client, device, kinds, limit)?
+        .iter()
+        .filter_map(|(grid, samples)| SeriesSummary::from_samples(samples, *grid))
+        .collect())
+}
+
+fn print_summary_table(summaries: &[SeriesSummary]) -> anyhow::Result<()> {
+    let mut table = create_table();
+    table.set_titles(Row::new(
+        [
+            "series", "samples", "from", "to", "min", "max", "mean", "total Wh",
+        ]
+        .into_iter()
+        .map(|title| Cell::new_align(title, format::Alignment::CENTER))
+        .collect(),
+    ));
+    for summary in summaries {
+        let number =
+            |value: f32| Cell::new_align(&format!("{:.1}", value), format::Alignment::RIGHT);
+        table.add_row(Row::new(vec![
+            Cell::new(&format!(
+                "{:?} ({}), every {}",
+                summary.kind,
+                summary.unit,
+                format_grid(summary.grid)
+            )),
+            Cell::new_align(&summary.count.to_string(), format::Alignment::RIGHT),
+            Cell::new(&summary.from.format("%Y-%m-%d %H:%M").to_string()),
+            Cell::new(&summary.to.format("%Y-%m-%d %H:%M").to_string()),
+            number(summary.min),
+            number(summary.max),
+            number(summary.mean),
+            summary.total_watt_h.map(number).unwrap_or_default(),
+        ]));
+    }
+    table.print_tty(false)?;
+    Ok(())
+}
+
+/// `900` -> `15 min`.
+fn format_grid(seconds: usize) -> String {
+    match seconds {
+        s if s >= 86400 && s % 86400 == 0 => format!("{} d", s / 86400),
+        s if s >= 3600 && s % 3600 == 0 => format!("{} h", s / 3600),
+        s if s >= 60 && s % 60 == 0 => format!("{} min", s / 60),
+        s => format!("{} s", s),
     }
 }
@@ -5,10 +5,16 @@
 //! The command line tool has several subcommands:
 //! - list: List all devices or list sensor data of individual device.
 //! - switch: Turn device on / off.
-//! - schedule: Reads and parses lines from stdin that contain date, device id, and state. Runs until all commands are processed.
-//! - daylight: Helper command that prints sunrise / sunset times for a given location and time range.
+//! - schedule: Reads and parses lines from stdin that contain date (or a recurring rule), device id, and state. Runs until all commands are processed.
+//! - daemon: Runs a schedule file until it is stopped, reloading the file when it changes.
+//! - daylight: Helper command that prints sunrise / sunset (and twilight) times for a given location and time range.
+//! - rename: Renames one device or many devices listed in a CSV file.
+//! - raw: Sends an arbitrary command to the AHA interface and prints the response.
+//! - watch: Shows the state and readings of devices in a continuously refreshed table.
+//! - tui: Full-screen interface to browse devices, groups and templates and to control them with the keyboard.
+//! - completions: Prints the completion script for bash, zsh, fish, elvish or powershell.
 //!
-//! Pretty much all commands need the fritz.box user name and password. You can set it in an env vars `FRTIZ_USER` and `FRITZ_PASSWORD` or pass it as arguments to the subcommands (the user / password combo is the same you use for <http://fritz.box>).
+//! Pretty much all commands need the fritz.box user name and password. You can set it in an env vars `FRTIZ_USER` and `FRITZ_PASSWORD`, pass it as arguments to the subcommands (the user / password combo is the same you use for <http://fritz.box>) or store it in a profile of the configuration file, see below. Instead of `--password`, which shows up in the shell history and the process list, the password can be read from a file with `--password-file FILE` or from the output of a command such as `--password-command "pass show fritz"`. If no password is given at all it is asked for. A box that is not reachable as `fritz.box` can be set with `--host` / `FRITZ_HOST`.
 //!
 //! ## Examples
 //!
@@ -26,9 +32,9 @@
 //!  11630 0123723 | FRITZ!DECT 200 | FRITZ!DECT 200 #5           | off
 //! ```
 //!
-//! ### List last 5 temperature readings of one device
+//! ### List last 4 temperature readings of one device
 //!
-//! `$ fritzctrl list --device "11630 0123723" --kinds temp --limit 3`
+//! `$ fritzctrl list --device "11630 0123723" --kinds temp --limit 4`
 //!
 //! ```text
 //!         time         | Temperature (°C)
@@ -39,15 +45,118 @@
 //!  2021-01-31 22:57:31 |             23.0
 //! ```
 //!
+//! ### Charts and summaries of the readings
+//!
+//! `$ fritzctrl list --device "11630 0123723" --kinds power --chart`
+//!
+//! ```text
+//! Power (W), every 10 min
+//! 35.0┤                      ▃▆▇███▅▁
+//!     │                    ▂█████████▇▅
+//!     │                  ▃█████████████▇▁
+//!     │               ▃███████████████████▇▁
+//! 17.5┤              ▅██████████████████████▅
+//!     │            ▃▆█████████████████████████▂
+//!     │▇▅▂       ▃▇████████████████████████████▅▂
+//!     │███▇▃▃▃▃▆█████████████████████████████████▇▆▃
+//!  0.0┤█████████████████████████████████████████████
+//!     └─────────────────────────────────────────────
+//!      21:55                                   13:45
+//! ```
+//!
+//! draws each series as bar chart, `--chart line` uses plain ASCII instead of the Unicode block elements. `--summary` prints the number of readings, min, max and mean of each series, and for power and energy the total energy in Wh; it works with `--output` as well.
+//!
+//! ### Output for scripts
+//!
+//! The global `--output` option prints devices and readings as `json`, `ndjson`, `csv` or `yaml` instead of tables:
+//!
+//! `$ fritzctrl --output json list | jq '.[] | select(.type == "FritzDect2XX") | .name'`
+//!
+//! `$ fritzctrl --output ndjson list --device "11630 0123723" --kinds power | jq .value`
+//!
 //! ### Turn device on
 //!
 //! `$ fritzctrl switch --device "11630 0123723" --on`
 //!
+//! ### Turn several devices off at once
+//!
+//! `$ fritzctrl switch --device "Laufband*" --device group:Wohnzimmer --off`
+//!
+//! prints the result for each device and exits with a non-zero code if any of them could not be switched.
+//!
+//! ### Watch devices
+//!
+//! `$ fritzctrl watch --interval 5s --high-refresh`
+//!
+//! shows the state, power (W), voltage (V), temperature (°C) and energy (Wh) of all devices (or the ones selected with `--device`) in a table that is refreshed every interval, changed values are highlighted. `--high-refresh` keeps the fritz box updating the readings every ~10 seconds instead of every 2 minutes. With `--once-on-change`, or when the output is not a terminal, the devices are only printed when their values change, e.g. to feed other tools:
+//!
+//! `$ fritzctrl --output ndjson watch --device "Laufband" | jq --unbuffered .milliwatts`
+//!
+//! ### Interactive terminal UI
+//!
+//! `$ fritzctrl tui`
+//!
+//! lists the devices, groups and templates (switch between them with `tab`) and shows the power and temperature history of the selected device. `space` toggles the selected device or group, `+` / `-` change the target temperature of a thermostat by 0.5 °C, `enter` applies the selected template, `r` refreshes and `q` quits. The list is refreshed every `--interval` (10s by default).
+//!
+//! ### Selecting devices
+//!
+//! Instead of the ain, devices can be addressed by name (`"Laufband"`, case does not matter if it is unique), by a glob on the name (`"Laufband*"`), a regular expression (`re:^Lauf` or `/^Lauf/`), a glob on the product name (`product:FRITZ!DECT 2*`) or by group (`group:Wohnzimmer`). Use `name:` to match names containing glob characters literally. The same selectors work in schedule lines.
+//!
+//!
+//! ### Shell completion
+//!
+//! `$ echo 'source <(fritzctrl completions bash)' >> ~/.bashrc`
+//!
+//! enables tab completion of subcommands and options in bash, use `completions zsh` for zsh and `fritzctrl completions fish | source` in `~/.config/fish/config.fish` for fish. Values of `--device` complete to the names and AINs of the devices, the groups and the aliases of the profile. They are fetched from the fritz box with the cached session (see below) or a password that can be read without a prompt; the completion never asks for the password.
+//!
+//! ### Sessions
+//!
+//! Logging in to the fritz box is slow, so fritzctrl keeps the session id in `$XDG_RUNTIME_DIR/fritzctrl/` (readable only by you) and reuses it in later calls until it expires. Use `--no-session-cache` to log in every time.
+//!
+//! ### Configuration file and profiles
+//!
+//! To manage more than one fritz box, put named profiles into `~/.config/fritzctrl/config.toml` (or the file passed with `--config`) and select one with `--profile NAME` / `FRITZ_PROFILE`:
+//!
+//! ```toml
+//! default_profile = "home"
+//!
+//! [profiles.home]
+//! user = "fritz1234"
+//! password_command = "pass show fritz/home"
+//! location = { latitude = 52.52, longitude = 13.40 }
+//!
+//! [profiles.home.aliases]
+//! kaffee = "11630 0123723"
+//! wohnzimmer = "group:Wohnzimmer"
+//!
+//! [profiles.office]
+//! host = "https://192.168.10.1"
+//! user = "office"
+//! tls = { ca_cert = "~/.config/fritzctrl/office.pem" }
+//! ```
+//!
+//! Instead of `password_command` a profile can use `password_file = "~/.fritz-password"`, or `keyring = "SERVICE"` to read the password from the OS keyring / Secret Service. The keyring needs fritzctrl to be installed with `--features keyring`, the password is asked for and stored on first use.
+//!
+//! `host` defaults to `fritz.box`. For https connections the certificate of the box can be trusted with `ca_cert`, or any certificate is accepted with `accept_invalid_certs = true`. Aliases can be used wherever a device is expected, e.g. `fritzctrl switch --device kaffee --on`, and `location` is used by `daylight` and by sunrise / sunset schedule entries when no `--location` is given. The location is either a table with `latitude`, `longitude` and optionally `altitude` in meters, or a string like `"52.52,13.40"` or the name of a city such as `"Berlin"`, `"Wien"` or `"Zürich"` from the list of cities built into fritzctrl. Command line arguments override the profile, the `FRITZ_HOST`, `FRITZ_USER` and `FRITZ_PASSWORD` environment variables are used for settings that the profile does not contain.
+//!
+//! ### Rename devices
+//!
+//! `$ fritzctrl rename --device "11630 0123723" "Laufband"`
+//!
+//! or, for many devices at once, from a CSV file with `ain,name` lines:
+//!
+//! `$ fritzctrl rename --csv names.csv`
+//!
+//! ### Send a command that fritzctrl does not know about
+//!
+//! `$ fritzctrl raw getswitchname "11630 0123723"`
+//!
+//! Additional request parameters can be passed with `--param key=value`.
 //!
 //! ### Schedule switching a device based on daylight hours
 //!
 //! 1. First figure out what the times you want to turn the device on / off are. E.g.
-//!    `$ fritzctrl daylight --from-date 2021-02-01 --to-date 2021-02-03 --shift-from="-30min" --shift-to="30hour"`
+//!    `$ fritzctrl daylight --location Berlin --from-date 2021-02-01 --to-date 2021-02-03 --shift-from="-30min" --shift-to="30hour"`
 //!    generates sunrise / sunset times shifted by -30 minutes (sunrise) and +30 minutes sunset:
 //!
 //! ```text
@@ -60,6 +169,8 @@
 //! sunset: 2021-02-03 17:24:30
 //! ```
 //!
+//! `--details` prints a table with dawn and dusk at civil, nautical and astronomical twilight, solar noon and the day length instead, `--output json|ndjson|csv|yaml` prints all of these times as records. Days without sunrise or sunset (polar day / night) are marked as such. `--location` takes `"LATITUDE,LONGITUDE"`, optionally with the altitude in meters (`"47.42,10.98,2962"`, the sun rises earlier on a mountain), or a city name; `FRITZ_LOCATION` or the location of the profile are used otherwise. There is no default location.
+//!
 //! Then store some commands into a file:
 //!
 //! `fritz-commands.txt`:
@@ -74,7 +185,85 @@
 //! You can run start processing those commands with
 //! `$ cat fritz-commands.txt | fritzctrl schedule`
 //!
-//! The program will wait until the next command should run and then toggle the device state. Once all commands are done the app will exit.
+//! The program will wait until the next command should run and then toggle the device state. Once all commands are done the app will exit, unless the schedule contains recurring lines.
+//!
+//! ### Recurring schedules
+//!
+//! Instead of listing every date, schedule lines can repeat. They are expanded one occurrence at a time, so a schedule with recurring lines keeps running:
+//!
+//! ```text
+//! # every Monday to Friday
+//! every weekday 06:30 "Kaffee" on
+//! every weekday 07:15 "Kaffee" off
+//! # days can be listed: day, weekday, weekend, mon,wed,fri or sat-sun
+//! every sat,sun 09:00 "Kaffee" on
+//! # only during the holidays
+//! from 2025-12-01 to 2026-01-06 every day 17:00 "Lichterkette" on
+//! from 2025-12-01 to 2026-01-06 every day 23:00 "Lichterkette" off
+//! # cron expressions: minute hour day-of-month month day-of-week (0 or 7 is Sunday)
+//! cron "*/30 8-18 * * 1-5" "Pumpe" on
+//! # relative to sunrise / sunset, every day or on the given days
+//! sunset-30min "Lichterkette" on
+//! every weekday sunrise-1h not before 06:00 "Lichterkette" on
+//! every weekday sunrise+15min "Lichterkette" off
+//! sunset+4h not after 22:30 "Lichterkette" off
+//! ```
+//!
+//! The date range is optional, `from DATE` and `to DATE` can also be used alone. Empty lines and lines starting with `#` are ignored. Sunrise and sunset are calculated for the location given with `--location` or the location of the profile. `not before` and `not after` keep the time within a window, e.g. the lights are not switched on before 06:00 even if the sun rises late.
+//!
+//! ### Schedule actions
+//!
+//! Besides `on` and `off`, a schedule line can end with any of these actions:
+//!
+//! ```text
+//! 2025-12-01 06:00:00 "Kaffee" toggle
+//! every weekday 06:00 "Heizung Bad" temperature 22.5
+//! every weekday 08:00 "Heizung Bad" temperature off
+//! sunset "Stehlampe" brightness 40%
+//! sunset "Stehlampe" color 35,214
+//! every day 22:00 "Stehlampe" color-temperature 2700K
+//! sunset+15min "Rollladen Wohnzimmer" blind close
+//! every day 23:00 "Alles aus" apply
+//! every weekend 09:00 "Bewegung Flur" disable
+//! every weekday 18:00 "Bewegung Flur" enable
+//! ```
+//!
+//! `temperature` sets the target of a thermostat (8 to 28 °C, `on` or `off`), `brightness` and `color HUE,SATURATION` (hue 0 to 359, saturation 0 to 255) and `color-temperature` (2700 to 6500 K) control lights, and `blind` opens, closes or stops a blind. `apply` applies the template and `enable` / `disable` switch the trigger with the given name or identifier instead of a device. Only `on` and `off` are verified after switching and enforced with `--enforce-current-state`.
+//!
+//! ### Running as a daemon
+//!
+//! `fritzctrl daemon --schedule schedule.txt` runs the same kind of schedule from a file and keeps running when no actions are left. The file is reloaded when it changes, so entries can be added without restarting, and `SIGHUP` reloads it as well. If the changed file cannot be read or lacks a location for sunrise / sunset entries the previous schedule stays active. Every executed action is logged with the outcome for each device:
+//!
+//! ```text
+//! 2025-12-01 16:02:00 [daemon] started with schedule.txt (4 entries)
+//! 2025-12-01 16:02:00 [daemon] next action Lichterkette on at 2025-12-01 16:05:00 CET
+//! 2025-12-01 16:05:00 [daemon] Lichterkette on: Lichterkette (11630 0123723) ok (on)
+//! ```
+//!
+//! `SIGTERM` or `SIGINT` (Ctrl-C) stops the daemon once a running action is done, a second signal stops it immediately.
+//!
+//! Both `schedule` and `daemon` check the clock every second instead of sleeping until the next action, so a suspend or a clock change is noticed. Actions that are more than a minute late, e.g. because the computer was asleep, are handled according to `--missed`: `skip` drops them, `late` (the default) runs them if they are not later than `--grace` (10 minutes by default) and `catch-up` runs the last missed action of each device. When the clock is set back, actions that already ran are not repeated. A time that does not exist because the clock is set forward for daylight saving time, e.g. 02:30, runs right after the change (at 03:30), a time that exists twice when the clock is set back runs once, the first time.
+//!
+//! Switching is retried when a device is not present, the request fails or the device is not in the expected state when its state is read again afterwards (`--no-verify` skips that check). `--retries` (3 by default) sets how often, the first retry waits `--retry-delay` (10 seconds by default) and each further one twice as long. Locked devices are not retried. When an action still fails, the command given with `--on-failure` is run with the shell and gets the details in `FRITZCTRL_TIME`, `FRITZCTRL_DEVICE`, `FRITZCTRL_ACTION` and `FRITZCTRL_ERROR`:
+//!
+//! ```text
+//! $ fritzctrl daemon --schedule schedule.txt --on-failure 'notify-send "$FRITZCTRL_DEVICE $FRITZCTRL_ACTION failed" "$FRITZCTRL_ERROR"'
+//! ```
+//!
+//! With `--enforce-current-state` the devices are brought into the state the schedule says they should be in: on start, after the schedule file was reloaded and every `--enforce-interval` (10 minutes by default), each device that is not in the state of the last action the schedule had for it is switched. This helps when the scheduler is started in the middle of an on period or a device was switched by hand. When several entries of the schedule match the same device, the latest of their actions wins.
+//!
+//! ### Checking a schedule
+//!
+//! `fritzctrl schedule --check --schedule schedule.txt` reports problems without switching anything: lines that cannot be parsed, devices that don't exist, dates in the past, date ranges that have ended, sunrise / sunset entries without a location and lines that switch the same device on and off at the same time. The exit code is 1 if errors were found. When the fritz box cannot be reached, the device list of the last successful check is used.
+//!
+//! ```text
+//! $ fritzctrl schedule --check --schedule schedule.txt
+//! line 3: error: No device with ain or name "Lichterkete"
+//! line 7: warning: the date range ended on 2025-01-06
+//! 1 errors, 1 warnings
+//! ```
+//!
+//! `--preview` prints the actions of the next 7 days (or until `--until DATE`) with the line they come from, in any `--output` format. Without `--schedule` the schedule is read from stdin.
 //!
 //! ## Why???
 //!
@@ -93,16 +282,32 @@
 extern crate tracing;
 
 use chrono::{prelude::*, Duration};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::parser::ValueSource;
 use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use clap_complete::engine::ArgValueCandidates;
 use std::io::Read;
 use std::process::exit;
 use tracing_subscriber::prelude::*;
 
+mod chart;
+mod completions;
+mod config;
+mod credentials;
+mod daemon;
 mod daylight;
+mod gazetteer;
 mod list;
+mod output;
 mod parser;
+mod raw;
+mod rename;
 mod schedule;
+mod selector;
+mod session;
 mod switch;
+mod tui;
+mod watch;
 
 fn daylight(args: &ArgMatches) {
     // get date arguments
@@ -123,22 +328,67 @@ fn daylight(args: &ArgMatches) {
     let shift_from = args.get_one::<Duration>("shift-from").copied();
     let shift_to = args.get_one::<Duration>("shift-to").copied();
 
-    // get location
-    let latitude = args.get_one::<f64>("latitude");
-    let longitude = args.get_one::<f64>("longitude");
-    let location = match (latitude, longitude) {
-        (Some(latitude), Some(longitude)) => daylight::Location::new(*latitude, *longitude),
-        _ => {
-            if let Ok(loc) = daylight::default_location() {
-                loc
-            } else {
-                println!("Could not determine location for daylight time. Maybe use --latitude / --longitude?");
-                exit(1);
-            }
+    // get location, the profile is only needed without location arguments
+    let location = match location_from_args(args) {
+        Some(location) => Ok(Some(location)),
+        None => config::Profile::from_args(args).and_then(|profile| profile.location()),
+    };
+    let location = match location {
+        Ok(Some(location)) => location,
+        Ok(None) => {
+            println!("Could not determine location for daylight time. Use --location, --latitude / --longitude or set the location of the profile.");
+            exit(1);
+        }
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
         }
     };
 
-    daylight::print_daylight_times(location, from_date, to_date, shift_from, shift_to);
+    let format = *args.get_one::<output::OutputFormat>("output").unwrap();
+    let details = args.get_flag("details");
+    if let Err(err) = daylight::print_daylight_times(
+        location, from_date, to_date, shift_from, shift_to, format, details,
+    ) {
+        println!("Error: {}", err);
+        exit(1);
+    }
+}
+
+/// The location given by `--location`, `--latitude` / `--longitude` or the
+/// profile. Arguments on the command line take precedence over the
+/// `FRITZ_LOCATION`, `LATITUDE` and `LONGITUDE` environment variables.
+fn location(
+    args: &ArgMatches,
+    profile: &config::Profile,
+) -> anyhow::Result<Option<daylight::Location>> {
+    match location_from_args(args) {
+        Some(location) => Ok(Some(location)),
+        None => profile.location(),
+    }
+}
+
+/// The location given by `--location` or `--latitude` / `--longitude`, or by
+/// their environment variables.
+fn location_from_args(args: &ArgMatches) -> Option<daylight::Location> {
+    let named = args
+        .get_one::<daylight::Location>("location")
+        .map(|location| (args.value_source("location"), *location));
+    let coordinates = match (
+        args.get_one::<f64>("latitude"),
+        args.get_one::<f64>("longitude"),
+    ) {
+        (Some(latitude), Some(longitude)) => Some((
+            args.value_source("latitude"),
+            daylight::Location::new(*latitude, *longitude),
+        )),
+        _ => None,
+    };
+    [named, coordinates]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(source, _)| *source != Some(ValueSource::CommandLine))
+        .map(|(_, location)| location)
 }
 
 // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
@@ -149,55 +399,208 @@ enum Commands {
     Switch,
     Daylight,
     Schedule,
+    Daemon,
+    Rename,
+    Raw,
+    Watch,
+    Tui,
+    Completions,
 }
 
-fn main() {
-    dotenv::dotenv().ok();
-
+/// The command line interface, also used to complete it.
+fn cli() -> Command {
     let user = Arg::new("user")
         .long("user")
         .short('u')
         .value_name("USER")
-        .required(true)
         .env("FRITZ_USER");
 
     let password = Arg::new("password")
         .long("password")
         .short('p')
-        .required(true)
-        .env("FRITZ_PASSWORD");
+        .env("FRITZ_PASSWORD")
+        .hide_env_values(true)
+        .help("The password, visible in the process list, prefer --password-file or --password-command");
+
+    let password_file = Arg::new("password-file")
+        .long("password-file")
+        .value_name("FILE")
+        .env("FRITZ_PASSWORD_FILE")
+        .value_parser(value_parser!(std::path::PathBuf))
+        .help("Read the password from the first line of FILE");
+
+    let password_command = Arg::new("password-command")
+        .long("password-command")
+        .value_name("COMMAND")
+        .env("FRITZ_PASSWORD_COMMAND")
+        .help("Run COMMAND with the shell and use the first line it prints as password, e.g. \"pass show fritz\"");
 
     let device = Arg::new("device")
-        .required(true)
-        .help("The device identifier (ain) of the device to query / control.");
+        .long("device")
+        .short('d')
+        .value_name("DEVICE")
+        .value_parser(parser::parse_selector)
+        .add(ArgValueCandidates::new(completions::device_candidates))
+        .help("The device to query / control: ain, name, glob on the name (\"Laufband*\"), re:REGEX, product:GLOB or group:NAME");
+
+    // before --device existed the device was passed as positional argument
+    let device_positional = Arg::new("device-positional")
+        .value_name("DEVICE")
+        .value_parser(parser::parse_selector)
+        .add(ArgValueCandidates::new(completions::device_candidates))
+        .conflicts_with("device")
+        .hide(true);
+
+    let missed = Arg::new("missed")
+        .long("missed")
+        .value_name("POLICY")
+        .default_value("late")
+        .value_parser(PossibleValuesParser::new(schedule::timing::MISSED_POLICIES))
+        .help("What to do with actions missed by more than a minute, e.g. while the computer was suspended: skip them, run them if they are late by less than --grace, or catch up by running the last missed action of each device");
+
+    let grace = Arg::new("grace")
+        .long("grace")
+        .value_name("DURATION")
+        .default_value("10min")
+        .value_parser(parser::parse_duration)
+        .help("How late a missed action may still run with --missed late");
+
+    let retries = Arg::new("retries")
+        .long("retries")
+        .value_name("N")
+        .default_value("3")
+        .value_parser(value_parser!(u32))
+        .help("How often switching a device is retried when it is not present, fails or is not in the expected state afterwards");
+
+    let retry_delay = Arg::new("retry-delay")
+        .long("retry-delay")
+        .value_name("DURATION")
+        .default_value("10s")
+        .value_parser(parser::parse_duration)
+        .help(
+            "The delay before the first retry, doubled for every further retry (at most 5 minutes)",
+        );
+
+    let no_verify = Arg::new("no-verify")
+        .long("no-verify")
+        .action(ArgAction::SetTrue)
+        .help("Don't read the device states again after switching to check them");
+
+    let on_failure = Arg::new("on-failure")
+        .long("on-failure")
+        .value_name("COMMAND")
+        .help("Shell command to run when an action fails after all retries, gets FRITZCTRL_TIME, FRITZCTRL_DEVICE, FRITZCTRL_ACTION and FRITZCTRL_ERROR in the environment");
 
-    let mut app = Command::new(env!("CARGO_PKG_NAME"))
+    let enforce_current_state = Arg::new("enforce-current-state")
+        .long("enforce-current-state")
+        .action(ArgAction::SetTrue)
+        .help("On start and every --enforce-interval, switch devices that are not in the state of the last action the schedule had for them");
+
+    let enforce_interval = Arg::new("enforce-interval")
+        .long("enforce-interval")
+        .value_name("DURATION")
+        .default_value("10min")
+        .value_parser(parser::parse_duration)
+        .help("How often the state is enforced with --enforce-current-state");
+
+    let location = Arg::new("location")
+        .long("location")
+        .env("FRITZ_LOCATION")
+        .value_name("LOCATION")
+        .value_parser(parser::parse_location)
+        .add(ArgValueCandidates::new(gazetteer::candidates))
+        .help("\"LATITUDE,LONGITUDE[,ALTITUDE]\" or the name of a city, e.g. \"52.52,13.40\" or Berlin. Defaults to the location of the profile");
+    let latitude = Arg::new("latitude")
+        .long("latitude")
+        .requires("longitude")
+        .env("LATITUDE")
+        .value_parser(value_parser!(f64));
+    let longitude = Arg::new("longitude")
+        .long("longitude")
+        .requires("latitude")
+        .env("LONGITUDE")
+        .value_parser(value_parser!(f64));
+
+    Command::new(env!("CARGO_PKG_NAME"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .version(env!("CARGO_PKG_VERSION"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
         .arg(Arg::new("verbose").long("verbose").short('v').action(ArgAction::SetTrue))
+        .arg(Arg::new("output")
+             .long("output")
+             .short('o')
+             .global(true)
+             .default_value("table")
+             .value_parser(PossibleValuesParser::new(output::OUTPUT_FORMATS)
+                           .map(|format| format.parse::<output::OutputFormat>().unwrap()))
+             .help("Output format, json / ndjson / csv / yaml are meant for scripts"))
+        .arg(Arg::new("config")
+             .long("config")
+             .global(true)
+             .value_name("FILE")
+             .env("FRITZCTRL_CONFIG")
+             .value_parser(value_parser!(std::path::PathBuf))
+             .help("Configuration file, defaults to ~/.config/fritzctrl/config.toml"))
+        .arg(Arg::new("profile")
+             .long("profile")
+             .short('P')
+             .global(true)
+             .value_name("NAME")
+             .env("FRITZ_PROFILE")
+             .help("Profile of the configuration file to use"))
+        .arg(Arg::new("host")
+             .long("host")
+             .global(true)
+             .value_name("HOST")
+             .env("FRITZ_HOST")
+             .help("Host name, address or url of the fritz box, defaults to fritz.box"))
+        .arg(Arg::new("no-session-cache")
+             .long("no-session-cache")
+             .global(true)
+             .action(ArgAction::SetTrue)
+             .help("Log in again instead of reusing the session of an earlier call"))
         .subcommand(
             Command::new("list")
                 .about("List all connected devices or list sensor data of individual device (when used with --device ID)")
                 .arg(user.clone())
                 .arg(password.clone())
-                .arg(device.clone().required(false))
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(device.clone())
+                .arg(device_positional.clone())
                 .arg(Arg::new("limit")
                      .long("limit")
                      .short('l')
                      .value_parser(value_parser!(usize)))
                 .arg(Arg::new("kinds")
                      .long("kinds")
-                    .value_parser(parser::parse_kinds)
-                     .requires("device")
-                     .help("Comma separated list of the detail categories to show. Possible values: temperature, voltage, power, energy")),
+                     .value_parser(parser::parse_kinds)
+                     .help("Comma separated list of the detail categories to show. Possible values: temperature, voltage, power, energy"))
+                .arg(Arg::new("chart")
+                     .long("chart")
+                     .value_name("STYLE")
+                     .num_args(0..=1)
+                     .default_missing_value("bars")
+                     .value_parser(PossibleValuesParser::new(chart::CHART_STYLES)
+                                   .map(|style| style.parse::<chart::ChartStyle>().unwrap()))
+                     .help("Draw each series as chart instead of listing the readings, bars (Unicode) or line (ASCII)"))
+                .arg(Arg::new("summary")
+                     .long("summary")
+                     .action(ArgAction::SetTrue)
+                     .conflicts_with("chart")
+                     .help("Print min / max / mean / total of each series instead of the readings")),
         )
         .subcommand(
             Command::new("switch")
-                .about("Toggle device on / off")
+                .about("Toggle devices on / off. --device can be used multiple times, the devices are switched in parallel")
                 .arg(user.clone())
                 .arg(password.clone())
-                .arg(device.required(true))
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(device.clone()
+                     .action(ArgAction::Append)
+                     .required_unless_present("device-positional"))
+                .arg(device_positional.num_args(1..))
                 .arg(Arg::new("toggle").long("toggle").action(ArgAction::SetTrue))
                 .arg(Arg::new("on").long("on").action(ArgAction::SetTrue))
                 .arg(Arg::new("off").long("off").action(ArgAction::SetTrue)),
@@ -205,16 +608,9 @@ fn main() {
         .subcommand(
             Command::new("daylight")
                 .about("Prints the daylight times at a specific location.")
-                .arg(Arg::new("latitude")
-                     .long("latitude")
-                     .required(true)
-                     .env("LATITUDE")
-                     .value_parser(value_parser!(f64)))
-                .arg(Arg::new("longitude")
-                     .long("longitude")
-                     .required(true)
-                     .env("LONGITUDE")
-                     .value_parser(value_parser!(f64)))
+                .arg(location.clone())
+                .arg(latitude.clone())
+                .arg(longitude.clone())
                 .arg(Arg::new("date")
                      .long("date")
                      .value_parser(parser::valid_date))
@@ -230,14 +626,169 @@ fn main() {
                 .arg(Arg::new("shift-to")
                      .long("shift-to")
                      .value_parser(parser::parse_duration))
+                .arg(Arg::new("details")
+                     .long("details")
+                     .action(ArgAction::SetTrue)
+                     .help("Print a table with dawn and dusk (civil, nautical and astronomical twilight), solar noon and day length"))
         )
         .subcommand(
             Command::new("schedule")
-                .about("Reads newline separated commands from stdin and then runs until the last command is done.")
+                .about("Reads newline separated commands and recurring rules from stdin and then runs until the last command is done. With --check or --preview nothing is switched.")
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(location.clone().help("Location for sunrise / sunset entries: \"LATITUDE,LONGITUDE[,ALTITUDE]\" or the name of a city. Defaults to the location of the profile"))
+                .arg(latitude.clone())
+                .arg(longitude.clone())
+                .arg(missed.clone())
+                .arg(grace.clone())
+                .arg(retries.clone())
+                .arg(retry_delay.clone())
+                .arg(no_verify.clone())
+                .arg(on_failure.clone())
+                .arg(enforce_current_state.clone())
+                .arg(enforce_interval.clone())
+                .arg(Arg::new("schedule")
+                     .long("schedule")
+                     .short('s')
+                     .value_name("FILE")
+                     .value_parser(value_parser!(std::path::PathBuf))
+                     .help("Read the schedule from a file instead of stdin"))
+                .arg(Arg::new("check")
+                     .long("check")
+                     .action(ArgAction::SetTrue)
+                     .help("Report lines that cannot be parsed, unknown devices, entries in the past and devices switched on and off at the same time, then exit. Devices are checked against the fritz box or, if it cannot be reached, the device list of the last check"))
+                .arg(Arg::new("preview")
+                     .long("preview")
+                     .action(ArgAction::SetTrue)
+                     .help("Print the actions of the schedule until --until (a week by default), then exit"))
+                .arg(Arg::new("until")
+                     .long("until")
+                     .value_name("DATE")
+                     .value_parser(parser::valid_date)
+                     .help("Last day of --preview and of the conflict search of --check (a year by default)"))
+        )
+        .subcommand(
+            Command::new("daemon")
+                .about("Runs the schedule in a file until it is stopped with SIGTERM or SIGINT. The file is reloaded when it changes or on SIGHUP.")
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(Arg::new("schedule")
+                     .long("schedule")
+                     .short('s')
+                     .value_name("FILE")
+                     .required(true)
+                     .value_parser(value_parser!(std::path::PathBuf))
+                     .help("The schedule file, in the same format as the input of the schedule command"))
+                .arg(location.help("Location for sunrise / sunset entries: \"LATITUDE,LONGITUDE[,ALTITUDE]\" or the name of a city. Defaults to the location of the profile"))
+                .arg(latitude)
+                .arg(longitude)
+                .arg(missed)
+                .arg(grace)
+                .arg(retries)
+                .arg(retry_delay)
+                .arg(no_verify)
+                .arg(on_failure)
+                .arg(enforce_current_state)
+                .arg(enforce_interval)
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Renames a device or, with --csv, all devices listed in a CSV file of ain,name lines")
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(device.clone().required_unless_present_any(["csv", "legacy-name"]))
+                .arg(Arg::new("name")
+                     .required_unless_present("csv")
+                     .help("The new name of the device"))
+                // before --device existed the device was passed as first
+                // positional argument, the name is the second one then
+                .arg(Arg::new("legacy-name")
+                     .conflicts_with("device")
+                     .hide(true))
+                .arg(Arg::new("csv")
+                     .long("csv")
+                     .value_name("FILE")
+                     .conflicts_with_all(["device", "name"])
+                     .help("CSV file with ain,name lines (or any selector matching a single device instead of the ain), use - to read from stdin")),
+        )
+        .subcommand(
+            Command::new("raw")
+                .about("Sends a command to the AHA interface and prints the unprocessed response")
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(Arg::new("switchcmd")
+                     .required(true)
+                     .help("The switchcmd to send, e.g. getswitchname"))
+                .arg(Arg::new("device")
+                     .value_parser(parser::parse_ain)
+                     .help("The device identifier (ain) to send along"))
+                .arg(Arg::new("param")
+                     .long("param")
+                     .value_name("KEY=VALUE")
+                     .action(ArgAction::Append)
+                     .value_parser(parser::parse_key_value)
+                     .help("Additional request parameter, can be used multiple times")),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Shows the state and readings of devices in a continuously refreshed table")
+                .arg(user.clone())
+                .arg(password.clone())
+                .arg(password_file.clone())
+                .arg(password_command.clone())
+                .arg(device.action(ArgAction::Append))
+                .arg(Arg::new("interval")
+                     .long("interval")
+                     .short('i')
+                     .default_value("5s")
+                     .value_parser(parser::parse_duration)
+                     .help("Time between two refreshes"))
+                .arg(Arg::new("high-refresh")
+                     .long("high-refresh")
+                     .action(ArgAction::SetTrue)
+                     .help("Keep the fritz box updating the readings every ~10 seconds instead of every 2 minutes"))
+                .arg(Arg::new("once-on-change")
+                     .long("once-on-change")
+                     .action(ArgAction::SetTrue)
+                     .help("Print devices only when they change instead of refreshing the table, the default when the output is not a terminal")),
+        )
+        .subcommand(
+            Command::new("tui")
+                .about("Full-screen interface to browse and switch devices, groups and templates")
                 .arg(user)
                 .arg(password)
-        );
+                .arg(password_file)
+                .arg(password_command)
+                .arg(Arg::new("interval")
+                     .long("interval")
+                     .short('i')
+                     .default_value("10s")
+                     .value_parser(parser::parse_duration)
+                     .help("Time between two refreshes")),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Prints the shell completion script, device names are completed by asking the fritz box")
+                .arg(Arg::new("shell")
+                     .required(true)
+                     .value_parser(PossibleValuesParser::new(completions::shells()))
+                     .help("The shell to complete for")),
+        )
+}
 
+fn main() {
+    dotenv::dotenv().ok();
+    completions::complete_if_requested(cli);
+
+    let mut app = cli();
     let args = app.clone().get_matches();
 
     let log_level = if args.get_flag("verbose") {
@@ -260,6 +811,12 @@ fn main() {
             "list" => Commands::List,
             "switch" => Commands::Switch,
             "schedule" => Commands::Schedule,
+            "daemon" => Commands::Daemon,
+            "rename" => Commands::Rename,
+            "raw" => Commands::Raw,
+            "watch" => Commands::Watch,
+            "tui" => Commands::Tui,
+            "completions" => Commands::Completions,
             _ => {
                 app.print_help().unwrap();
                 exit(1);
@@ -289,17 +846,145 @@ fn main() {
 
         Commands::Schedule => {
             let args = args.subcommand_matches("schedule").unwrap();
-            let user = args.get_one::<String>("user").unwrap();
-            let password = args.get_one::<String>("password").unwrap();
-            let stdin = std::io::stdin();
-            let mut input = String::new();
-            stdin.lock().read_to_string(&mut input).unwrap();
-            if let Err(err) = schedule::Schedule::from_string(input)
-                .and_then(|mut schedule| schedule.start(user, password))
-            {
+            let input = match args.get_one::<std::path::PathBuf>("schedule") {
+                Some(path) => std::fs::read_to_string(path).unwrap_or_else(|err| {
+                    eprintln!("Error reading {}: {}", path.display(), err);
+                    exit(3);
+                }),
+                None => {
+                    let mut input = String::new();
+                    std::io::stdin().lock().read_to_string(&mut input).unwrap();
+                    input
+                }
+            };
+            if args.get_flag("check") || args.get_flag("preview") {
+                match config::Profile::from_args(args).and_then(|profile| {
+                    let location = location(args, &profile)?;
+                    schedule::check::check_and_preview(args, &profile, &input, location)
+                }) {
+                    Ok(true) => {}
+                    Ok(false) => exit(1),
+                    Err(err) => {
+                        eprintln!("Error checking schedule: {}", err);
+                        exit(3);
+                    }
+                }
+                return;
+            }
+            if let Err(err) = config::Profile::from_args(args).and_then(|profile| {
+                let mut client = profile.client(args)?;
+                let mut schedule = schedule::Schedule::from_string(input)
+                    .map_err(|err| anyhow::anyhow!("cannot parse the schedule: {}", err))?
+                    .with_location(location(args, &profile)?);
+                schedule.start(
+                    &mut client,
+                    &profile,
+                    &schedule::RunOptions::from_args(args),
+                )
+            }) {
                 eprintln!("Error running schedule: {}", err);
                 exit(3);
             };
         }
+
+        Commands::Daemon => {
+            if let Err(err) = daemon::daemon(args.subcommand_matches("daemon").unwrap()) {
+                eprintln!("Error running daemon: {}", err);
+                exit(3);
+            }
+        }
+
+        Commands::Rename => {
+            if let Err(err) = rename::rename(args.subcommand_matches("rename").unwrap()) {
+                println!("Error: {}", err);
+                exit(2);
+            }
+        }
+
+        Commands::Watch => {
+            if let Err(err) = watch::watch(args.subcommand_matches("watch").unwrap()) {
+                println!("Error: {}", err);
+                exit(2);
+            }
+        }
+
+        Commands::Tui => {
+            if let Err(err) = tui::tui(args.subcommand_matches("tui").unwrap()) {
+                println!("Error: {}", err);
+                exit(2);
+            }
+        }
+
+        Commands::Completions => {
+            let args = args.subcommand_matches("completions").unwrap();
+            if let Err(err) = completions::completions(args) {
+                println!("Error: {}", err);
+                exit(2);
+            }
+        }
+
+        Commands::Raw => {
+            if let Err(err) = raw::raw(args.subcommand_matches("raw").unwrap()) {
+                println!("Error: {}", err);
+                exit(2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_with_positional_device() {
+        let rename = |argv: &[&str]| {
+            let args = cli().try_get_matches_from(argv).unwrap();
+            let (_, args) = args.subcommand().unwrap();
+            let rename = rename::from_args(args).unwrap();
+            (rename.device.to_string(), rename.name)
+        };
+        let expected = ("11630 0123723".to_string(), "Laufband".to_string());
+        assert_eq!(
+            rename(&[
+                "fritzctrl",
+                "rename",
+                "--device",
+                "11630 0123723",
+                "Laufband"
+            ]),
+            expected
+        );
+        assert_eq!(
+            rename(&["fritzctrl", "rename", "11630 0123723", "Laufband"]),
+            expected
+        );
+        assert!(cli()
+            .try_get_matches_from(["fritzctrl", "rename", "-d", "a", "b", "c"])
+            .is_err());
+    }
+
+    #[test]
+    fn location_without_profile() {
+        let location = |argv: &[&str]| {
+            let args = cli().try_get_matches_from(argv).unwrap();
+            let (_, args) = args.subcommand().unwrap();
+            location_from_args(args).map(|location| (location.latitude, location.longitude))
+        };
+        assert_eq!(
+            location(&["fritzctrl", "daylight", "--location", "52.52,13.40"]),
+            Some((52.52, 13.40))
+        );
+        assert_eq!(
+            location(&[
+                "fritzctrl",
+                "daylight",
+                "--latitude",
+                "1",
+                "--longitude",
+                "2"
+            ]),
+            Some((1.0, 2.0))
+        );
     }
 }
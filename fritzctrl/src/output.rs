@@ -0,0 +1,368 @@
+//! Machine readable output of devices and their statistics.
+
+use chrono::prelude::*;
+use fritzapi::stats::DeviceStatValues;
+use fritzapi::{AVMDevice, DeviceStats, DeviceStatsKind};
+use serde::Serialize;
+use std::io::Write;
+
+/// Value of the global `--output` option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Yaml,
+}
+
+pub const OUTPUT_FORMATS: [&str; 5] = ["table", "json", "ndjson", "csv", "yaml"];
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
+            _ => Err(anyhow::anyhow!("unknown output format {:?}", input)),
+        }
+    }
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// Column names of [DeviceRecord].
+pub const DEVICE_RECORD_HEADER: [&str; 13] = [
+    "id",
+    "type",
+    "productname",
+    "name",
+    "state",
+    "present",
+    "txbusy",
+    "locked",
+    "mode",
+    "millivolts",
+    "milliwatts",
+    "energy_in_watt_h",
+    "celsius",
+];
+
+/// Flat representation of an [AVMDevice] for csv output. Readings are empty
+/// for devices that don't provide them.
+#[derive(Debug, Serialize)]
+pub struct DeviceRecord<'a> {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub device_type: &'static str,
+    pub productname: &'a str,
+    pub name: &'a str,
+    pub state: &'a str,
+    pub present: bool,
+    pub txbusy: bool,
+    pub locked: bool,
+    pub mode: Option<String>,
+    pub millivolts: Option<u32>,
+    pub milliwatts: Option<u32>,
+    pub energy_in_watt_h: Option<u32>,
+    pub celsius: Option<f32>,
+}
+
+impl<'a> From<&'a AVMDevice> for DeviceRecord<'a> {
+    fn from(device: &'a AVMDevice) -> Self {
+        let dect = match device {
+            AVMDevice::FritzDect2XX(dev) => Some(dev),
+            AVMDevice::Other(_) => None,
+        };
+        DeviceRecord {
+            id: device.id().to_string(),
+            device_type: match device {
+                AVMDevice::FritzDect2XX(_) => "FritzDect2XX",
+                AVMDevice::Other(_) => "Other",
+            },
+            productname: device.productname(),
+            name: device.name(),
+            state: device.state(),
+            present: device.is_present(),
+            txbusy: device.is_tx_busy(),
+            locked: device.is_locked(),
+            mode: device.switch_mode().map(|mode| mode.to_string()),
+            millivolts: dect.map(|dev| dev.millivolts),
+            milliwatts: dect.map(|dev| dev.milliwatts),
+            energy_in_watt_h: dect.map(|dev| dev.energy_in_watt_h),
+            celsius: dect.map(|dev| dev.celsius),
+        }
+    }
+}
+
+/// A single measurement with the time it was taken.
+#[derive(Debug, Serialize)]
+pub struct StatSample {
+    pub device: String,
+    pub kind: fritzapi::DeviceStatsKind,
+    pub unit: fritzapi::Unit,
+    pub time: DateTime<Local>,
+    pub value: f32,
+}
+
+impl StatSample {
+    /// Turns the series of `stat` into samples. Values are ordered newest
+    /// first, one `grid` interval apart, starting at `now`. At most `limit`
+    /// samples are taken from each series.
+    pub fn from_stats(
+        device: &AVMDevice,
+        stat: &DeviceStats,
+        limit: Option<usize>,
+        now: DateTime<Local>,
+    ) -> Vec<StatSample> {
+        stat.values
+            .iter()
+            .flat_map(|values| StatSample::from_series(device, stat.kind, values, limit, now))
+            .collect()
+    }
+
+    /// Like [StatSample::from_stats] for a single series.
+    pub fn from_series(
+        device: &AVMDevice,
+        kind: DeviceStatsKind,
+        values: &DeviceStatValues,
+        limit: Option<usize>,
+        now: DateTime<Local>,
+    ) -> Vec<StatSample> {
+        values
+            .values
+            .iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .enumerate()
+            .map(|(i, value)| StatSample {
+                device: device.id().to_string(),
+                kind,
+                unit: kind.unit(),
+                time: now - chrono::Duration::seconds((i * values.grid) as i64),
+                value: *value,
+            })
+            .collect()
+    }
+}
+
+/// Min / max / mean of a series of [StatSample]s. `total` is the sum for
+/// energy and the energy consumed in the period for power, both in Wh.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SeriesSummary {
+    pub device: String,
+    pub kind: DeviceStatsKind,
+    pub unit: fritzapi::Unit,
+    /// Seconds between two samples.
+    pub grid: usize,
+    pub count: usize,
+    pub from: DateTime<Local>,
+    pub to: DateTime<Local>,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub total_watt_h: Option<f32>,
+}
+
+impl SeriesSummary {
+    /// `None` for an empty series.
+    pub fn from_samples(samples: &[StatSample], grid: usize) -> Option<Self> {
+        let first = samples.first()?;
+        let values = samples.iter().map(|sample| sample.value);
+        let sum: f32 = values.clone().sum();
+        let total_watt_h = match first.kind {
+            DeviceStatsKind::Energy => Some(sum),
+            DeviceStatsKind::Power => Some(sum * grid as f32 / 3600.0),
+            DeviceStatsKind::Temperature | DeviceStatsKind::Voltage => None,
+        };
+        Some(SeriesSummary {
+            device: first.device.clone(),
+            kind: first.kind,
+            unit: first.unit,
+            grid,
+            count: samples.len(),
+            from: samples.iter().map(|sample| sample.time).min()?,
+            to: samples.iter().map(|sample| sample.time).max()?,
+            min: values.clone().fold(f32::INFINITY, f32::min),
+            max: values.fold(f32::NEG_INFINITY, f32::max),
+            mean: sum / samples.len() as f32,
+            total_watt_h,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DeviceDetail<'a> {
+    device: &'a AVMDevice,
+    stats: &'a [StatSample],
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// Prints `devices` in `format`. [OutputFormat::Table] is handled by the
+/// callers.
+pub fn print_devices(format: OutputFormat, devices: &[AVMDevice]) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+    match format {
+        OutputFormat::Table => {}
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, devices)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => print_ndjson(&mut out, devices)?,
+        OutputFormat::Csv => print_csv(&mut out, devices.iter().map(DeviceRecord::from))?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, devices)?,
+    }
+    Ok(())
+}
+
+/// Prints the `stats` of `device`. For json and yaml the device is included,
+/// ndjson and csv contain one sample per line.
+pub fn print_device_stats(
+    format: OutputFormat,
+    device: &AVMDevice,
+    stats: &[StatSample],
+) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+    let detail = DeviceDetail { device, stats };
+    match format {
+        OutputFormat::Table => {}
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &detail)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => print_ndjson(&mut out, stats)?,
+        OutputFormat::Csv => print_csv(&mut out, stats)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, &detail)?,
+    }
+    Ok(())
+}
+
+/// Prints the `summaries` of device statistics in `format`.
+/// [OutputFormat::Table] is handled by the callers.
+pub fn print_summaries(format: OutputFormat, summaries: &[SeriesSummary]) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+    match format {
+        OutputFormat::Table => {}
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, summaries)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => print_ndjson(&mut out, summaries)?,
+        OutputFormat::Csv => print_csv(&mut out, summaries)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, summaries)?,
+    }
+    Ok(())
+}
+
+pub fn print_ndjson<T: Serialize>(out: &mut impl Write, items: &[T]) -> anyhow::Result<()> {
+    for item in items {
+        serde_json::to_writer(&mut *out, item)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+pub fn print_csv<T: Serialize>(
+    out: &mut impl Write,
+    records: impl IntoIterator<Item = T>,
+) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fritzapi::{FritzDect2XX, SwitchMode};
+
+    fn device() -> AVMDevice {
+        AVMDevice::FritzDect2XX(FritzDect2XX {
+            identifier: "11630 0123723".parse().unwrap(),
+            id: "20".to_string(),
+            name: "Laufband".to_string(),
+            productname: "FRITZ!DECT 200".to_string(),
+            present: true,
+            txbusy: false,
+            on: true,
+            lock: false,
+            devicelock: false,
+            mode: SwitchMode::Manual,
+            millivolts: 234877,
+            milliwatts: 4570,
+            energy_in_watt_h: 43714,
+            celsius: 19.5,
+        })
+    }
+
+    #[test]
+    fn stat_samples() {
+        let device = device();
+        let stats = DeviceStats {
+            kind: DeviceStatsKind::Power,
+            values: vec![fritzapi::stats::DeviceStatValues {
+                values: vec![4.5, 4.0, 3.5],
+                grid: 900,
+            }],
+        };
+        let now = Local.with_ymd_and_hms(2025, 12, 24, 18, 0, 0).unwrap();
+        let samples = StatSample::from_stats(&device, &stats, Some(2), now);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].time, now - chrono::Duration::minutes(15));
+
+        let json = serde_json::to_value(&samples[0]).unwrap();
+        assert_eq!(json["device"], "11630 0123723");
+        assert_eq!(json["kind"], "power");
+        assert_eq!(json["unit"], "W");
+        assert_eq!(json["value"], 4.5);
+    }
+
+    #[test]
+    fn series_summary() {
+        let device = device();
+        let values = DeviceStatValues {
+            values: vec![40.0, 20.0, 0.0, 20.0],
+            grid: 900,
+        };
+        let now = Local.with_ymd_and_hms(2025, 12, 24, 18, 0, 0).unwrap();
+        let samples = StatSample::from_series(&device, DeviceStatsKind::Power, &values, None, now);
+        let summary = SeriesSummary::from_samples(&samples, values.grid).unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.from, now - chrono::Duration::minutes(45));
+        assert_eq!(summary.to, now);
+        assert_eq!((summary.min, summary.max, summary.mean), (0.0, 40.0, 20.0));
+        // 80 W for 15 minutes
+        assert_eq!(summary.total_watt_h, Some(20.0));
+
+        let samples =
+            StatSample::from_series(&device, DeviceStatsKind::Temperature, &values, None, now);
+        let summary = SeriesSummary::from_samples(&samples, values.grid).unwrap();
+        assert_eq!(summary.total_watt_h, None);
+        assert_eq!(SeriesSummary::from_samples(&[], 900), None);
+    }
+
+    #[test]
+    fn device_csv() {
+        let device = device();
+        let mut out = Vec::new();
+        print_csv(&mut out, [DeviceRecord::from(&device)]).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "id,type,productname,name,state,present,txbusy,locked,mode,millivolts,milliwatts,energy_in_watt_h,celsius"
+        );
+        assert_eq!(DEVICE_RECORD_HEADER.join(","), csv.lines().next().unwrap());
+        assert_eq!(
+            lines.next().unwrap(),
+            "11630 0123723,FritzDect2XX,FRITZ!DECT 200,Laufband,on,true,false,false,manual,234877,4570,43714,19.5"
+        );
+    }
+}
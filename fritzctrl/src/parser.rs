@@ -16,3 +16,47 @@ pub(crate) fn parse_duration(arg: &str) -> Result<chrono::Duration, String> {
 pub(crate) fn parse_kinds(arg: &str) -> fritzapi::Result<Vec<fritzapi::DeviceStatsKind>> {
     arg.split(',').map(|ea| ea.parse()).collect()
 }
+
+pub(crate) fn parse_key_value(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, val)) if !key.is_empty() => Ok((key.to_string(), val.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", arg)),
+    }
+}
+
+pub(crate) fn parse_ain(arg: &str) -> Result<fritzapi::Ain, String> {
+    arg.parse()
+        .map_err(|err: fritzapi::FritzError| err.to_string())
+}
+
+pub(crate) fn parse_selector(arg: &str) -> Result<crate::selector::DeviceSelector, String> {
+    arg.parse().map_err(|err: anyhow::Error| err.to_string())
+}
+
+pub(crate) fn parse_location(arg: &str) -> Result<crate::daylight::Location, String> {
+    arg.parse().map_err(|err: anyhow::Error| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_key_value;
+
+    #[test]
+    fn key_value() {
+        assert_eq!(
+            parse_key_value("param=220"),
+            Ok(("param".to_string(), "220".to_string()))
+        );
+        assert_eq!(
+            parse_key_value("name="),
+            Ok(("name".to_string(), String::new()))
+        );
+        assert_eq!(
+            parse_key_value("name=a=b"),
+            Ok(("name".to_string(), "a=b".to_string()))
+        );
+        assert!(parse_key_value("param").is_err());
+        assert!(parse_key_value("=220").is_err());
+        assert!(parse_key_value("").is_err());
+    }
+}
@@ -0,0 +1,22 @@
+use crate::config::Profile;
+use clap::ArgMatches;
+
+/// Sends an arbitrary AHA `switchcmd` and prints the unprocessed response.
+pub fn raw(args: &ArgMatches) -> anyhow::Result<()> {
+    let switchcmd = args.get_one::<String>("switchcmd").unwrap();
+    let ain = args.get_one::<fritzapi::Ain>("device");
+    let params: Vec<(&str, &str)> = args
+        .get_many::<(String, String)>("param")
+        .unwrap_or_default()
+        .map(|(key, val)| (key.as_str(), val.as_str()))
+        .collect();
+
+    let mut client = Profile::from_args(args)?.client(args)?;
+    let body = client.raw_command(switchcmd, ain, &params)?;
+    print!("{}", body);
+    if !body.ends_with('\n') {
+        println!();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::config::Profile;
+use crate::selector::{DeviceSelector, Inventory};
+use clap::ArgMatches;
+use fritzapi::FritzClient;
+use std::io::Read;
+
+/// A single device → name assignment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub device: DeviceSelector,
+    pub name: String,
+}
+
+pub fn rename(args: &ArgMatches) -> anyhow::Result<()> {
+    let profile = Profile::from_args(args)?;
+
+    let renames = match args.get_one::<String>("csv") {
+        Some(file) => {
+            let input = if file == "-" {
+                let mut input = String::new();
+                std::io::stdin().lock().read_to_string(&mut input)?;
+                input
+            } else {
+                std::fs::read_to_string(file)?
+            };
+            parse_csv(&input)?
+        }
+        None => vec![from_args(args)?],
+    };
+
+    let renames: Vec<_> = renames
+        .into_iter()
+        .map(|rename| Rename {
+            device: profile.expand_alias(rename.device),
+            ..rename
+        })
+        .collect();
+    let mut client = profile.client(args)?;
+    run(&mut client, &renames)
+}
+
+/// The rename given with `--device DEVICE NAME`, or `DEVICE NAME` as before
+/// `--device` existed.
+pub fn from_args(args: &ArgMatches) -> anyhow::Result<Rename> {
+    let name = args.get_one::<String>("name").unwrap().clone();
+    Ok(match args.get_one::<String>("legacy-name") {
+        Some(legacy) => Rename {
+            device: name.parse()?,
+            name: legacy.clone(),
+        },
+        None => Rename {
+            device: args.get_one::<DeviceSelector>("device").unwrap().clone(),
+            name,
+        },
+    })
+}
+
+/// Parses lines of `ain,name`. Instead of the ain any device selector that
+/// matches a single device can be used. Empty lines and lines starting with `#` are
+/// ignored, an optional `ain,name` header is skipped.
+pub fn parse_csv(input: &str) -> anyhow::Result<Vec<Rename>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+
+    let mut renames = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+        match (record.get(0), record.get(1), record.len()) {
+            (Some(ain), Some(_), 2) if i == 0 && ain.eq_ignore_ascii_case("ain") => continue,
+            (Some(ain), Some(name), 2) if !ain.is_empty() => {
+                let device = ain
+                    .parse()
+                    .map_err(|err| anyhow::anyhow!("line {}: {}", line, err))?;
+                fritzapi::devices::validate_name(name)
+                    .map_err(|err| anyhow::anyhow!("line {}: {}", line, err))?;
+                renames.push(Rename {
+                    device,
+                    name: name.to_string(),
+                });
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "line {}: expected two columns \"ain,name\"",
+                    line
+                ))
+            }
+        }
+    }
+    Ok(renames)
+}
+
+#[tracing::instrument(level = "trace", skip(client))]
+pub fn run(client: &mut FritzClient, renames: &[Rename]) -> anyhow::Result<()> {
+    let selectors: Vec<_> = renames.iter().map(|rename| rename.device.clone()).collect();
+    let inventory = Inventory::fetch(client, &selectors)?;
+
+    // make sure all devices exist before renaming anything
+    let renames = renames
+        .iter()
+        .map(|rename| Ok((inventory.resolve_one(&rename.device)?, rename)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    for (device, rename) in renames {
+        let name = client.set_name(device.id(), &rename.name)?;
+        println!("{}: {:?} -> {:?}", device.id(), device.name(), name);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_renames() {
+        let input = r#"ain,name
+# plugs in the living room
+11630 0123723,Laufband
+"11630 0128064","Schreibtisch, links"
+
+116300266726,Router
+"#;
+        let renames = parse_csv(input).unwrap();
+        assert_eq!(
+            renames,
+            vec![
+                Rename {
+                    device: "11630 0123723".parse().unwrap(),
+                    name: "Laufband".to_string()
+                },
+                Rename {
+                    device: "11630 0128064".parse().unwrap(),
+                    name: "Schreibtisch, links".to_string()
+                },
+                Rename {
+                    device: "116300266726".parse().unwrap(),
+                    name: "Router".to_string()
+                },
+            ]
+        );
+
+        assert!(parse_csv("11630 0123723").is_err());
+        assert!(parse_csv("11630 0123723,a,b").is_err());
+        assert!(parse_csv("Lampe [,a").is_err());
+        assert!(parse_csv(&format!("11630 0123723,{}", "x".repeat(41))).is_err());
+    }
+}
@@ -1,26 +1,29 @@
+use crate::switch::{SwitchAction, ACTION_PATTERN};
 use chrono::prelude::*;
 use lazy_static::lazy_static;
 use regex::Regex;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Action {
-    TurnOn { time: DateTime<Local>, id: String },
-    TurnOff { time: DateTime<Local>, id: String },
+/// A single schedule line such as `2025-10-11 09:00:00 Kaffee on` or
+/// `2025-10-11 18:00:00 Heizung temperature 21.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub time: DateTime<Local>,
+    pub id: String,
+    pub command: SwitchAction,
 }
 
 impl Action {
     pub fn time(&self) -> DateTime<Local> {
-        match self {
-            Self::TurnOn { time, .. } => *time,
-            Self::TurnOff { time, .. } => *time,
-        }
+        self.time
     }
 
     pub fn device_id(&self) -> &str {
-        match self {
-            Self::TurnOn { id, .. } => id,
-            Self::TurnOff { id, .. } => id,
-        }
+        &self.id
+    }
+
+    /// The same action for another device.
+    pub fn with_device_id(&self, id: String) -> Self {
+        Self { id, ..self.clone() }
     }
 }
 
@@ -29,36 +32,32 @@ impl std::str::FromStr for Action {
 
     fn from_str(line: &str) -> Result<Self, Self::Err> {
         lazy_static! {
-            static ref RE: Regex = regex::RegexBuilder::new(
-                r"([0-9]{4}-[0-9]{2}-[0-9]{2} [0-9]{2}:[0-9]{2}:[0-9]{2}) (.+) (on|off)"
-            )
+            static ref RE: Regex = regex::RegexBuilder::new(&format!(
+                r"^\s*([0-9]{{4}}-[0-9]{{2}}-[0-9]{{2}} [0-9]{{2}}:[0-9]{{2}}:[0-9]{{2}}) (.+?) ({})\s*$",
+                ACTION_PATTERN
+            ))
             .case_insensitive(true)
             .build()
             .unwrap();
         }
 
-        let err = anyhow::anyhow!("does not match schedule action format");
-        match RE.captures(line) {
-            None => Err(err),
-            Some(captures) => {
-                let ts = captures.get(1).unwrap().as_str();
-                let id = captures
-                    .get(2)
-                    .unwrap()
-                    .as_str()
-                    .trim_matches('"')
-                    .to_string();
-                let action = captures.get(3).unwrap().as_str().to_lowercase();
+        let err = || anyhow::anyhow!("does not match schedule action format");
+        let captures = RE.captures(line).ok_or_else(err)?;
+        let ts = captures.get(1).unwrap().as_str();
+        let id = captures
+            .get(2)
+            .unwrap()
+            .as_str()
+            .trim_matches('"')
+            .to_string();
+        let command = captures.get(3).unwrap().as_str().parse()?;
 
-                match NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S")
-                    .ok()
-                    .and_then(|time| Local.from_local_datetime(&time).earliest())
-                {
-                    Some(time) if action == "on" => Ok(Action::TurnOn { time, id }),
-                    Some(time) if action == "off" => Ok(Action::TurnOff { time, id }),
-                    _ => Err(err),
-                }
-            }
+        match NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .and_then(super::timing::local)
+        {
+            Some(time) => Ok(Action { time, id, command }),
+            None => Err(err()),
         }
     }
 }
@@ -67,35 +66,57 @@ impl std::str::FromStr for Action {
 mod tests {
     use super::*;
     use chrono::TimeZone;
+    use fritzapi::{BlindTarget, ThermostatTarget};
 
     #[test]
     fn parse_actions() {
         let time =
             NaiveDateTime::parse_from_str("2021-01-31 01:02:03", "%Y-%m-%d %H:%M:%S").unwrap();
         let time = chrono::Local.from_local_datetime(&time).unwrap();
+        let action = |id: &str, command| Action {
+            time,
+            id: id.to_string(),
+            command,
+        };
         assert_eq!("hello".parse::<Action>().ok(), None);
         assert_eq!(
             "2021-01-31 01:02:03 aaabbb on".parse::<Action>().unwrap(),
-            Action::TurnOn {
-                time,
-                id: "aaabbb".to_string()
-            }
+            action("aaabbb", SwitchAction::On)
         );
         assert_eq!(
             "2021-01-31 01:02:03 \"123 456\" on"
                 .parse::<Action>()
                 .unwrap(),
-            Action::TurnOn {
-                time,
-                id: "123 456".to_string()
-            }
+            action("123 456", SwitchAction::On)
         );
         assert_eq!(
             "2021-01-31 01:02:03 123 456 off".parse::<Action>().unwrap(),
-            Action::TurnOff {
-                time,
-                id: "123 456".to_string()
-            }
+            action("123 456", SwitchAction::Off)
+        );
+        assert_eq!(
+            "2021-01-31 01:02:03 Heizung temperature off"
+                .parse::<Action>()
+                .unwrap(),
+            action("Heizung", SwitchAction::Temperature(ThermostatTarget::Off))
+        );
+        assert_eq!(
+            "2021-01-31 01:02:03 \"Rollladen Küche\" Blind Close"
+                .parse::<Action>()
+                .unwrap(),
+            action("Rollladen Küche", SwitchAction::Blind(BlindTarget::Close))
+        );
+        assert_eq!(
+            "2021-01-31 01:02:03 Kaffee toggle"
+                .parse::<Action>()
+                .unwrap()
+                .command,
+            SwitchAction::Toggle
         );
+        assert!("2021-01-31 01:02:03 Heizung temperature 35"
+            .parse::<Action>()
+            .is_err());
+        assert!("2021-01-31 01:02:03 Lampe brightness 120%"
+            .parse::<Action>()
+            .is_err());
     }
 }
@@ -0,0 +1,683 @@
+//! `fritzctrl schedule --check` and `--preview`: reports problems of a
+//! schedule and prints the actions it would run, without switching anything.
+
+use super::schedule::{parse_lines, Entry};
+use super::timing;
+use crate::config::Profile;
+use crate::daylight::Location;
+use crate::output::{self, OutputFormat};
+use crate::selector::{DeviceSelector, Inventory};
+use crate::switch::{self, SwitchAction};
+use chrono::prelude::*;
+use clap::ArgMatches;
+use fritzapi::devices::{Device, GroupInfo};
+use fritzapi::{AVMDevice, Ain, DeviceGroup, Template, Trigger};
+use prettytable::{format, Cell, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// How far ahead conflicts are searched for without `--until`.
+const CHECK_DAYS: i64 = 366;
+
+/// How far ahead `--preview` shows the actions without `--until`.
+const PREVIEW_DAYS: i64 = 7;
+
+/// Occurrences of a single line that are compared for conflicts, a cron
+/// expression running every minute would have half a million in a year.
+const MAX_OCCURRENCES: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// The devices, groups, templates and triggers of the fritz box the lines of
+/// a schedule are checked against.
+#[derive(Debug, Default)]
+pub struct Known {
+    pub inventory: Inventory,
+    pub templates: Vec<Template>,
+    pub triggers: Vec<Trigger>,
+}
+
+/// A problem found in a line of the schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(line: usize, message: String) -> Self {
+        Self {
+            line,
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    fn warning(line: usize, message: String) -> Self {
+        Self {
+            line,
+            severity: Severity::Warning,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "line {}: {}: {}", self.line, severity, self.message)
+    }
+}
+
+/// Runs `--check` and / or `--preview` for the schedule `input`. Returns
+/// `false` if the check found errors.
+pub fn check_and_preview(
+    args: &ArgMatches,
+    profile: &Profile,
+    input: &str,
+    location: Option<Location>,
+) -> anyhow::Result<bool> {
+    let now = Local::now();
+    let until = args
+        .get_one::<NaiveDate>("until")
+        .and_then(|until| until.succ_opt())
+        .and_then(|day| timing::local(day.and_time(NaiveTime::MIN)));
+    let mut ok = true;
+
+    if args.get_flag("check") {
+        let known = match known(args, profile) {
+            Ok(known) => Some(known),
+            Err(err) => {
+                eprintln!("cannot check the devices: {}", err);
+                None
+            }
+        };
+        let until = until.unwrap_or(now + chrono::Duration::days(CHECK_DAYS));
+        let findings = check(
+            input,
+            known.as_ref(),
+            profile,
+            location.as_ref(),
+            now,
+            until,
+        );
+        for finding in &findings {
+            println!("{}", finding);
+        }
+        let errors = findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .count();
+        match (errors, findings.len() - errors) {
+            (0, 0) => println!("no problems found"),
+            (errors, warnings) => println!("{} errors, {} warnings", errors, warnings),
+        }
+        ok = errors == 0;
+    }
+
+    if args.get_flag("preview") {
+        let until = until.unwrap_or(now + chrono::Duration::days(PREVIEW_DAYS));
+        let format = *args.get_one::<OutputFormat>("output").unwrap();
+        print_preview(format, &preview(input, location.as_ref(), now, until))?;
+    }
+
+    Ok(ok)
+}
+
+/// Checks the lines of the schedule: parse errors, devices, templates and
+/// triggers that are not `known` (if they are), entries in the past and
+/// devices that get contradicting actions at the same time until `until`.
+pub fn check(
+    input: &str,
+    known: Option<&Known>,
+    profile: &Profile,
+    location: Option<&Location>,
+    now: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<Finding> {
+    let lines: Vec<_> = input.lines().collect();
+    let mut findings = Vec::new();
+    // the devices switched by each line, names and ains if they can be resolved
+    let mut targets: Vec<(usize, Entry, Vec<String>)> = Vec::new();
+
+    for (line, entry) in parse_lines(input) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let text = lines[line - 1].trim();
+                findings.push(Finding::error(
+                    line,
+                    format!("cannot parse {:?}: {}", text, err),
+                ));
+                continue;
+            }
+        };
+
+        match &entry {
+            Entry::Action(action) if action.time() <= now => findings.push(Finding::warning(
+                line,
+                format!(
+                    "{} is in the past",
+                    action.time().format("%Y-%m-%d %H:%M:%S")
+                ),
+            )),
+            Entry::Action(_) => {}
+            Entry::Rule(rule) if rule.needs_location() && location.is_none() => {
+                findings.push(Finding::error(
+                    line,
+                    "sunrise / sunset entries need a location, use --location or set the location of the profile".to_string(),
+                ))
+            }
+            Entry::Rule(rule) => match rule.to {
+                Some(to) if to < now.date_naive() => findings.push(Finding::warning(
+                    line,
+                    format!("the date range ended on {}", to),
+                )),
+                _ if rule.next_after(now, location).is_none() => {
+                    findings.push(Finding::warning(line, "never runs".to_string()))
+                }
+                _ => {}
+            },
+        }
+
+        let devices = match known {
+            None => vec![entry.device_id().to_string()],
+            Some(known) => match resolve(known, profile, &entry) {
+                Ok(devices) => devices,
+                Err(err) => {
+                    findings.push(Finding::error(line, err.to_string()));
+                    continue;
+                }
+            },
+        };
+        targets.push((line, entry, devices));
+    }
+
+    findings.extend(conflicts(&targets, location, now, until));
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
+
+fn resolve(known: &Known, profile: &Profile, entry: &Entry) -> anyhow::Result<Vec<String>> {
+    let selector = profile.expand_alias(entry.device_id().parse::<DeviceSelector>()?);
+    let selectors = [selector];
+    let named = match entry.command() {
+        SwitchAction::ApplyTemplate => {
+            switch::resolve_named(&known.templates, &selectors, "template", |template| {
+                (&template.identifier, &template.name)
+            })?
+            .into_iter()
+            .map(|template| (template.name.as_str(), &template.identifier))
+            .collect()
+        }
+        SwitchAction::Trigger(_) => {
+            switch::resolve_named(&known.triggers, &selectors, "trigger", |trigger| {
+                (&trigger.identifier, &trigger.name)
+            })?
+            .into_iter()
+            .map(|trigger| (trigger.name.as_str(), &trigger.identifier))
+            .collect()
+        }
+        _ => known
+            .inventory
+            .resolve(&selectors[0])?
+            .into_iter()
+            .map(|device| (device.name(), device.id()))
+            .collect::<Vec<_>>(),
+    };
+    Ok(named
+        .into_iter()
+        .map(|(name, id)| format!("{} ({})", name, id))
+        .collect())
+}
+
+/// Lines that do contradicting things to the same device at the same time,
+/// e.g. switch it on and off or set two temperatures.
+fn conflicts(
+    targets: &[(usize, Entry, Vec<String>)],
+    location: Option<&Location>,
+    now: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<Finding> {
+    let mut switched: HashMap<(DateTime<Local>, &str, &str), (usize, SwitchAction)> =
+        HashMap::new();
+    // the first conflict of each pair of lines
+    let mut conflicts: HashMap<(usize, usize), String> = HashMap::new();
+
+    for (line, entry, devices) in targets {
+        for action in entry
+            .actions_between(now, until, location)
+            .take(MAX_OCCURRENCES)
+        {
+            for device in devices {
+                let key = (action.time(), device.as_str(), action.command.kind());
+                match switched.get(&key) {
+                    Some((other, other_command)) if *other_command != action.command => {
+                        conflicts.entry((*other, *line)).or_insert_with(|| {
+                            format!(
+                                "{}: {:?} at the same time as {:?} in line {}, first at {}",
+                                device,
+                                action.command.to_string(),
+                                other_command.to_string(),
+                                other,
+                                action.time().format("%Y-%m-%d %H:%M:%S")
+                            )
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        switched.insert(key, (*line, action.command));
+                    }
+                }
+            }
+        }
+    }
+
+    conflicts
+        .into_iter()
+        .map(|((_, line), message)| Finding::error(line, message))
+        .collect()
+}
+
+/// An action of the schedule shown by `--preview`.
+#[derive(Debug, Serialize)]
+pub struct PreviewRecord {
+    pub time: DateTime<Local>,
+    pub device: String,
+    pub action: String,
+    pub line: usize,
+}
+
+/// The actions of the schedule after `now` until `until`, in order. Lines
+/// that cannot be parsed are left out.
+pub fn preview(
+    input: &str,
+    location: Option<&Location>,
+    now: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Vec<PreviewRecord> {
+    let entries: Vec<_> = parse_lines(input)
+        .into_iter()
+        .filter_map(|(line, entry)| Some((line, entry.ok()?)))
+        .collect();
+    let mut records: Vec<_> = entries
+        .iter()
+        .flat_map(|(line, entry)| {
+            entry
+                .actions_between(now, until, location)
+                .map(move |action| PreviewRecord {
+                    time: action.time(),
+                    device: action.device_id().to_string(),
+                    action: action.command.to_string(),
+                    line: *line,
+                })
+        })
+        .collect();
+    records.sort_by_key(|record| (record.time, record.line));
+    records
+}
+
+fn print_preview(format: OutputFormat, records: &[PreviewRecord]) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+    match format {
+        OutputFormat::Table => {
+            let mut table = crate::list::create_table();
+            table.set_titles(Row::new(vec![
+                Cell::new_align("time", format::Alignment::CENTER),
+                Cell::new_align("device", format::Alignment::CENTER),
+                Cell::new_align("action", format::Alignment::CENTER),
+                Cell::new_align("line", format::Alignment::CENTER),
+            ]));
+            for record in records {
+                table.add_row(Row::new(vec![
+                    Cell::new(&record.time.format("%a %Y-%m-%d %H:%M:%S").to_string()),
+                    Cell::new(&record.device),
+                    Cell::new(&record.action),
+                    Cell::new_align(&record.line.to_string(), format::Alignment::RIGHT),
+                ]));
+            }
+            table.print(&mut out)?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, records)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => output::print_ndjson(&mut out, records)?,
+        OutputFormat::Csv => output::print_csv(&mut out, records)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, records)?,
+    }
+    Ok(())
+}
+
+/// The device list of a fritz box, stored whenever `--check` could list the
+/// devices so that schedules can be checked while the box is not reachable.
+/// Only what is needed to resolve device selectors is kept.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDevices {
+    host: String,
+    fetched: DateTime<Local>,
+    devices: Vec<CachedDevice>,
+    groups: Vec<CachedGroup>,
+    #[serde(default)]
+    templates: Vec<CachedNamed>,
+    #[serde(default)]
+    triggers: Vec<CachedNamed>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDevice {
+    identifier: Ain,
+    id: String,
+    name: String,
+    productname: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedGroup {
+    identifier: Ain,
+    id: String,
+    name: String,
+    members: Vec<String>,
+}
+
+/// A template or trigger.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedNamed {
+    identifier: Ain,
+    name: String,
+}
+
+impl CachedDevices {
+    fn new(host: &str, known: &Known) -> Self {
+        Self {
+            host: host.to_string(),
+            fetched: Local::now(),
+            devices: known
+                .inventory
+                .devices
+                .iter()
+                .map(|device| CachedDevice {
+                    identifier: device.id().clone(),
+                    id: device.internal_id().to_string(),
+                    name: device.name().to_string(),
+                    productname: device.productname().to_string(),
+                })
+                .collect(),
+            groups: known
+                .inventory
+                .groups
+                .iter()
+                .map(|group| CachedGroup {
+                    identifier: group.identifier.clone(),
+                    id: group.id.clone(),
+                    name: group.name.clone(),
+                    members: group.member_ids().into_iter().map(String::from).collect(),
+                })
+                .collect(),
+            templates: known
+                .templates
+                .iter()
+                .map(|template| CachedNamed {
+                    identifier: template.identifier.clone(),
+                    name: template.name.clone(),
+                })
+                .collect(),
+            triggers: known
+                .triggers
+                .iter()
+                .map(|trigger| CachedNamed {
+                    identifier: trigger.identifier.clone(),
+                    name: trigger.name.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The cached devices with unknown readings, they are assumed to be
+    /// present.
+    fn into_known(self) -> Known {
+        let devices = self
+            .devices
+            .into_iter()
+            .map(|device| {
+                AVMDevice::Other(Device {
+                    identifier: device.identifier,
+                    id: device.id,
+                    functionbitmask: String::new(),
+                    fwversion: String::new(),
+                    manufacturer: String::new(),
+                    productname: device.productname,
+                    present: true,
+                    txbusy: false,
+                    name: device.name,
+                    battery: None,
+                    batterylow: None,
+                    switch: None,
+                    simpleonoff: None,
+                    powermeter: None,
+                    temperature: None,
+                    hkr: None,
+                })
+            })
+            .collect();
+        let groups = self
+            .groups
+            .into_iter()
+            .map(|group| DeviceGroup {
+                synchronized: true,
+                identifier: group.identifier,
+                id: group.id,
+                functionbitmask: String::new(),
+                fwversion: String::new(),
+                manufacturer: String::new(),
+                present: true,
+                txbusy: false,
+                name: group.name,
+                switch: None,
+                simpleonoff: None,
+                powermeter: None,
+                groupinfo: Some(GroupInfo {
+                    masterdeviceid: String::new(),
+                    members: group.members.join(","),
+                }),
+            })
+            .collect();
+        Known {
+            inventory: Inventory { devices, groups },
+            templates: self
+                .templates
+                .into_iter()
+                .map(|template| Template {
+                    identifier: template.identifier,
+                    id: String::new(),
+                    functionbitmask: String::new(),
+                    name: template.name,
+                })
+                .collect(),
+            triggers: self
+                .triggers
+                .into_iter()
+                .map(|trigger| Trigger {
+                    identifier: trigger.identifier,
+                    active: true,
+                    name: trigger.name,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The devices, groups, templates and triggers of the fritz box, or the
+/// cached ones if the box cannot be reached. Older boxes don't know templates
+/// or triggers, then there are none.
+fn known(args: &ArgMatches, profile: &Profile) -> anyhow::Result<Known> {
+    let host = profile.host(args);
+    let fetched = profile.client(args).and_then(|mut client| {
+        let (devices, groups) = client.list_devices_and_groups()?;
+        let known = Known {
+            inventory: Inventory { devices, groups },
+            templates: client.list_templates().unwrap_or_default(),
+            triggers: client.list_triggers().unwrap_or_default(),
+        };
+        client.persist();
+        Ok(known)
+    });
+
+    match fetched {
+        Ok(known) => {
+            if let Err(err) = store_devices(&host, &known) {
+                debug!("cannot store the device list: {}", err);
+            }
+            Ok(known)
+        }
+        Err(err) => match load_devices(&host) {
+            Some(cached) => {
+                eprintln!(
+                    "cannot list the devices ({}), using the device list of {}",
+                    err,
+                    cached.fetched.format("%Y-%m-%d %H:%M:%S")
+                );
+                Ok(cached.into_known())
+            }
+            None => Err(err),
+        },
+    }
+}
+
+fn devices_path(host: &str) -> Option<PathBuf> {
+    let key: String = host
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    dirs::cache_dir().map(|dir| dir.join("fritzctrl").join(format!("devices-{}.json", key)))
+}
+
+fn store_devices(host: &str, known: &Known) -> anyhow::Result<()> {
+    let path = devices_path(host).ok_or_else(|| anyhow::anyhow!("no cache dir"))?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let cached = CachedDevices::new(host, known);
+    std::fs::write(&path, serde_json::to_string(&cached)?)?;
+    Ok(())
+}
+
+fn load_devices(host: &str) -> Option<CachedDevices> {
+    let content = std::fs::read_to_string(devices_path(host)?).ok()?;
+    let cached: CachedDevices = serde_json::from_str(&content)
+        .map_err(|err| debug!("cannot read the cached device list: {}", err))
+        .ok()?;
+    (cached.host == host).then_some(cached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(time: &str) -> DateTime<Local> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap();
+        timing::local(time).unwrap()
+    }
+
+    const SCHEDULE: &str = "# Kaffee
+every weekday 06:30 Kaffee on
+2025-10-13 06:30:00 Kaffee off
+2025-10-01 06:30:00 Kaffee off
+to 2025-09-30 every day 07:00 Kaffee on
+every day 25:00 Kaffee on
+sunset Licht on
+";
+
+    #[test]
+    fn check_schedule() {
+        let findings = check(
+            SCHEDULE,
+            None,
+            &Profile::default(),
+            None,
+            local("2025-10-10 12:00:00"),
+            local("2025-12-31 00:00:00"),
+        );
+        let findings: Vec<_> = findings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            findings,
+            [
+                "line 3: error: Kaffee: \"off\" at the same time as \"on\" in line 2, first at 2025-10-13 06:30:00",
+                "line 4: warning: 2025-10-01 06:30:00 is in the past",
+                "line 5: warning: the date range ended on 2025-09-30",
+                "line 6: error: cannot parse \"every day 25:00 Kaffee on\": invalid time \"25:00\"",
+                "line 7: error: sunrise / sunset entries need a location, use --location or set the location of the profile",
+            ]
+        );
+
+        let findings = check(
+            SCHEDULE,
+            Some(&Known::default()),
+            &Profile::default(),
+            None,
+            local("2025-10-10 12:00:00"),
+            local("2025-12-31 00:00:00"),
+        );
+        assert_eq!(
+            findings
+                .iter()
+                .filter(|finding| finding.message.contains("No device"))
+                .map(|finding| finding.line)
+                .collect::<Vec<_>>(),
+            [2, 3, 4, 5, 7]
+        );
+
+        let known = Known {
+            templates: vec![Template {
+                identifier: "tmp6F0093-39136314A".parse().unwrap(),
+                id: "60001".to_string(),
+                functionbitmask: String::new(),
+                name: "Alles aus".to_string(),
+            }],
+            ..Default::default()
+        };
+        let findings = check(
+            "every day 23:00 \"Alles aus\" apply\nevery day 23:00 Bewegung disable\n",
+            Some(&known),
+            &Profile::default(),
+            None,
+            local("2025-10-10 12:00:00"),
+            local("2025-12-31 00:00:00"),
+        );
+        assert_eq!(
+            findings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["line 2: error: No trigger matches \"Bewegung\", known triggers: "]
+        );
+    }
+
+    #[test]
+    fn preview_schedule() {
+        let records = preview(
+            SCHEDULE,
+            None,
+            local("2025-10-10 12:00:00"),
+            local("2025-10-14 00:00:00"),
+        );
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| format!(
+                    "{} {} {} {}",
+                    record.time.format("%a %H:%M"),
+                    record.device,
+                    record.action,
+                    record.line
+                ))
+                .collect::<Vec<_>>(),
+            ["Mon 06:30 Kaffee on 2", "Mon 06:30 Kaffee off 3"]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! `--enforce-current-state`: devices that are not in the state of the last
+//! action the schedule had for them are switched, e.g. when the scheduler is
+//! started in the middle of an on period or a device was switched by hand.
+
+use super::action::Action;
+use super::schedule::Schedule;
+use crate::config::Profile;
+use crate::selector::{DeviceSelector, Inventory};
+use chrono::prelude::*;
+use fritzapi::{AVMDevice, Ain, FritzClient};
+use std::collections::HashMap;
+
+/// The actions that bring the devices into the state of the last action of
+/// `schedule` until `at`. The actions address the devices by their ain and
+/// keep the time they were scheduled for. Devices that are in that state
+/// already or not present are left out, entries that don't match any device
+/// are reported with `log`.
+pub fn actions(
+    schedule: &Schedule,
+    at: DateTime<Local>,
+    client: &mut FritzClient,
+    profile: &Profile,
+    log: impl Fn(String),
+) -> anyhow::Result<Vec<Action>> {
+    let mut last = Vec::new();
+    for action in schedule.last_actions(at) {
+        match action.device_id().parse::<DeviceSelector>() {
+            Ok(selector) => last.push((profile.expand_alias(selector), action)),
+            Err(err) => log(format!("{}: {}", action.device_id(), err)),
+        }
+    }
+    if last.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let selectors: Vec<_> = last.iter().map(|(selector, _)| selector.clone()).collect();
+    let inventory = Inventory::fetch(client, &selectors)?;
+
+    // the actions are in order, a later action for the same device wins
+    let mut expected: HashMap<&Ain, (&AVMDevice, &Action)> = HashMap::new();
+    for (selector, action) in &last {
+        match inventory.resolve(selector) {
+            Ok(devices) => {
+                for device in devices {
+                    expected.insert(device.id(), (device, action));
+                }
+            }
+            Err(err) => log(format!("{}: {}", action.device_id(), err)),
+        }
+    }
+
+    let mut actions: Vec<_> = expected
+        .into_values()
+        .filter(|(device, action)| {
+            device.is_present() && action.command.state() != Some(device.is_on())
+        })
+        .map(|(device, action)| action.with_device_id(device.id().to_string()))
+        .collect();
+    actions.sort_by(|a, b| (a.time(), a.device_id()).cmp(&(b.time(), b.device_id())));
+    Ok(actions)
+}
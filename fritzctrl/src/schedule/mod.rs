@@ -1,6 +1,11 @@
 mod action;
+pub mod check;
+pub mod enforce;
+pub mod retry;
+mod rule;
 #[allow(clippy::module_inception)]
 mod schedule;
+pub mod timing;
 
 pub use action::Action;
-pub use schedule::Schedule;
+pub use schedule::{RunOptions, Schedule};
@@ -0,0 +1,372 @@
+//! Running a scheduled action until the devices are in the expected state:
+//! failed devices are retried with an increasing delay, and a command can be
+//! run when an action ultimately fails.
+
+use super::action::Action;
+use crate::config::Profile;
+use crate::selector::DeviceSelector;
+use crate::switch::{self, SwitchOutcome, SwitchResult};
+use fritzapi::FritzClient;
+use std::time::{Duration, Instant};
+
+/// The longest delay between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How an action is retried, from `--retries`, `--retry-delay`, `--no-verify`
+/// and `--on-failure`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub retries: u32,
+    /// The delay before the first retry, doubled for every further one.
+    pub delay: Duration,
+    /// Whether the device states are read again after switching.
+    pub verify: bool,
+    /// Shell command run when the action failed after all retries.
+    pub on_failure: Option<String>,
+}
+
+impl RetryPolicy {
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self {
+            retries: *args.get_one::<u32>("retries").unwrap(),
+            delay: args
+                .get_one::<chrono::Duration>("retry-delay")
+                .unwrap()
+                .to_std()
+                .unwrap_or_default(),
+            verify: !args.get_flag("no-verify"),
+            on_failure: args.get_one::<String>("on-failure").cloned(),
+        }
+    }
+
+    /// The delays before the retries.
+    fn delays(&self) -> impl Iterator<Item = Duration> {
+        std::iter::successors(Some(self.delay), |delay| Some((*delay * 2).min(MAX_DELAY)))
+            .take(self.retries as usize)
+    }
+}
+
+/// An action that failed for some devices, they are tried again at `due`.
+struct Pending {
+    action: Action,
+    selectors: Vec<DeviceSelector>,
+    /// The results of the devices that were switched by earlier attempts.
+    done: Vec<SwitchResult>,
+    /// Retries so far.
+    retries: u32,
+    due: Instant,
+}
+
+/// Runs scheduled actions, retrying devices that are not present, fail or are
+/// not in the expected state afterwards. Locked devices are not retried. If
+/// an action still fails the failure command is run.
+///
+/// The scheduler keeps running while an action waits for its retry: the
+/// retries are kept here and run by [Retries::run_due] on a later tick.
+pub struct Retries {
+    policy: RetryPolicy,
+    pending: Vec<Pending>,
+}
+
+impl Retries {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Whether no action waits for a retry.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Runs `action`, its results are returned unless it is retried later.
+    /// Retries of an earlier action of the same device are dropped, they
+    /// would undo this one. `log` reports the retries.
+    pub fn run(
+        &mut self,
+        action: &Action,
+        client: &mut FritzClient,
+        profile: &Profile,
+        log: impl Fn(String),
+    ) -> Option<anyhow::Result<Vec<SwitchResult>>> {
+        let (superseded, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|pending| pending.action.device_id() == action.device_id());
+        self.pending = pending;
+        for pending in superseded {
+            log(format!(
+                "{}: not retrying {}, superseded by {}",
+                action.device_id(),
+                pending.action.command,
+                action.command
+            ));
+        }
+
+        let selector = match action.device_id().parse() {
+            Ok(selector) => profile.expand_alias(selector),
+            Err(err) => return Some(self.finish(action, Err(err), &log)),
+        };
+        let pending = Pending {
+            action: action.clone(),
+            selectors: vec![selector],
+            done: Vec::new(),
+            retries: 0,
+            due: Instant::now(),
+        };
+        self.attempt(pending, client, &log)
+            .map(|(_, result)| result)
+    }
+
+    /// Runs the retries that are due at `now`, returns the actions that are
+    /// finished with their results.
+    pub fn run_due(
+        &mut self,
+        client: &mut FritzClient,
+        now: Instant,
+        log: impl Fn(String),
+    ) -> Vec<(Action, anyhow::Result<Vec<SwitchResult>>)> {
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|pending| pending.due <= now);
+        self.pending = waiting;
+        due.into_iter()
+            .filter_map(|pending| self.attempt(pending, client, &log))
+            .collect()
+    }
+
+    fn attempt(
+        &mut self,
+        mut pending: Pending,
+        client: &mut FritzClient,
+        log: &impl Fn(String),
+    ) -> Option<(Action, anyhow::Result<Vec<SwitchResult>>)> {
+        let result =
+            switch::run(client, &pending.selectors, pending.action.command).and_then(|results| {
+                if self.policy.verify {
+                    verify(client, &pending.action, results)
+                } else {
+                    Ok(results)
+                }
+            });
+
+        let failure = match &result {
+            Err(err) => Some(err.to_string()),
+            Ok(results) => {
+                let failed: Vec<_> = results
+                    .iter()
+                    .filter(|result| retryable(&result.outcome))
+                    .map(|result| format!("{} ({})", result.id, result.outcome))
+                    .collect();
+                (!failed.is_empty()).then(|| failed.join(", "))
+            }
+        };
+        let next = self.policy.delays().nth(pending.retries as usize);
+        let delay = match failure.as_ref().and(next) {
+            Some(delay) => delay,
+            None => {
+                let result = result.map(|results| {
+                    pending.done.extend(results);
+                    pending.done
+                });
+                let result = self.finish(&pending.action, result, log);
+                return Some((pending.action, result));
+            }
+        };
+
+        if let Ok(results) = result {
+            let (failed, ok): (Vec<_>, Vec<_>) = results
+                .into_iter()
+                .partition(|result| retryable(&result.outcome));
+            pending.done.extend(ok);
+            pending.selectors = failed
+                .iter()
+                .map(|result| DeviceSelector::Plain(result.id.to_string()))
+                .collect();
+        }
+        log(format!(
+            "{}: {}, retrying in {}s",
+            pending.action.device_id(),
+            failure.unwrap_or_default(),
+            delay.as_secs()
+        ));
+        pending.retries += 1;
+        pending.due = Instant::now() + delay;
+        self.pending.push(pending);
+        None
+    }
+
+    /// Runs the failure command if `result` is a failure.
+    fn finish(
+        &self,
+        action: &Action,
+        result: anyhow::Result<Vec<SwitchResult>>,
+        log: &impl Fn(String),
+    ) -> anyhow::Result<Vec<SwitchResult>> {
+        let error = match &result {
+            Err(err) => Some(err.to_string()),
+            Ok(results) => {
+                let failed: Vec<_> = results
+                    .iter()
+                    .filter(|result| !result.outcome.is_ok())
+                    .map(|result| format!("{} ({})", result.id, result.outcome))
+                    .collect();
+                (!failed.is_empty()).then(|| format!("failed to switch {}", failed.join(", ")))
+            }
+        };
+        if let (Some(error), Some(command)) = (error, &self.policy.on_failure) {
+            if let Err(err) = run_failure_command(command, action, &error) {
+                log(err.to_string());
+            }
+        }
+        result
+    }
+}
+
+/// Whether trying again might help, a locked device stays locked.
+fn retryable(outcome: &SwitchOutcome) -> bool {
+    matches!(
+        outcome,
+        SwitchOutcome::NotPresent | SwitchOutcome::Error { .. }
+    )
+}
+
+/// Reads the devices again and turns the outcome of the devices that are not
+/// in the expected state into errors. Only actions that switch a device on or
+/// off are verified.
+fn verify(
+    client: &mut FritzClient,
+    action: &Action,
+    mut results: Vec<SwitchResult>,
+) -> anyhow::Result<Vec<SwitchResult>> {
+    let Some(on) = action.command.state() else {
+        return Ok(results);
+    };
+    if !results.iter().any(|result| result.outcome.is_ok()) {
+        return Ok(results);
+    }
+    let devices = client.list_devices()?;
+    for result in results.iter_mut().filter(|result| result.outcome.is_ok()) {
+        let device = devices.iter().find(|device| device.id() == &result.id);
+        result.outcome = match device {
+            None => SwitchOutcome::Error {
+                error: "device is gone".to_string(),
+            },
+            Some(device) if !device.is_present() => SwitchOutcome::NotPresent,
+            Some(device) if device.is_on() != on => SwitchOutcome::Error {
+                error: format!("device is {} after switching", device.state()),
+            },
+            Some(_) => SwitchOutcome::Ok { on },
+        };
+    }
+    Ok(results)
+}
+
+/// Runs `command` with the shell, the action and the error are passed in the
+/// `FRITZCTRL_TIME`, `FRITZCTRL_DEVICE`, `FRITZCTRL_ACTION` and
+/// `FRITZCTRL_ERROR` environment variables.
+fn run_failure_command(command: &str, action: &Action, error: &str) -> anyhow::Result<()> {
+    let mut process = if cfg!(windows) {
+        let mut process = std::process::Command::new("cmd");
+        process.args(["/C", command]);
+        process
+    } else {
+        let mut process = std::process::Command::new("sh");
+        process.args(["-c", command]);
+        process
+    };
+    let status = process
+        .env(
+            "FRITZCTRL_TIME",
+            action.time().format("%Y-%m-%d %H:%M:%S").to_string(),
+        )
+        .env("FRITZCTRL_DEVICE", action.device_id())
+        .env("FRITZCTRL_ACTION", action.command.to_string())
+        .env("FRITZCTRL_ERROR", error)
+        .status()
+        .map_err(|err| anyhow::anyhow!("cannot run failure command {:?}: {}", command, err))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "failure command {:?} failed with {}",
+            command,
+            status
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy {
+            retries: 7,
+            delay: Duration::from_secs(10),
+            verify: true,
+            on_failure: None,
+        };
+        assert_eq!(
+            policy
+                .delays()
+                .map(|delay| delay.as_secs())
+                .collect::<Vec<_>>(),
+            [10, 20, 40, 80, 160, 300, 300]
+        );
+        let policy = RetryPolicy {
+            retries: 0,
+            ..policy
+        };
+        assert_eq!(policy.delays().count(), 0);
+
+        assert!(retryable(&SwitchOutcome::NotPresent));
+        assert!(!retryable(&SwitchOutcome::Locked));
+        assert!(!retryable(&SwitchOutcome::Ok { on: true }));
+    }
+
+    #[test]
+    fn retries_do_not_block() {
+        let policy = RetryPolicy {
+            retries: 1,
+            delay: Duration::from_secs(10),
+            verify: true,
+            on_failure: None,
+        };
+        let mut retries = Retries::new(policy);
+        // never logs in, every attempt fails without a request
+        let mut client = FritzClient::new("user", "")
+            .with_host("192.0.2.1")
+            .unwrap()
+            .without_login();
+        let profile = Profile::default();
+        let action: Action = "2025-10-11 09:00:00 Kaffee on".parse().unwrap();
+        let messages = std::cell::RefCell::new(Vec::new());
+        let log = |message| messages.borrow_mut().push(message);
+
+        let started = Instant::now();
+        assert!(retries.run(&action, &mut client, &profile, log).is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!retries.is_empty());
+        assert!(retries.run_due(&mut client, started, log).is_empty());
+
+        // the last retry finishes the action
+        let finished = retries.run_due(&mut client, Instant::now() + Duration::from_secs(10), log);
+        assert_eq!(finished.len(), 1);
+        assert!(finished[0].1.is_err());
+        assert!(retries.is_empty());
+
+        // a later action of the device replaces the retry
+        retries.run(&action, &mut client, &profile, log);
+        let off: Action = "2025-10-11 10:00:00 Kaffee off".parse().unwrap();
+        retries.run(&off, &mut client, &profile, log);
+        assert_eq!(retries.pending.len(), 1);
+        assert_eq!(retries.pending[0].action, off);
+        assert!(messages
+            .borrow()
+            .iter()
+            .any(|message| message == "Kaffee: not retrying on, superseded by off"));
+    }
+}
@@ -0,0 +1,602 @@
+use super::action::Action;
+use super::timing;
+use crate::daylight::{self, Location};
+use crate::switch::{SwitchAction, ACTION_PATTERN};
+use chrono::prelude::*;
+use croner::Cron;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// How many days are searched for the next / previous occurrence of a rule.
+const SEARCH_DAYS: u64 = 366;
+
+/// A recurring schedule line such as `every weekday 06:30 "Kaffee" on`,
+/// `cron "0 17 * * *" Lichterkette on`, `sunset-30min Lichterkette on` or
+/// `from 2025-12-01 to 2026-01-06 every day 17:00 Lichterkette on`. The times
+/// are expanded on demand by [Rule::next_after] and [Rule::last_until].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub recurrence: Recurrence,
+    /// First day the rule applies to.
+    pub from: Option<NaiveDate>,
+    /// Last day the rule applies to.
+    pub to: Option<NaiveDate>,
+    pub id: String,
+    pub command: SwitchAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    /// At `time` on each of `days`.
+    Daily { days: Weekdays, time: TimeOfDay },
+    /// Whenever the cron expression matches.
+    Cron(Box<Cron>),
+}
+
+/// When a [Recurrence::Daily] rule fires on a day.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeOfDay {
+    At(NaiveTime),
+    /// Sunrise or sunset shifted by `offset`, optionally kept within
+    /// `not_before` and `not_after`.
+    Sun {
+        event: SunEvent,
+        offset: chrono::Duration,
+        not_before: Option<NaiveTime>,
+        not_after: Option<NaiveTime>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SunEvent {
+    Sunrise,
+    Sunset,
+}
+
+impl TimeOfDay {
+    /// The time on `date`, `None` if it does not exist in the local time
+    /// zone, the sun does not rise / set or the location is unknown.
+    pub fn on(&self, date: NaiveDate, location: Option<&Location>) -> Option<DateTime<Local>> {
+        let local = |time: NaiveTime| timing::local(date.and_time(time));
+        match self {
+            TimeOfDay::At(time) => local(*time),
+            TimeOfDay::Sun {
+                event,
+                offset,
+                not_before,
+                not_after,
+            } => {
+                let (sunrise, sunset) = daylight::sunrise_sunset(location?, date)?;
+                let mut time = match event {
+                    SunEvent::Sunrise => sunrise,
+                    SunEvent::Sunset => sunset,
+                } + *offset;
+                if let Some(not_before) = not_before.and_then(local) {
+                    time = time.max(not_before);
+                }
+                if let Some(not_after) = not_after.and_then(local) {
+                    time = time.min(not_after);
+                }
+                Some(time)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for TimeOfDay {
+    type Err = anyhow::Error;
+
+    /// `HH:MM[:SS]`, or `sunrise` / `sunset` with an optional offset such as
+    /// `+15min` or `-1h`, followed by optional `not before HH:MM` and
+    /// `not after HH:MM` clamps.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref SUN: Regex = regex::RegexBuilder::new(
+                r"^(sunrise|sunset)(?:([+-])(\S+))?((?:\s+not\s+(?:before|after)\s+\S+)*)$"
+            )
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+            static ref CLAMP: Regex = regex::RegexBuilder::new(r"not\s+(before|after)\s+(\S+)")
+                .case_insensitive(true)
+                .build()
+                .unwrap();
+        }
+
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+                .map_err(|_| anyhow::anyhow!("invalid time {:?}", time))
+        };
+        let input = input.trim();
+        let Some(captures) = SUN.captures(input) else {
+            return Ok(TimeOfDay::At(time(input)?));
+        };
+
+        let event = if captures[1].eq_ignore_ascii_case("sunrise") {
+            SunEvent::Sunrise
+        } else {
+            SunEvent::Sunset
+        };
+        let offset = match (captures.get(2), captures.get(3)) {
+            (Some(sign), Some(offset)) => crate::parser::parse_duration(&format!(
+                "{}{}",
+                sign.as_str().trim_start_matches('+'),
+                offset.as_str()
+            ))
+            .map_err(|err| anyhow::anyhow!("invalid offset {:?}: {}", offset.as_str(), err))?,
+            _ => chrono::Duration::zero(),
+        };
+        let (mut not_before, mut not_after) = (None, None);
+        for clamp in CLAMP.captures_iter(&captures[4]) {
+            let value = Some(time(&clamp[2])?);
+            if clamp[1].eq_ignore_ascii_case("before") {
+                not_before = value;
+            } else {
+                not_after = value;
+            }
+        }
+        Ok(TimeOfDay::Sun {
+            event,
+            offset,
+            not_before,
+            not_after,
+        })
+    }
+}
+
+/// A set of days of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    pub const ALL: Weekdays = Weekdays(0b111_1111);
+    pub const WORKDAYS: Weekdays = Weekdays(0b001_1111);
+    pub const WEEKEND: Weekdays = Weekdays(0b110_0000);
+
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.0 & (1 << day.num_days_from_monday()) != 0
+    }
+
+    fn insert(&mut self, day: Weekday) {
+        self.0 |= 1 << day.num_days_from_monday();
+    }
+}
+
+impl std::str::FromStr for Weekdays {
+    type Err = anyhow::Error;
+
+    /// `day`, `weekday`, `weekend` or a list of days and ranges of days like
+    /// `mon,wed,fri` or `mon-thu,sat`.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_lowercase().as_str() {
+            "day" | "days" | "daily" => return Ok(Weekdays::ALL),
+            "weekday" | "weekdays" | "workday" | "workdays" => return Ok(Weekdays::WORKDAYS),
+            "weekend" | "weekends" => return Ok(Weekdays::WEEKEND),
+            _ => {}
+        }
+
+        let weekday = |name: &str| {
+            name.parse::<Weekday>()
+                .map_err(|_| anyhow::anyhow!("unknown day {:?}", name))
+        };
+        let mut days = Weekdays(0);
+        for item in input.split(',') {
+            match item.split_once('-') {
+                Some((first, last)) => {
+                    let (mut day, last) = (weekday(first)?, weekday(last)?);
+                    days.insert(day);
+                    while day != last {
+                        day = day.succ();
+                        days.insert(day);
+                    }
+                }
+                None => days.insert(weekday(item)?),
+            }
+        }
+        Ok(days)
+    }
+}
+
+impl Rule {
+    /// Whether `line` starts like a rule rather than a single action.
+    pub fn is_rule(line: &str) -> bool {
+        let first_word = line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        ["every", "cron", "from", "to", "until"].contains(&first_word.as_str())
+            || first_word.starts_with("sunrise")
+            || first_word.starts_with("sunset")
+    }
+
+    /// Whether the times depend on sunrise / sunset and need a location.
+    pub fn needs_location(&self) -> bool {
+        matches!(
+            self.recurrence,
+            Recurrence::Daily {
+                time: TimeOfDay::Sun { .. },
+                ..
+            }
+        )
+    }
+
+    /// The first time the rule fires after `at`. Sun-relative times need the
+    /// `location`.
+    pub fn next_after(
+        &self,
+        at: DateTime<Local>,
+        location: Option<&Location>,
+    ) -> Option<DateTime<Local>> {
+        match &self.recurrence {
+            Recurrence::Daily { days, time } => {
+                // sun-relative times can be shifted into the previous day
+                let start = at.date_naive().pred_opt()?;
+                let start = self.from.map_or(start, |from| from.max(start));
+                start
+                    .iter_days()
+                    .take(SEARCH_DAYS as usize)
+                    .take_while(|date| self.to.is_none_or(|to| *date <= to))
+                    .filter(|date| days.contains(date.weekday()))
+                    .filter_map(|date| time.on(date, location))
+                    .find(|time| *time > at)
+            }
+            Recurrence::Cron(cron) => {
+                let from = self
+                    .from
+                    .filter(|from| *from > at.date_naive())
+                    .and_then(|from| timing::local(from.and_time(NaiveTime::MIN)));
+                let time = match from {
+                    Some(from) => cron.find_next_occurrence(&from, true).ok()?,
+                    None => cron.find_next_occurrence(&at, false).ok()?,
+                };
+                self.contains(time.date_naive()).then_some(time)
+            }
+        }
+    }
+
+    /// The times the rule fires after `from` until (and including) `to`,
+    /// computed one by one so that callers can stop early.
+    pub fn times_between<'a>(
+        &'a self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+        location: Option<&'a Location>,
+    ) -> impl Iterator<Item = DateTime<Local>> + 'a {
+        std::iter::successors(self.next_after(from, location), move |at| {
+            self.next_after(*at, location)
+        })
+        .take_while(move |time| *time <= to)
+    }
+
+    /// The last time the rule fired until (and including) `at`.
+    pub fn last_until(
+        &self,
+        at: DateTime<Local>,
+        location: Option<&Location>,
+    ) -> Option<DateTime<Local>> {
+        match &self.recurrence {
+            Recurrence::Daily { days, time } => {
+                let end = at.date_naive().succ_opt()?;
+                let end = self.to.map_or(end, |to| to.min(end));
+                end.iter_days()
+                    .rev()
+                    .take(SEARCH_DAYS as usize)
+                    .take_while(|date| self.from.is_none_or(|from| *date >= from))
+                    .filter(|date| days.contains(date.weekday()))
+                    .filter_map(|date| time.on(date, location))
+                    .find(|time| *time <= at)
+            }
+            Recurrence::Cron(cron) => {
+                let to = self
+                    .to
+                    .filter(|to| *to < at.date_naive())
+                    .and_then(|to| to.succ_opt())
+                    .and_then(|day| timing::local(day.and_time(NaiveTime::MIN)));
+                let time = match to {
+                    Some(to) => cron.find_previous_occurrence(&to, false).ok()?,
+                    None => cron.find_previous_occurrence(&at, true).ok()?,
+                };
+                self.contains(time.date_naive()).then_some(time)
+            }
+        }
+    }
+
+    /// The action the rule performs at `time`.
+    pub fn action_at(&self, time: DateTime<Local>) -> Action {
+        Action {
+            time,
+            id: self.id.clone(),
+            command: self.command,
+        }
+    }
+
+    /// Whether `date` is within the date range of the rule.
+    fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+}
+
+impl std::str::FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: Regex = regex::RegexBuilder::new(&format!(
+                r#"^\s*(?:from\s+([0-9]{{4}}-[0-9]{{2}}-[0-9]{{2}})\s+)?(?:(?:to|until)\s+([0-9]{{4}}-[0-9]{{2}}-[0-9]{{2}})\s+)?(?:every\s+(\S+)\s+([0-9]{{1,2}}:[0-9]{{2}}(?::[0-9]{{2}})?|(?:sunrise|sunset)\S*(?:\s+not\s+(?:before|after)\s+\S+)*)|((?:sunrise|sunset)\S*(?:\s+not\s+(?:before|after)\s+\S+)*)|cron\s+"([^"]+)")\s+(.+?)\s+({})\s*$"#,
+                ACTION_PATTERN
+            ))
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        }
+
+        let captures = RE.captures(line).ok_or_else(|| {
+            anyhow::anyhow!(
+                "does not match recurring schedule format, expected e.g. `every weekday 06:30 DEVICE on`, `sunset-30min DEVICE on` or `cron \"30 6 * * 1-5\" DEVICE on`"
+            )
+        })?;
+        let date = |i: usize| {
+            captures
+                .get(i)
+                .map(|date| NaiveDate::parse_from_str(date.as_str(), "%Y-%m-%d"))
+                .transpose()
+        };
+        let (from, to) = (date(1)?, date(2)?);
+        if let (Some(from), Some(to)) = (from, to) {
+            if to < from {
+                anyhow::bail!("date range ends ({}) before it starts ({})", to, from);
+            }
+        }
+
+        let recurrence = match (
+            captures.get(3),
+            captures.get(4),
+            captures.get(5),
+            captures.get(6),
+        ) {
+            (Some(days), Some(time), _, _) => Recurrence::Daily {
+                days: days.as_str().parse()?,
+                time: time.as_str().parse()?,
+            },
+            (_, _, Some(time), _) => Recurrence::Daily {
+                days: Weekdays::ALL,
+                time: time.as_str().parse()?,
+            },
+            (_, _, _, Some(expression)) => Recurrence::Cron(Box::new(
+                expression
+                    .as_str()
+                    .parse()
+                    .map_err(|err| anyhow::anyhow!("invalid cron expression: {}", err))?,
+            )),
+            _ => unreachable!(),
+        };
+
+        Ok(Rule {
+            recurrence,
+            from,
+            to,
+            id: captures
+                .get(7)
+                .unwrap()
+                .as_str()
+                .trim_matches('"')
+                .to_string(),
+            command: captures.get(8).unwrap().as_str().parse()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(time: &str) -> DateTime<Local> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&time).unwrap()
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rule: Rule = "every weekday 06:30 \"Kaffee\" on".parse().unwrap();
+        assert_eq!(
+            rule,
+            Rule {
+                recurrence: Recurrence::Daily {
+                    days: Weekdays::WORKDAYS,
+                    time: TimeOfDay::At(NaiveTime::from_hms_opt(6, 30, 0).unwrap())
+                },
+                from: None,
+                to: None,
+                id: "Kaffee".to_string(),
+                command: SwitchAction::On,
+            }
+        );
+
+        let rule: Rule =
+            "from 2025-12-01 to 2026-01-06 every sat,sun-mon 17:00:30 11630 0123723 off"
+                .parse()
+                .unwrap();
+        assert_eq!(rule.from, NaiveDate::from_ymd_opt(2025, 12, 1));
+        assert_eq!(rule.to, NaiveDate::from_ymd_opt(2026, 1, 6));
+        assert_eq!(rule.id, "11630 0123723");
+        assert_eq!(rule.command, SwitchAction::Off);
+        let Recurrence::Daily { days, .. } = rule.recurrence else {
+            panic!("expected daily rule")
+        };
+        assert_eq!(days, "sat-mon".parse().unwrap());
+        assert!(days.contains(Weekday::Sun) && !days.contains(Weekday::Tue));
+
+        let rule: Rule = "cron \"0 17 * * MON-FRI\" group:Garten on".parse().unwrap();
+        assert!(matches!(rule.recurrence, Recurrence::Cron(_)));
+        assert_eq!(rule.id, "group:Garten");
+
+        let rule: Rule = "every weekday sunset \"Licht Flur\" brightness 30%"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            (rule.id.as_str(), rule.command),
+            ("Licht Flur", SwitchAction::Brightness(30))
+        );
+        let rule: Rule = "every day 22:00 Abends apply".parse().unwrap();
+        assert_eq!(rule.command, SwitchAction::ApplyTemplate);
+
+        assert!("every someday 06:30 Kaffee on".parse::<Rule>().is_err());
+        assert!("every day 25:00 Kaffee on".parse::<Rule>().is_err());
+        assert!("cron \"61 * * * *\" Kaffee on".parse::<Rule>().is_err());
+        assert!("from 2026-01-06 to 2025-12-01 every day 06:30 Kaffee on"
+            .parse::<Rule>()
+            .is_err());
+        assert!("2021-01-31 01:02:03 aaabbb on".parse::<Rule>().is_err());
+        assert!(!Rule::is_rule("2021-01-31 01:02:03 aaabbb on"));
+        assert!(Rule::is_rule("Sunset-30min Lichterkette on"));
+        assert!(Rule::is_rule("every day 06:30 Kaffee on"));
+    }
+
+    #[test]
+    fn weekday_occurrences() {
+        let rule: Rule = "every weekday 06:30 Kaffee on".parse().unwrap();
+        // Friday 2025-10-10
+        assert_eq!(
+            rule.next_after(local("2025-10-10 06:00"), None),
+            Some(local("2025-10-10 06:30"))
+        );
+        assert_eq!(
+            rule.next_after(local("2025-10-10 06:30"), None),
+            Some(local("2025-10-13 06:30"))
+        );
+        assert_eq!(
+            rule.last_until(local("2025-10-12 23:00"), None),
+            Some(local("2025-10-10 06:30"))
+        );
+        assert_eq!(
+            rule.last_until(local("2025-10-13 06:30"), None),
+            Some(local("2025-10-13 06:30"))
+        );
+        assert_eq!(
+            rule.action_at(local("2025-10-13 06:30")),
+            Action {
+                time: local("2025-10-13 06:30"),
+                id: "Kaffee".to_string(),
+                command: SwitchAction::On,
+            }
+        );
+    }
+
+    #[test]
+    fn date_ranges() {
+        for line in [
+            "from 2025-12-01 to 2025-12-03 every day 17:00 Lichterkette on",
+            "from 2025-12-01 to 2025-12-03 cron \"0 17 * * *\" Lichterkette on",
+        ] {
+            let rule: Rule = line.parse().unwrap();
+            assert_eq!(
+                rule.next_after(local("2025-10-01 12:00"), None),
+                Some(local("2025-12-01 17:00")),
+                "{}",
+                line
+            );
+            assert_eq!(
+                rule.next_after(local("2025-12-02 17:00"), None),
+                Some(local("2025-12-03 17:00")),
+                "{}",
+                line
+            );
+            assert_eq!(
+                rule.next_after(local("2025-12-03 17:00"), None),
+                None,
+                "{}",
+                line
+            );
+            assert_eq!(
+                rule.last_until(local("2025-12-01 16:59"), None),
+                None,
+                "{}",
+                line
+            );
+            assert_eq!(
+                rule.last_until(local("2026-03-01 12:00"), None),
+                Some(local("2025-12-03 17:00")),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn cron_occurrences() {
+        let rule: Rule = "cron \"*/15 6-7 * * 1-5\" Kaffee off".parse().unwrap();
+        assert_eq!(
+            rule.next_after(local("2025-10-10 07:50"), None),
+            Some(local("2025-10-13 06:00"))
+        );
+        assert_eq!(
+            rule.last_until(local("2025-10-13 06:14"), None),
+            Some(local("2025-10-13 06:00"))
+        );
+    }
+
+    #[test]
+    fn sun_relative() {
+        let berlin: Location = "52.52,13.405".parse().unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 12, 10).unwrap();
+        let (sunrise, sunset) = daylight::sunrise_sunset(&berlin, date).unwrap();
+        let at = |date: NaiveDate, time: &str| local(&format!("{} {}", date, time));
+
+        let rule: Rule = "sunset-30min \"Lichterkette\" on".parse().unwrap();
+        assert!(rule.needs_location());
+        assert_eq!(rule.id, "Lichterkette");
+        assert_eq!(rule.next_after(at(date, "00:00"), None), None);
+        assert_eq!(
+            rule.next_after(at(date, "00:00"), Some(&berlin)),
+            Some(sunset - chrono::Duration::minutes(30))
+        );
+
+        let rule: Rule = "every weekday sunrise+15min Lichterkette off"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rule.last_until(sunrise + chrono::Duration::hours(1), Some(&berlin)),
+            Some(sunrise + chrono::Duration::minutes(15))
+        );
+
+        let rule: Rule = "sunrise-12h not before 06:00 not after 07:00 Kaffee on"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rule.recurrence,
+            Recurrence::Daily {
+                days: Weekdays::ALL,
+                time: TimeOfDay::Sun {
+                    event: SunEvent::Sunrise,
+                    offset: chrono::Duration::hours(-12),
+                    not_before: NaiveTime::from_hms_opt(6, 0, 0),
+                    not_after: NaiveTime::from_hms_opt(7, 0, 0),
+                }
+            }
+        );
+        assert_eq!(
+            rule.next_after(at(date, "05:00"), Some(&berlin)),
+            Some(at(date, "06:00"))
+        );
+        let rule: Rule = "sunset+14h not after 22:30 Lichterkette off"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            rule.next_after(at(date, "12:00"), Some(&berlin)),
+            Some(at(date, "22:30"))
+        );
+
+        // no sunset during the polar night
+        let svalbard = Location::new(78.2, 15.6);
+        let rule: Rule = "sunset Lichterkette on".parse().unwrap();
+        let next = rule.next_after(at(date, "00:00"), Some(&svalbard)).unwrap();
+        assert!(next.date_naive() > NaiveDate::from_ymd_opt(2026, 2, 1).unwrap());
+
+        assert!("sunset-soon Lichterkette on".parse::<Rule>().is_err());
+        assert!("sunset not before 25:00 Lichterkette on"
+            .parse::<Rule>()
+            .is_err());
+    }
+}
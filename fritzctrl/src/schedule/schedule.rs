@@ -1,65 +1,269 @@
 use super::action::Action;
-use crate::switch;
+use super::enforce;
+use super::retry::{Retries, RetryPolicy};
+use super::rule::Rule;
+use super::timing::{self, MissedPolicy, Ticker};
+use crate::config::Profile;
+use crate::daylight::Location;
+use crate::switch::{SwitchAction, SwitchResult};
 use chrono::prelude::*;
-use std::{fs, path::Path};
+use fritzapi::FritzClient;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// How a schedule is run, from the command line arguments of `schedule` and
+/// `daemon`.
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub missed: MissedPolicy,
+    pub retry: RetryPolicy,
+    /// How often devices are switched to the state the schedule says they
+    /// should be in, `None` unless `--enforce-current-state` is given.
+    pub enforce: Option<std::time::Duration>,
+}
+
+impl RunOptions {
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self {
+            missed: MissedPolicy::from_args(args),
+            retry: RetryPolicy::from_args(args),
+            enforce: args.get_flag("enforce-current-state").then(|| {
+                args.get_one::<chrono::Duration>("enforce-interval")
+                    .unwrap()
+                    .to_std()
+                    .unwrap_or_default()
+            }),
+        }
+    }
+}
+
+/// A line of a schedule.
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Action(Action),
+    Rule(Rule),
+}
+
+impl Entry {
+    pub fn device_id(&self) -> &str {
+        match self {
+            Entry::Action(action) => action.device_id(),
+            Entry::Rule(rule) => &rule.id,
+        }
+    }
+
+    pub fn command(&self) -> SwitchAction {
+        match self {
+            Entry::Action(action) => action.command,
+            Entry::Rule(rule) => rule.command,
+        }
+    }
+
+    /// The actions of this entry after `from` until (and including) `to`, in
+    /// order and computed one by one.
+    pub fn actions_between<'a>(
+        &'a self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+        location: Option<&'a Location>,
+    ) -> impl Iterator<Item = Action> + 'a {
+        let (once, rule) = match self {
+            Entry::Action(action) => (
+                Some(action.clone()).filter(|action| action.time() > from && action.time() <= to),
+                None,
+            ),
+            Entry::Rule(rule) => (None, Some(rule)),
+        };
+        let recurring = rule.into_iter().flat_map(move |rule| {
+            rule.times_between(from, to, location)
+                .map(|time| rule.action_at(time))
+        });
+        once.into_iter().chain(recurring)
+    }
+}
+
+/// Parses the lines of a schedule, each with its line number (starting at 1).
+/// Empty lines and comments are left out.
+pub fn parse_lines(string: &str) -> Vec<(usize, anyhow::Result<Entry>)> {
+    string
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let trimmed = line.trim();
+            !trimmed.is_empty() && !trimmed.starts_with('#')
+        })
+        .map(|(i, line)| {
+            let entry = if Rule::is_rule(line.trim()) {
+                line.parse().map(Entry::Rule)
+            } else {
+                line.parse().map(Entry::Action)
+            };
+            (i + 1, entry)
+        })
+        .collect()
+}
 
 #[derive(Debug)]
 pub struct Schedule {
     pub actions: Vec<Action>,
+    /// Recurring entries, expanded into actions by [Schedule::next_action].
+    pub rules: Vec<Rule>,
+    /// Location for sunrise / sunset relative rules.
+    pub location: Option<Location>,
 }
 
 impl Schedule {
-    #[allow(dead_code)]
-    pub fn from_file<P: AsRef<Path>>(schedule_file: P) -> anyhow::Result<Self> {
-        let content = fs::read_to_string(&schedule_file)?;
-        Self::from_string(content)
-    }
-
+    /// The schedule of the lines of `string`, fails if any line is invalid:
+    /// running only part of a schedule could leave devices on.
     pub fn from_string(string: String) -> anyhow::Result<Self> {
-        let lines = string.lines();
-        let actions: Vec<Action> = lines
-            .into_iter()
-            .filter_map(|line| {
-                line.parse()
-                    .map_err(|_| {
-                        eprintln!("[schedule] cannot parse line {:?}", line);
-                    })
-                    .ok()
-            })
-            .collect();
+        let mut schedule = Schedule {
+            actions: Vec::new(),
+            rules: Vec::new(),
+            location: None,
+        };
+
+        let mut errors = Vec::new();
+        for (number, entry) in parse_lines(&string) {
+            match entry {
+                Ok(Entry::Action(action)) => schedule.actions.push(action),
+                Ok(Entry::Rule(rule)) => schedule.rules.push(rule),
+                Err(err) => errors.push(format!("line {}: {}", number, err)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("{}", errors.join(", ")));
+        }
 
-        let mut schedule = Schedule { actions };
         schedule.actions.sort_by_key(|ea| ea.time());
         Ok(schedule)
     }
 
+    /// The next action after `at`, either from the list of actions or the
+    /// next occurrence of a recurring rule.
     pub fn next_action(&self, at: DateTime<Local>) -> Option<Action> {
-        self.actions
+        let once = self
+            .actions
             .iter()
             .find(|action| action.time() > at)
+            .cloned();
+        let recurring = self.rules.iter().filter_map(|rule| {
+            rule.next_after(at, self.location.as_ref())
+                .map(|time| rule.action_at(time))
+        });
+        once.into_iter().chain(recurring).min_by_key(Action::time)
+    }
+
+    /// All actions after `from` until (and including) `to`, in order.
+    pub fn actions_between(&self, from: DateTime<Local>, to: DateTime<Local>) -> Vec<Action> {
+        let mut actions: Vec<_> = self
+            .actions
+            .iter()
+            .filter(|action| action.time() > from && action.time() <= to)
             .cloned()
+            .collect();
+        for rule in &self.rules {
+            let times = rule.times_between(from, to, self.location.as_ref());
+            actions.extend(times.map(|time| rule.action_at(time)));
+        }
+        actions.sort_by_key(Action::time);
+        actions
     }
 
-    #[allow(dead_code)]
-    pub fn last_action(&self, at: DateTime<Local>) -> Option<&Action> {
-        self.actions
+    /// The last action until (and including) `at` that switches each device
+    /// on or off, in order. Devices are compared as written in the schedule,
+    /// `Kaffee` and `"Kaffee*"` are different even if they match the same
+    /// device.
+    pub fn last_actions(&self, at: DateTime<Local>) -> Vec<Action> {
+        let once = self
+            .actions
             .iter()
             .take_while(|action| action.time() <= at)
-            .last()
+            .filter(|action| action.command.state().is_some())
+            .cloned();
+        let recurring = self
+            .rules
+            .iter()
+            .filter(|rule| rule.command.state().is_some())
+            .filter_map(|rule| {
+                rule.last_until(at, self.location.as_ref())
+                    .map(|time| rule.action_at(time))
+            });
+        let mut last: HashMap<String, Action> = HashMap::new();
+        for action in once.chain(recurring) {
+            match last.get(action.device_id()) {
+                Some(previous) if previous.time() > action.time() => {}
+                _ => {
+                    last.insert(action.device_id().to_string(), action);
+                }
+            }
+        }
+        let mut actions: Vec<_> = last.into_values().collect();
+        actions.sort_by_key(Action::time);
+        actions
+    }
+
+    /// Sets the location for sunrise / sunset relative rules.
+    pub fn with_location(mut self, location: Option<Location>) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// Fails if there are sunrise / sunset relative rules but no location.
+    pub fn check_location(&self) -> anyhow::Result<()> {
+        if self.location.is_none() && self.rules.iter().any(Rule::needs_location) {
+            return Err(anyhow::anyhow!(
+                "sunrise / sunset entries need a location, use --location or set the location of the profile"
+            ));
+        }
+        Ok(())
     }
 
-    pub fn start(&mut self, user: &str, password: &str) -> anyhow::Result<()> {
+    pub fn start(
+        &mut self,
+        client: &mut FritzClient,
+        profile: &Profile,
+        options: &RunOptions,
+    ) -> anyhow::Result<()> {
+        self.check_location()?;
+
         let now = Local::now();
         println!(
             "[schedule] starting processing at time {}",
             now.format("%Y-%m-%d %H:%M:%S %Z")
         );
 
+        let mut ticker = Ticker::new(options.missed, now, Instant::now());
+        let mut next = None;
+        let mut enforced: Option<Instant> = None;
+        let mut retries = Retries::new(options.retry.clone());
         loop {
-            let now = Local::now();
-            let next = self.next_action(now);
+            if let Some(interval) = options.enforce {
+                if enforced.is_none_or(|enforced| enforced.elapsed() >= interval) {
+                    self.enforce(ticker.checked(), client, profile, &mut retries);
+                    enforced = Some(Instant::now());
+                }
+            }
+
+            let tick = ticker.tick(self, Local::now(), Instant::now());
+            if let Some(jump) = tick.jump {
+                println!(
+                    "[schedule] clock jumped by {}, e.g. after a suspend",
+                    timing::format_duration(jump)
+                );
+            }
+            for (action, skipped) in tick.skipped {
+                eprintln!("[schedule] skipping action {:?}, {}", action, skipped);
+            }
+            for action in tick.run {
+                Self::run(&action, client, profile, &mut retries);
+            }
+            for (_, result) in retries.run_due(client, Instant::now(), log) {
+                Self::report(result);
+            }
 
-            match next {
+            let now = ticker.checked();
+            match self.next_action(now) {
+                None if !retries.is_empty() => {}
                 None => {
                     println!(
                         "[schedule] no actions left stopping at time {}",
@@ -67,24 +271,174 @@ impl Schedule {
                     );
                     return Ok(());
                 }
-                Some(action) => {
-                    let time = action.time();
-                    let duration = time - now;
-
+                Some(action) if next.as_ref() != Some(&action) => {
                     println!(
                         "[schedule] scheduling next action {:?}, sleeping for {}",
-                        action, duration
+                        action,
+                        timing::format_duration(action.time() - now)
+                    );
+                    next = Some(action);
+                }
+                Some(_) => {}
+            }
+            std::thread::sleep(timing::TICK);
+        }
+    }
+
+    /// Switches the devices that are not in the state of their last action.
+    fn enforce(
+        &self,
+        at: DateTime<Local>,
+        client: &mut FritzClient,
+        profile: &Profile,
+        retries: &mut Retries,
+    ) {
+        match enforce::actions(self, at, client, profile, log) {
+            Ok(actions) => {
+                for action in actions {
+                    println!("[schedule] enforcing current state {:?}", action);
+                    Self::run(&action, client, profile, retries);
+                }
+            }
+            Err(err) => eprintln!("[schedule] cannot enforce current state: {}", err),
+        }
+    }
+
+    fn run(action: &Action, client: &mut FritzClient, profile: &Profile, retries: &mut Retries) {
+        if let Some(result) = retries.run(action, client, profile, log) {
+            Self::report(result);
+        }
+    }
+
+    fn report(result: anyhow::Result<Vec<SwitchResult>>) {
+        match result {
+            Ok(results) => {
+                let failed: Vec<_> = results
+                    .iter()
+                    .filter(|res| !res.outcome.is_ok())
+                    .map(|res| format!("{} ({})", res.id, res.outcome))
+                    .collect();
+                if !failed.is_empty() {
+                    eprintln!(
+                        "[schedule] error running action: failed to switch {}",
+                        failed.join(", ")
                     );
-                    std::thread::sleep(duration.to_std()?);
-                    if let Err(err) = self.run(action, user, password) {
-                        eprintln!("[schedule] error running action: {:?}", err);
-                    }
                 }
             }
+            Err(err) => eprintln!("[schedule] error running action: {:?}", err),
         }
     }
+}
+
+fn log(message: String) {
+    eprintln!("[schedule] {}", message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(time: &str) -> DateTime<Local> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap();
+        Local.from_local_datetime(&time).unwrap()
+    }
+
+    #[test]
+    fn merges_actions_and_rules() {
+        let schedule = Schedule::from_string(
+            "# Kaffee
+every weekday 06:30 Kaffee on
+every weekday 07:00 Kaffee off
 
-    fn run(&mut self, action: Action, user: &str, password: &str) -> anyhow::Result<()> {
-        switch::run(user, password, action.device_id(), action.clone().into())
+2025-10-11 09:00:00 Kaffee on
+"
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!((schedule.actions.len(), schedule.rules.len()), (1, 2));
+
+        let err = Schedule::from_string(
+            "every day 18:00 Licht on\nevery day 25:00 Licht off\n2025-10-11 Kaffee on\n"
+                .to_string(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.starts_with("line 2: "), "{}", err);
+        assert!(err.contains(", line 3: "), "{}", err);
+
+        let times = |mut at: DateTime<Local>| {
+            let schedule = &schedule;
+            std::iter::from_fn(move || {
+                let action = schedule.next_action(at)?;
+                at = action.time();
+                Some(action)
+            })
+            .take(4)
+            .map(|action| {
+                format!(
+                    "{} {}",
+                    action.time().format("%a %H:%M"),
+                    action.command == SwitchAction::On
+                )
+            })
+            .collect::<Vec<_>>()
+        };
+        // Friday
+        assert_eq!(
+            times(local("2025-10-10 06:45:00")),
+            [
+                "Fri 07:00 false",
+                "Sat 09:00 true",
+                "Mon 06:30 true",
+                "Mon 07:00 false"
+            ]
+        );
+        let between =
+            schedule.actions_between(local("2025-10-10 07:00:00"), local("2025-10-13 06:30:00"));
+        assert_eq!(
+            between.iter().map(Action::time).collect::<Vec<_>>(),
+            [local("2025-10-11 09:00:00"), local("2025-10-13 06:30:00")]
+        );
+        assert_eq!(
+            schedule.last_actions(local("2025-10-12 12:00:00")),
+            [Action {
+                time: local("2025-10-11 09:00:00"),
+                id: "Kaffee".to_string(),
+                command: SwitchAction::On,
+            }]
+        );
+
+        let schedule = Schedule::from_string(
+            "every day 18:00 Licht on
+every day 23:00 Licht off
+2025-10-11 09:00:00 Kaffee on
+2025-10-11 09:30:00 Kaffee off
+2025-10-11 20:00:00 Licht off
+2025-10-11 21:00:00 Licht brightness 50%
+"
+            .to_string(),
+        )
+        .unwrap();
+        let last = |at| {
+            schedule
+                .last_actions(local(at))
+                .iter()
+                .map(|action| format!("{} {:?}", action.device_id(), action.time()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            last("2025-10-11 09:15:00"),
+            [
+                format!("Licht {:?}", local("2025-10-10 23:00:00")),
+                format!("Kaffee {:?}", local("2025-10-11 09:00:00"))
+            ]
+        );
+        assert_eq!(
+            last("2025-10-11 22:00:00"),
+            [
+                format!("Kaffee {:?}", local("2025-10-11 09:30:00")),
+                format!("Licht {:?}", local("2025-10-11 20:00:00"))
+            ]
+        );
     }
 }
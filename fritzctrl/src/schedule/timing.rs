@@ -0,0 +1,308 @@
+//! When scheduled actions run: local times that don't exist or exist twice,
+//! and actions that were missed because the computer was suspended or the
+//! clock changed.
+
+use super::action::Action;
+use super::schedule::Schedule;
+use chrono::prelude::*;
+use chrono::Duration;
+use clap::ArgMatches;
+use std::time::Instant;
+
+/// How often the scheduler wakes up to compare the schedule with the clock.
+/// A single long sleep would oversleep a suspend or a clock change.
+pub const TICK: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Actions found later than this were missed, a regular tick finds them
+/// within a second or two.
+pub const MISSED_AFTER: Duration = Duration::minutes(1);
+
+/// The difference between the wall clock and the monotonic clock between
+/// two ticks above which the clock is considered to have jumped.
+const MAX_DRIFT: Duration = Duration::seconds(10);
+
+pub const MISSED_POLICIES: [&str; 3] = ["skip", "late", "catch-up"];
+
+/// What to do with actions that were missed, see [MISSED_AFTER].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissedPolicy {
+    /// Don't run them.
+    Skip,
+    /// Run them if they are not later than the grace period.
+    Late { grace: Duration },
+    /// Run the last missed action of each device, no matter how late.
+    CatchUp,
+}
+
+impl MissedPolicy {
+    /// The policy from `--missed` and `--grace`.
+    pub fn from_args(args: &ArgMatches) -> Self {
+        match args.get_one::<String>("missed").map(String::as_str) {
+            Some("skip") => MissedPolicy::Skip,
+            Some("catch-up") => MissedPolicy::CatchUp,
+            _ => MissedPolicy::Late {
+                grace: *args.get_one::<Duration>("grace").unwrap(),
+            },
+        }
+    }
+}
+
+/// Why an action was not run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skipped {
+    /// It was missed by the given time.
+    Missed(Duration),
+    /// A later missed action of the same device is run instead.
+    Superseded,
+}
+
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Skipped::Missed(late) => write!(f, "missed by {}", format_duration(*late)),
+            Skipped::Superseded => write!(f, "superseded by a later action of the device"),
+        }
+    }
+}
+
+/// The outcome of a [Ticker::tick].
+#[derive(Debug, Default)]
+pub struct Tick {
+    /// The actions to run now, in order.
+    pub run: Vec<Action>,
+    pub skipped: Vec<(Action, Skipped)>,
+    /// How far the wall clock moved in addition to the time that passed,
+    /// negative if it was set back. A suspend shows up as a jump forward.
+    pub jump: Option<Duration>,
+}
+
+/// Finds the actions that are due since the last tick.
+#[derive(Debug)]
+pub struct Ticker {
+    policy: MissedPolicy,
+    /// Actions until (and including) this time were handled.
+    checked: DateTime<Local>,
+    last: (DateTime<Local>, Instant),
+}
+
+impl Ticker {
+    pub fn new(policy: MissedPolicy, now: DateTime<Local>, instant: Instant) -> Self {
+        Self {
+            policy,
+            checked: now,
+            last: (now, instant),
+        }
+    }
+
+    /// Actions until (and including) this time were handled, later ones
+    /// are still to come.
+    pub fn checked(&self) -> DateTime<Local> {
+        self.checked
+    }
+
+    /// The actions of `schedule` that are due at wall clock time `now`, the
+    /// monotonic `instant` is used to notice clock jumps. When the clock is
+    /// set back actions are not repeated, they run again once the clock has
+    /// passed the last checked time.
+    pub fn tick(&mut self, schedule: &Schedule, now: DateTime<Local>, instant: Instant) -> Tick {
+        let (last_now, last_instant) = self.last;
+        let elapsed = Duration::from_std(instant.saturating_duration_since(last_instant))
+            .unwrap_or(Duration::MAX);
+        let drift = (now - last_now) - elapsed;
+        self.last = (now, instant);
+
+        let due = schedule.actions_between(self.checked, now);
+        self.checked = self.checked.max(now);
+
+        let mut tick = Tick {
+            jump: (drift.abs() > MAX_DRIFT).then_some(drift),
+            ..Default::default()
+        };
+        for (i, action) in due.iter().enumerate() {
+            let late = now - action.time();
+            if late <= MISSED_AFTER {
+                tick.run.push(action.clone());
+                continue;
+            }
+            let skipped = match self.policy {
+                MissedPolicy::Skip => Some(Skipped::Missed(late)),
+                MissedPolicy::Late { grace } => (late > grace).then_some(Skipped::Missed(late)),
+                MissedPolicy::CatchUp => due[i + 1..]
+                    .iter()
+                    .any(|later| later.device_id() == action.device_id())
+                    .then_some(Skipped::Superseded),
+            };
+            match skipped {
+                Some(skipped) => tick.skipped.push((action.clone(), skipped)),
+                None => tick.run.push(action.clone()),
+            }
+        }
+        tick
+    }
+}
+
+/// The local time of `time` in `tz`. A time that exists twice when the clock
+/// is set back is the first (earlier) one, a time that is skipped when the
+/// clock is set forward is moved forward by the gap, e.g. 02:30 becomes 03:30.
+pub fn resolve<Tz: TimeZone>(tz: &Tz, time: NaiveDateTime) -> Option<DateTime<Tz>> {
+    match tz.from_local_datetime(&time) {
+        chrono::LocalResult::Single(time) => Some(time),
+        chrono::LocalResult::Ambiguous(earliest, _) => Some(earliest),
+        chrono::LocalResult::None => {
+            // the offset before the gap, transitions are far more than a day apart
+            let before = tz
+                .from_local_datetime(&(time - Duration::days(1)))
+                .earliest()?
+                .offset()
+                .fix();
+            Some(tz.from_utc_datetime(&(time - before)))
+        }
+    }
+}
+
+/// [resolve] in the local time zone.
+pub fn local(time: NaiveDateTime) -> Option<DateTime<Local>> {
+    resolve(&Local, time)
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().abs();
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    match seconds {
+        0..60 => format!("{}{}s", sign, seconds),
+        60..3600 => format!("{}{}min {}s", sign, seconds / 60, seconds % 60),
+        _ => format!("{}{}h {}min", sign, seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America::New_York, Europe::Berlin};
+
+    fn naive(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn ambiguous_and_skipped_times() {
+        let resolved = |tz, time| {
+            resolve(&tz, naive(time))
+                .unwrap()
+                .format("%Y-%m-%d %H:%M:%S %z")
+                .to_string()
+        };
+        assert_eq!(
+            resolved(Berlin, "2025-03-30 02:30:00"),
+            "2025-03-30 03:30:00 +0200"
+        );
+        assert_eq!(
+            resolved(Berlin, "2025-10-26 02:30:00"),
+            "2025-10-26 02:30:00 +0200"
+        );
+        assert_eq!(
+            resolved(Berlin, "2025-07-01 02:30:00"),
+            "2025-07-01 02:30:00 +0200"
+        );
+        assert_eq!(
+            resolved(New_York, "2025-03-09 02:15:00"),
+            "2025-03-09 03:15:00 -0400"
+        );
+        assert_eq!(
+            resolved(New_York, "2025-11-02 01:15:00"),
+            "2025-11-02 01:15:00 -0400"
+        );
+    }
+
+    fn schedule() -> Schedule {
+        Schedule::from_string(
+            "2025-06-02 07:00:00 Kaffee on
+2025-06-02 07:30:00 Kaffee off
+2025-06-02 07:45:00 Licht on
+2025-06-02 08:00:00 Kaffee on
+"
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    fn local_time(time: &str) -> DateTime<Local> {
+        local(naive(time)).unwrap()
+    }
+
+    fn ids(actions: &[Action]) -> Vec<String> {
+        actions
+            .iter()
+            .map(|action| format!("{} {}", action.time().format("%H:%M"), action.device_id()))
+            .collect()
+    }
+
+    #[test]
+    fn missed_actions() {
+        let schedule = schedule();
+        let start = Instant::now();
+        // suspended from 06:59:59 until 08:00:30
+        let woke = |policy| {
+            let mut ticker = Ticker::new(policy, local_time("2025-06-02 06:59:59"), start);
+            let tick = ticker.tick(&schedule, local_time("2025-06-02 08:00:30"), start + TICK);
+            assert_eq!(ticker.checked(), local_time("2025-06-02 08:00:30"));
+            assert!(tick.jump.unwrap() > Duration::hours(1));
+            tick
+        };
+
+        let tick = woke(MissedPolicy::Skip);
+        assert_eq!(ids(&tick.run), ["08:00 Kaffee"]);
+        assert_eq!(tick.skipped.len(), 3);
+        assert_eq!(tick.skipped[0].1, Skipped::Missed(Duration::seconds(3630)));
+
+        let tick = woke(MissedPolicy::Late {
+            grace: Duration::minutes(20),
+        });
+        assert_eq!(ids(&tick.run), ["07:45 Licht", "08:00 Kaffee"]);
+        assert_eq!(tick.skipped.len(), 2);
+
+        let tick = woke(MissedPolicy::CatchUp);
+        assert_eq!(ids(&tick.run), ["07:45 Licht", "08:00 Kaffee"]);
+        assert_eq!(
+            tick.skipped
+                .iter()
+                .map(|(_, skipped)| *skipped)
+                .collect::<Vec<_>>(),
+            [Skipped::Superseded, Skipped::Superseded]
+        );
+    }
+
+    #[test]
+    fn clock_set_back() {
+        let schedule = schedule();
+        let start = Instant::now();
+        let mut ticker = Ticker::new(MissedPolicy::Skip, local_time("2025-06-02 07:29:59"), start);
+        let tick = ticker.tick(&schedule, local_time("2025-06-02 07:30:00"), start + TICK);
+        assert_eq!(
+            (ids(&tick.run), tick.jump),
+            (vec!["07:30 Kaffee".to_string()], None)
+        );
+
+        // set back by an hour, 07:30 is not repeated
+        let tick = ticker.tick(
+            &schedule,
+            local_time("2025-06-02 06:30:01"),
+            start + TICK * 2,
+        );
+        assert!(tick.run.is_empty());
+        assert_eq!(tick.jump, Some(Duration::seconds(-3600)));
+        let tick = ticker.tick(
+            &schedule,
+            local_time("2025-06-02 07:30:01"),
+            start + TICK * 3,
+        );
+        assert!(tick.run.is_empty());
+        assert_eq!(ticker.checked(), local_time("2025-06-02 07:30:01"));
+        let tick = ticker.tick(
+            &schedule,
+            local_time("2025-06-02 07:45:00"),
+            start + TICK * 4,
+        );
+        assert_eq!(ids(&tick.run), ["07:45 Licht"]);
+    }
+}
@@ -0,0 +1,439 @@
+//! Resolves the device arguments of the subcommands and the device column of
+//! schedule lines to devices.
+//!
+//! A selector can be
+//! - an ain (`"11630 0123723"`) or a device name, exact or ignoring case,
+//! - a glob on the device name (`"Laufband*"`), ignoring case,
+//! - a regular expression on the device name (`re:^Lauf` or `/^Lauf/`),
+//!   ignoring case unless it starts with `(?-i)`,
+//! - a glob on the product name (`product:FRITZ!DECT 2*`),
+//! - the name or ain of a device group (`group:Wohnzimmer`),
+//! - a literal device name (`name:Lampe [alt]`), e.g. for names that contain
+//!   glob characters.
+
+use clap::ArgMatches;
+use fritzapi::{AVMDevice, Ain, DeviceGroup, FritzClient};
+use regex::Regex;
+
+/// The selectors passed with `--device` or, for backwards compatibility, as
+/// positional argument.
+pub fn from_args(args: &ArgMatches) -> Vec<DeviceSelector> {
+    ["device", "device-positional"]
+        .into_iter()
+        .filter(|id| args.try_contains_id(id).unwrap_or(false))
+        .flat_map(|id| args.get_many::<DeviceSelector>(id).unwrap_or_default())
+        .cloned()
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub enum DeviceSelector {
+    /// Ain or device name.
+    Plain(String),
+    /// Device name, matched literally (but ignoring case).
+    Name(String),
+    Glob(glob::Pattern),
+    Regex(Regex),
+    Product(glob::Pattern),
+    Group(String),
+}
+
+impl PartialEq for DeviceSelector {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl std::str::FromStr for DeviceSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(anyhow::anyhow!("empty device selector"));
+        }
+
+        if let Some(group) = input.strip_prefix("group:") {
+            return Ok(DeviceSelector::Group(group.trim().to_string()));
+        }
+        if let Some(name) = input.strip_prefix("name:") {
+            return Ok(DeviceSelector::Name(name.trim().to_string()));
+        }
+        if let Some(product) = input.strip_prefix("product:") {
+            return Ok(DeviceSelector::Product(glob::Pattern::new(product.trim())?));
+        }
+        if let Some(re) = input.strip_prefix("re:") {
+            return Ok(DeviceSelector::Regex(regex(re)?));
+        }
+        if input.len() > 2 && input.starts_with('/') && input.ends_with('/') {
+            return Ok(DeviceSelector::Regex(regex(&input[1..input.len() - 1])?));
+        }
+        if input.contains(['*', '?', '[']) {
+            return Ok(DeviceSelector::Glob(glob::Pattern::new(input)?));
+        }
+        Ok(DeviceSelector::Plain(input.to_string()))
+    }
+}
+
+impl std::fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelector::Plain(input) => write!(f, "{}", input),
+            DeviceSelector::Name(name) => write!(f, "name:{}", name),
+            DeviceSelector::Glob(pattern) => write!(f, "{}", pattern),
+            DeviceSelector::Regex(re) => write!(f, "re:{}", re),
+            DeviceSelector::Product(pattern) => write!(f, "product:{}", pattern),
+            DeviceSelector::Group(group) => write!(f, "group:{}", group),
+        }
+    }
+}
+
+/// A regular expression ignoring case like the other selectors, `(?-i)`
+/// makes it case-sensitive.
+fn regex(pattern: &str) -> Result<Regex, regex::Error> {
+    regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+}
+
+const IGNORE_CASE: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+impl DeviceSelector {
+    /// Whether the template or trigger with `identifier` and `name` matches.
+    /// Products and groups only select devices.
+    pub fn matches_named(&self, identifier: &Ain, name: &str) -> bool {
+        match self {
+            DeviceSelector::Plain(input) => *identifier == input.as_str() || name == input,
+            DeviceSelector::Name(input) => same_name(name, input),
+            DeviceSelector::Glob(pattern) => pattern.matches_with(name, IGNORE_CASE),
+            DeviceSelector::Regex(re) => re.is_match(name),
+            DeviceSelector::Product(_) | DeviceSelector::Group(_) => false,
+        }
+    }
+}
+
+/// The devices (and, if needed, groups) of a fritz box that selectors are
+/// resolved against.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    pub devices: Vec<AVMDevice>,
+    pub groups: Vec<DeviceGroup>,
+}
+
+impl Inventory {
+    /// Lists the devices. Groups are only requested if one of the `selectors`
+    /// refers to a group.
+    pub fn fetch(client: &mut FritzClient, selectors: &[DeviceSelector]) -> anyhow::Result<Self> {
+        let (devices, groups) = if selectors
+            .iter()
+            .any(|sel| matches!(sel, DeviceSelector::Group(_)))
+        {
+            client.list_devices_and_groups()?
+        } else {
+            (client.list_devices()?, Vec::new())
+        };
+        Ok(Self { devices, groups })
+    }
+
+    /// All devices matching `selector`. Fails if nothing matches or if a
+    /// plain name is ambiguous.
+    pub fn resolve(&self, selector: &DeviceSelector) -> anyhow::Result<Vec<&AVMDevice>> {
+        let found: Vec<_> = match selector {
+            DeviceSelector::Plain(input) => return self.resolve_plain(selector, input),
+            DeviceSelector::Name(name) => self
+                .devices
+                .iter()
+                .filter(|dev| same_name(dev.name(), name))
+                .collect(),
+            DeviceSelector::Glob(pattern) => self
+                .devices
+                .iter()
+                .filter(|dev| pattern.matches_with(dev.name(), IGNORE_CASE))
+                .collect(),
+            DeviceSelector::Regex(re) => self
+                .devices
+                .iter()
+                .filter(|dev| re.is_match(dev.name()))
+                .collect(),
+            DeviceSelector::Product(pattern) => self
+                .devices
+                .iter()
+                .filter(|dev| pattern.matches_with(dev.productname(), IGNORE_CASE))
+                .collect(),
+            DeviceSelector::Group(name) => {
+                let group = self
+                    .groups
+                    .iter()
+                    .find(|group| same_name(&group.name, name) || group.identifier == name.as_str())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Cannot find group {:?}, known groups: {}",
+                            name,
+                            self.groups
+                                .iter()
+                                .map(|group| format!("{:?}", group.name))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })?;
+                self.devices
+                    .iter()
+                    .filter(|dev| group.contains(dev))
+                    .collect()
+            }
+        };
+
+        if found.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No device matches {:?}",
+                selector.to_string()
+            ));
+        }
+        Ok(found)
+    }
+
+    fn resolve_plain(
+        &self,
+        selector: &DeviceSelector,
+        input: &str,
+    ) -> anyhow::Result<Vec<&AVMDevice>> {
+        if let Ok(ain) = input.parse::<Ain>() {
+            if let Some(dev) = self.devices.iter().find(|dev| *dev.id() == ain) {
+                return Ok(vec![dev]);
+            }
+        }
+
+        let exact: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|dev| dev.name() == input)
+            .collect();
+        let found = if exact.is_empty() {
+            self.devices
+                .iter()
+                .filter(|dev| same_name(dev.name(), input))
+                .collect()
+        } else {
+            exact
+        };
+
+        match found.len() {
+            0 => Err(anyhow::anyhow!(
+                "No device with ain or name {:?}",
+                selector.to_string()
+            )),
+            1 => Ok(found),
+            _ => Err(ambiguous(selector, &found)),
+        }
+    }
+
+    /// Like [Self::resolve] but fails if more than one device matches.
+    pub fn resolve_one(&self, selector: &DeviceSelector) -> anyhow::Result<&AVMDevice> {
+        let found = self.resolve(selector)?;
+        match found.as_slice() {
+            [dev] => Ok(dev),
+            _ => Err(ambiguous(selector, &found)),
+        }
+    }
+
+    /// Union of the devices matching `selectors`, in order and without
+    /// duplicates.
+    pub fn resolve_all(&self, selectors: &[DeviceSelector]) -> anyhow::Result<Vec<&AVMDevice>> {
+        let mut result: Vec<&AVMDevice> = Vec::new();
+        for selector in selectors {
+            for dev in self.resolve(selector)? {
+                if !result.iter().any(|known| known.id() == dev.id()) {
+                    result.push(dev);
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Names of devices and groups are compared ignoring case, including umlauts.
+fn same_name(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+fn ambiguous(selector: &DeviceSelector, candidates: &[&AVMDevice]) -> anyhow::Error {
+    anyhow::anyhow!(
+        "{:?} is ambiguous, it matches:\n{}",
+        selector.to_string(),
+        candidates
+            .iter()
+            .map(|dev| format!("  {} {:?}", dev.id(), dev.name()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fritzapi::devices::{Device, GroupInfo};
+
+    fn device(ain: &str, id: &str, name: &str, productname: &str) -> AVMDevice {
+        AVMDevice::Other(Device {
+            identifier: ain.parse().unwrap(),
+            id: id.to_string(),
+            functionbitmask: "35712".to_string(),
+            fwversion: "04.16".to_string(),
+            manufacturer: "AVM".to_string(),
+            productname: productname.to_string(),
+            present: true,
+            txbusy: false,
+            name: name.to_string(),
+            battery: None,
+            batterylow: None,
+            switch: None,
+            simpleonoff: None,
+            powermeter: None,
+            temperature: None,
+            hkr: None,
+        })
+    }
+
+    fn inventory() -> Inventory {
+        let group = DeviceGroup {
+            synchronized: false,
+            identifier: "grp424E2B-3D5C11C33".parse().unwrap(),
+            id: "900".to_string(),
+            functionbitmask: "37504".to_string(),
+            fwversion: "1.0".to_string(),
+            manufacturer: "AVM".to_string(),
+            present: true,
+            txbusy: false,
+            name: "Arbeitszimmer".to_string(),
+            switch: None,
+            simpleonoff: None,
+            powermeter: None,
+            groupinfo: Some(GroupInfo {
+                masterdeviceid: "0".to_string(),
+                members: "16,18,20".to_string(),
+            }),
+        };
+        let kitchen = DeviceGroup {
+            identifier: "grp424E2B-3D5C11C34".parse().unwrap(),
+            id: "901".to_string(),
+            name: "Küche".to_string(),
+            groupinfo: Some(GroupInfo {
+                masterdeviceid: "0".to_string(),
+                members: "22".to_string(),
+            }),
+            ..group.clone()
+        };
+        Inventory {
+            devices: vec![
+                device("11630 0069103", "16", "Laufband Seite", "FRITZ!DECT 200"),
+                device("11657 0272633", "17", "draußen", "FRITZ!DECT 210"),
+                device("11630 0128064", "18", "Schreibtisch", "FRITZ!DECT 200"),
+                device("11630 0123723", "20", "Laufband hinten", "FRITZ!DECT 200"),
+                device("09995 0335100", "19", "Heizung", "FRITZ!DECT 301"),
+                device("11630 0266726", "22", "lampe", "FRITZ!DECT 200"),
+                device("11630 0266727", "23", "Lampe", "FRITZ!DECT 200"),
+            ],
+            groups: vec![group, kitchen],
+        }
+    }
+
+    fn names(devices: Vec<&AVMDevice>) -> Vec<&str> {
+        devices.into_iter().map(|dev| dev.name()).collect()
+    }
+
+    fn resolve(input: &str) -> anyhow::Result<Vec<String>> {
+        let inventory = inventory();
+        let found = inventory.resolve(&input.parse()?)?;
+        Ok(names(found).into_iter().map(String::from).collect())
+    }
+
+    #[test]
+    fn parse_selectors() {
+        assert!(matches!(
+            "11630 0123723".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Plain(_)
+        ));
+        assert!(matches!(
+            "Laufband*".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Glob(_)
+        ));
+        assert!(matches!(
+            "/^Lauf/".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Regex(_)
+        ));
+        assert!(matches!(
+            "name:Lampe [alt]".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Name(name) if name == "Lampe [alt]"
+        ));
+        assert!("".parse::<DeviceSelector>().is_err());
+        assert!("re:(".parse::<DeviceSelector>().is_err());
+    }
+
+    #[test]
+    fn resolve_ains_and_names() {
+        assert_eq!(resolve("116300123723").unwrap(), vec!["Laufband hinten"]);
+        assert_eq!(resolve("11630 0123723").unwrap(), vec!["Laufband hinten"]);
+        assert_eq!(resolve("Schreibtisch").unwrap(), vec!["Schreibtisch"]);
+        assert_eq!(resolve("schreibtisch").unwrap(), vec!["Schreibtisch"]);
+        // an exact match wins over case-insensitive ones
+        assert_eq!(resolve("Lampe").unwrap(), vec!["Lampe"]);
+        assert!(resolve("unknown").is_err());
+    }
+
+    #[test]
+    fn resolve_ambiguous() {
+        let err = resolve("LAMPE").unwrap_err().to_string();
+        assert!(err.contains("ambiguous"), "{}", err);
+        assert!(err.contains("11630 0266726"), "{}", err);
+        assert!(err.contains("11630 0266727"), "{}", err);
+
+        let inventory = inventory();
+        let selector = "laufband*".parse().unwrap();
+        assert_eq!(inventory.resolve(&selector).unwrap().len(), 2);
+        assert!(inventory.resolve_one(&selector).is_err());
+    }
+
+    #[test]
+    fn resolve_patterns() {
+        assert_eq!(
+            resolve("laufband*").unwrap(),
+            vec!["Laufband Seite", "Laufband hinten"]
+        );
+        assert_eq!(resolve("re:hinten$").unwrap(), vec!["Laufband hinten"]);
+        assert_eq!(resolve("/^LAUFBAND H/").unwrap(), vec!["Laufband hinten"]);
+        assert!(resolve("re:(?-i)^laufband").is_err());
+        assert_eq!(resolve("product:*301").unwrap(), vec!["Heizung"]);
+        assert_eq!(
+            resolve("group:arbeitszimmer").unwrap(),
+            vec!["Laufband Seite", "Schreibtisch", "Laufband hinten"]
+        );
+        assert_eq!(resolve("group:grp424E2B-3D5C11C33").unwrap().len(), 3);
+        assert_eq!(resolve("group:KÜCHE").unwrap(), vec!["lampe"]);
+        assert!(resolve("group:Keller").is_err());
+        assert!(resolve("Keller*").is_err());
+    }
+
+    #[test]
+    fn resolve_all_without_duplicates() {
+        let inventory = inventory();
+        let selectors = ["Schreibtisch", "group:Arbeitszimmer", "Heizung"]
+            .iter()
+            .map(|sel| sel.parse().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names(inventory.resolve_all(&selectors).unwrap()),
+            vec![
+                "Schreibtisch",
+                "Laufband Seite",
+                "Laufband hinten",
+                "Heizung"
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Caches the session id of the fritz box between fritzctrl invocations so
+//! that not every call has to log in again. The sids are stored in
+//! `$XDG_RUNTIME_DIR/fritzctrl/` (the cache dir on systems without a runtime
+//! dir), one file per host and user, readable only by the user.
+
+use fritzapi::FritzClient;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSession {
+    host: String,
+    user: String,
+    sid: String,
+}
+
+/// Stores sids in `dir`.
+#[derive(Debug, Clone)]
+pub struct SessionCache {
+    dir: PathBuf,
+}
+
+impl SessionCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SessionCache { dir: dir.into() }
+    }
+
+    /// The cache in the runtime dir, `None` if there is no place for it.
+    pub fn default_location() -> Option<Self> {
+        dirs::runtime_dir()
+            .or_else(dirs::cache_dir)
+            .map(|dir| Self::new(dir.join("fritzctrl")))
+    }
+
+    pub fn load(&self, host: &str, user: &str) -> Option<String> {
+        let content = std::fs::read_to_string(self.path(host, user)).ok()?;
+        let session: CachedSession = serde_json::from_str(&content).ok()?;
+        (session.host == host && session.user == user).then_some(session.sid)
+    }
+
+    pub fn store(&self, host: &str, user: &str, sid: &str) -> anyhow::Result<()> {
+        create_private_dir(&self.dir)?;
+        let session = CachedSession {
+            host: host.to_string(),
+            user: user.to_string(),
+            sid: sid.to_string(),
+        };
+        // write to a temporary file first so that concurrent invocations never
+        // read a partial file
+        let path = self.path(host, user);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut file = create_private_file(&tmp)?;
+        serde_json::to_writer(&mut file, &session)?;
+        file.flush()?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn path(&self, host: &str, user: &str) -> PathBuf {
+        let key: String = format!("{}-{}", host, user)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(format!("session-{}.json", key))
+    }
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    std::fs::File::create(path)
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// A [FritzClient] that starts with the cached sid and writes the sid back to
+/// the cache when it is dropped, so a new login is stored as well.
+pub struct Session {
+    client: FritzClient,
+    cache: Option<SessionCache>,
+    initial_sid: Option<String>,
+}
+
+impl Session {
+    pub fn new(client: FritzClient, cache: Option<SessionCache>) -> Self {
+        let initial_sid = cache
+            .as_ref()
+            .and_then(|cache| cache.load(client.host(), client.user()));
+        let client = match &initial_sid {
+            Some(sid) => client.with_sid(sid),
+            None => client,
+        };
+        Session {
+            client,
+            cache,
+            initial_sid,
+        }
+    }
+
+    /// Writes the sid to the cache if it changed. Happens on drop as well,
+    /// long running commands call it so that the sid survives being killed.
+    pub fn persist(&mut self) {
+        let (Some(cache), Some(sid)) = (&self.cache, self.client.sid()) else {
+            return;
+        };
+        if self.initial_sid.as_deref() == Some(sid) {
+            return;
+        }
+        if let Err(err) = cache.store(self.client.host(), self.client.user(), sid) {
+            debug!("cannot store session: {}", err);
+        }
+        self.initial_sid = Some(sid.to_string());
+    }
+}
+
+impl Deref for Session {
+    type Target = FritzClient;
+
+    fn deref(&self) -> &FritzClient {
+        &self.client
+    }
+}
+
+impl DerefMut for Session {
+    fn deref_mut(&mut self) -> &mut FritzClient {
+        &mut self.client
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.persist();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_sessions() {
+        let dir = std::env::temp_dir().join(format!("fritzctrl-sessions-{}", std::process::id()));
+        let cache = SessionCache::new(&dir);
+        assert_eq!(cache.load("http://fritz.box", "admin"), None);
+
+        cache
+            .store("http://fritz.box", "admin", "0123456789abcdef")
+            .unwrap();
+        cache
+            .store("http://fritz.box", "guest", "fedcba9876543210")
+            .unwrap();
+        assert_eq!(
+            cache.load("http://fritz.box", "admin").as_deref(),
+            Some("0123456789abcdef")
+        );
+        assert_eq!(
+            cache.load("http://fritz.box", "guest").as_deref(),
+            Some("fedcba9876543210")
+        );
+        assert_eq!(cache.load("http://192.168.178.1", "admin"), None);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(cache.path("http://fritz.box", "admin"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let session = Session::new(FritzClient::new("admin", "password"), Some(cache.clone()));
+        assert_eq!(session.sid(), Some("0123456789abcdef"));
+
+        std::fs::write(cache.path("http://fritz.box", "admin"), "garbage").unwrap();
+        assert_eq!(cache.load("http://fritz.box", "admin"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
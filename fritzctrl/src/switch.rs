@@ -1,29 +1,236 @@
-use crate::schedule::Action;
+use crate::config::Profile;
+use crate::output::{self, OutputFormat};
+use crate::selector::{DeviceSelector, Inventory};
 use clap::ArgMatches;
+use fritzapi::{AVMDevice, Ain, BlindTarget, Color, FritzClient, FritzError, ThermostatTarget};
+use prettytable::{format, Cell, Row};
+use serde::Serialize;
+use std::io::Write;
 
-#[derive(Debug, Clone, Copy)]
+/// What is done to a device, or to a template or trigger of the fritz box.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SwitchAction {
     On,
     Off,
     Toggle,
+    /// Sets the target temperature of a thermostat.
+    Temperature(ThermostatTarget),
+    /// Sets the brightness of a light in percent.
+    Brightness(u8),
+    Color(Color),
+    /// Sets the white color temperature of a light in Kelvin.
+    ColorTemperature(u16),
+    Blind(BlindTarget),
+    /// Applies a template.
+    ApplyTemplate,
+    /// Enables (`true`) or disables a trigger.
+    Trigger(bool),
 }
 
-impl From<Action> for SwitchAction {
-    fn from(action: Action) -> Self {
-        match action {
-            Action::TurnOn { .. } => SwitchAction::On,
-            Action::TurnOff { .. } => SwitchAction::Off,
+/// Regular expression for the actions in schedule lines, see
+/// [SwitchAction::from_str].
+pub const ACTION_PATTERN: &str = r"on|off|toggle|temperature\s+\S+|brightness\s+\S+|color\s+\S+|color-?temperature\s+\S+|blind\s+\S+|apply|enable|disable";
+
+impl SwitchAction {
+    /// Whether the device is on or off afterwards, `None` for actions that
+    /// don't switch or whose outcome depends on the current state.
+    pub fn state(&self) -> Option<bool> {
+        match self {
+            SwitchAction::On => Some(true),
+            SwitchAction::Off => Some(false),
+            _ => None,
+        }
+    }
+
+    /// What the action changes, actions of different kinds (e.g. the
+    /// temperature and the brightness) don't contradict each other.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SwitchAction::On | SwitchAction::Off | SwitchAction::Toggle => "switch",
+            SwitchAction::Temperature(_) => "temperature",
+            SwitchAction::Brightness(_) => "brightness",
+            SwitchAction::Color(_) | SwitchAction::ColorTemperature(_) => "color",
+            SwitchAction::Blind(_) => "blind",
+            SwitchAction::ApplyTemplate => "template",
+            SwitchAction::Trigger(_) => "trigger",
+        }
+    }
+}
+
+impl std::str::FromStr for SwitchAction {
+    type Err = anyhow::Error;
+
+    /// `on`, `off`, `toggle`, `temperature 21.5` (or `off` / `on`),
+    /// `brightness 40%`, `color HUE,SATURATION`, `color-temperature 2700K`,
+    /// `blind open|close|stop`, `apply` (a template), `enable` or `disable`
+    /// (a trigger).
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim().to_lowercase();
+        let (name, value) = match input.split_once(char::is_whitespace) {
+            Some((name, value)) => (name, Some(value.trim())),
+            None => (input.as_str(), None),
+        };
+        let action = match (name, value) {
+            ("on", None) => SwitchAction::On,
+            ("off", None) => SwitchAction::Off,
+            ("toggle", None) => SwitchAction::Toggle,
+            ("apply", None) => SwitchAction::ApplyTemplate,
+            ("enable", None) => SwitchAction::Trigger(true),
+            ("disable", None) => SwitchAction::Trigger(false),
+            ("temperature", Some(value)) => SwitchAction::Temperature(value.parse()?),
+            ("brightness", Some(value)) => {
+                let percent = value
+                    .trim_end_matches('%')
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|percent| *percent <= 100)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("invalid brightness {:?}, expected 0% to 100%", value)
+                    })?;
+                SwitchAction::Brightness(percent)
+            }
+            ("color", Some(value)) => SwitchAction::Color(value.parse()?),
+            ("color-temperature" | "colortemperature", Some(value)) => {
+                let kelvin = value
+                    .trim_end_matches('k')
+                    .parse::<u16>()
+                    .map_err(|_| anyhow::anyhow!("invalid color temperature {:?}", value))?;
+                fritzapi::devices::light::validate_color_temperature(kelvin)?;
+                SwitchAction::ColorTemperature(kelvin)
+            }
+            ("blind", Some(value)) => SwitchAction::Blind(value.parse()?),
+            _ => return Err(anyhow::anyhow!("unknown action {:?}", input)),
+        };
+        Ok(action)
+    }
+}
+
+impl std::fmt::Display for SwitchAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwitchAction::On => write!(f, "on"),
+            SwitchAction::Off => write!(f, "off"),
+            SwitchAction::Toggle => write!(f, "toggle"),
+            SwitchAction::Temperature(ThermostatTarget::Celsius(celsius)) => {
+                write!(f, "temperature {}", celsius)
+            }
+            SwitchAction::Temperature(target) => write!(f, "temperature {}", target),
+            SwitchAction::Brightness(percent) => write!(f, "brightness {}%", percent),
+            SwitchAction::Color(color) => write!(f, "color {}", color),
+            SwitchAction::ColorTemperature(kelvin) => write!(f, "color-temperature {}K", kelvin),
+            SwitchAction::Blind(target) => write!(f, "blind {}", target),
+            SwitchAction::ApplyTemplate => write!(f, "apply"),
+            SwitchAction::Trigger(true) => write!(f, "enable"),
+            SwitchAction::Trigger(false) => write!(f, "disable"),
+        }
+    }
+}
+
+/// What happened when switching a single device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum SwitchOutcome {
+    /// The device is now on (`true`) or off.
+    Ok {
+        on: bool,
+    },
+    /// The command was accepted, for actions that don't switch.
+    #[serde(rename = "ok")]
+    Done,
+    NotPresent,
+    Locked,
+    Error {
+        error: String,
+    },
+}
+
+impl SwitchOutcome {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, SwitchOutcome::Ok { .. } | SwitchOutcome::Done)
+    }
+}
+
+impl From<fritzapi::Result<bool>> for SwitchOutcome {
+    fn from(result: fritzapi::Result<bool>) -> Self {
+        match result {
+            Ok(on) => SwitchOutcome::Ok { on },
+            Err(FritzError::DeviceNotPresent(_)) => SwitchOutcome::NotPresent,
+            Err(FritzError::DeviceLocked(_)) => SwitchOutcome::Locked,
+            Err(err) => SwitchOutcome::Error {
+                error: err.to_string(),
+            },
+        }
+    }
+}
+
+impl From<fritzapi::Result<()>> for SwitchOutcome {
+    fn from(result: fritzapi::Result<()>) -> Self {
+        match result {
+            Ok(()) => SwitchOutcome::Done,
+            Err(err) => Err::<bool, _>(err).into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SwitchOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwitchOutcome::Ok { on: true } => write!(f, "ok (on)"),
+            SwitchOutcome::Ok { on: false } => write!(f, "ok (off)"),
+            SwitchOutcome::Done => write!(f, "ok"),
+            SwitchOutcome::NotPresent => write!(f, "not present"),
+            SwitchOutcome::Locked => write!(f, "locked"),
+            SwitchOutcome::Error { error } => write!(f, "error: {}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwitchResult {
+    pub id: Ain,
+    pub name: String,
+    #[serde(flatten)]
+    pub outcome: SwitchOutcome,
+}
+
+/// Flat representation of a [SwitchResult] for csv output.
+#[derive(Debug, Serialize)]
+struct SwitchRecord<'a> {
+    id: String,
+    name: &'a str,
+    result: &'static str,
+    on: Option<bool>,
+    error: Option<&'a str>,
+}
+
+impl<'a> From<&'a SwitchResult> for SwitchRecord<'a> {
+    fn from(result: &'a SwitchResult) -> Self {
+        let (result_name, on, error) = match &result.outcome {
+            SwitchOutcome::Ok { on } => ("ok", Some(*on), None),
+            SwitchOutcome::Done => ("ok", None, None),
+            SwitchOutcome::NotPresent => ("not_present", None, None),
+            SwitchOutcome::Locked => ("locked", None, None),
+            SwitchOutcome::Error { error } => ("error", None, Some(error.as_str())),
+        };
+        SwitchRecord {
+            id: result.id.to_string(),
+            name: &result.name,
+            result: result_name,
+            on,
+            error,
         }
     }
 }
 
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
 pub fn switch(args: &ArgMatches) -> anyhow::Result<()> {
-    let user = args.get_one::<String>("user").unwrap();
-    let password = args.get_one::<String>("password").unwrap();
-    let ain = args.get_one::<String>("device").unwrap();
+    let profile = Profile::from_args(args)?;
+    let selectors = profile.expand_aliases(crate::selector::from_args(args));
     let toggle = args.get_flag("toggle");
     let on = args.get_flag("on");
     let off = args.get_flag("off");
+    let format = *args.get_one::<OutputFormat>("output").unwrap();
 
     let action = if on {
         SwitchAction::On
@@ -35,26 +242,270 @@ pub fn switch(args: &ArgMatches) -> anyhow::Result<()> {
         return Err(anyhow::anyhow!("invalid switch options"));
     };
 
-    run(user, password, ain, action)
+    let mut client = profile.client(args)?;
+    let results = run(&mut client, &selectors, action)?;
+    print_results(format, &results)?;
+
+    let failed = results.iter().filter(|res| !res.outcome.is_ok()).count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} devices could not be switched",
+            failed,
+            results.len()
+        ));
+    }
+
+    Ok(())
 }
 
-#[tracing::instrument(level = "trace", skip(password))]
-pub fn run(user: &str, password: &str, ain: &str, action: SwitchAction) -> anyhow::Result<()> {
-    let mut client = fritzapi::FritzClient::new(user, password);
-    let devices: Vec<_> = client.list_devices()?;
+/// How many devices are switched at the same time.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
 
-    let device = match devices.into_iter().find(|dev| dev.id() == ain) {
-        None => {
-            return Err(anyhow::anyhow!("Cannot find device with ain {:?}", ain));
+/// Switches all devices matching `selectors`. The requests are sent
+/// concurrently (up to [MAX_CONCURRENT_REQUESTS] at a time) using a single
+/// session. Only fails if the devices cannot be
+/// listed or a selector does not match, the outcome for each device is
+/// reported in the results. Templates and triggers are selected by their name
+/// or identifier instead of devices.
+#[tracing::instrument(level = "trace", skip(client))]
+pub fn run(
+    client: &mut FritzClient,
+    selectors: &[DeviceSelector],
+    action: SwitchAction,
+) -> anyhow::Result<Vec<SwitchResult>> {
+    match action {
+        SwitchAction::ApplyTemplate => {
+            let templates = client.list_templates()?;
+            let templates = resolve_named(&templates, selectors, "template", |template| {
+                (&template.identifier, &template.name)
+            })?;
+            return Ok(templates
+                .into_iter()
+                .map(|template| SwitchResult {
+                    id: template.identifier.clone(),
+                    name: template.name.clone(),
+                    outcome: client.apply_template(&template.identifier).into(),
+                })
+                .collect());
         }
-        Some(device) => device,
-    };
+        SwitchAction::Trigger(active) => {
+            let triggers = client.list_triggers()?;
+            let triggers = resolve_named(&triggers, selectors, "trigger", |trigger| {
+                (&trigger.identifier, &trigger.name)
+            })?;
+            return Ok(triggers
+                .into_iter()
+                .map(|trigger| SwitchResult {
+                    id: trigger.identifier.clone(),
+                    name: trigger.name.clone(),
+                    outcome: client
+                        .set_trigger_active(&trigger.identifier, active)
+                        .into(),
+                })
+                .collect());
+        }
+        _ => {}
+    }
 
-    match action {
-        SwitchAction::On => client.turn_on(device.id())?,
-        SwitchAction::Off => client.turn_off(device.id())?,
-        SwitchAction::Toggle => client.toggle(device.id())?,
+    let inventory = Inventory::fetch(client, selectors)?;
+    let devices = inventory.resolve_all(selectors)?;
+
+    // the client is logged in now, its clones share the session. If it
+    // expired nevertheless a clone logs in again, its sid is kept.
+    let mut results = Vec::with_capacity(devices.len());
+    let mut sid = None;
+    for chunk in devices.chunks(MAX_CONCURRENT_REQUESTS) {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|device| {
+                    let mut client = client.clone();
+                    scope.spawn(move || {
+                        let result = switch_device(&mut client, device, action);
+                        (result, client.sid().map(str::to_string))
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let (result, client_sid) = handle.join().expect("switch thread panicked");
+                results.push(result);
+                if client_sid.as_deref() != client.sid() {
+                    sid = client_sid;
+                }
+            }
+        });
+        if let Some(sid) = sid.take() {
+            *client = client.clone().with_sid(sid);
+        }
+    }
+
+    Ok(results)
+}
+
+fn switch_device(
+    client: &mut FritzClient,
+    device: &AVMDevice,
+    action: SwitchAction,
+) -> SwitchResult {
+    let mut device = device.clone();
+    let ain = device.id().clone();
+    let outcome = match action {
+        SwitchAction::On => device.turn_on(client).into(),
+        SwitchAction::Off => device.turn_off(client).into(),
+        SwitchAction::Toggle => device.toggle(client).into(),
+        _ if !device.is_present() => SwitchOutcome::NotPresent,
+        SwitchAction::Temperature(target) => client.set_thermostat_target(&ain, target).into(),
+        SwitchAction::Brightness(percent) => client.set_level_percentage(&ain, percent).into(),
+        SwitchAction::Color(color) => client.set_color(&ain, color).into(),
+        SwitchAction::ColorTemperature(kelvin) => client.set_color_temperature(&ain, kelvin).into(),
+        SwitchAction::Blind(target) => client.set_blind(&ain, target).into(),
+        SwitchAction::ApplyTemplate | SwitchAction::Trigger(_) => {
+            unreachable!("templates and triggers are not devices")
+        }
     };
+    SwitchResult {
+        id: ain,
+        name: device.name().to_string(),
+        outcome,
+    }
+}
 
+/// The templates or triggers matching `selectors`, `key` returns their
+/// identifier and name.
+pub fn resolve_named<'a, T>(
+    items: &'a [T],
+    selectors: &[DeviceSelector],
+    kind: &str,
+    key: impl Fn(&T) -> (&Ain, &String),
+) -> anyhow::Result<Vec<&'a T>> {
+    let mut found: Vec<&T> = Vec::new();
+    for selector in selectors {
+        let matching: Vec<_> = items
+            .iter()
+            .filter(|item| {
+                let (identifier, name) = key(item);
+                selector.matches_named(identifier, name)
+            })
+            .collect();
+        if matching.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No {} matches {:?}, known {}s: {}",
+                kind,
+                selector.to_string(),
+                kind,
+                items
+                    .iter()
+                    .map(|item| format!("{:?}", key(item).1))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        for item in matching {
+            if !found.iter().any(|other| std::ptr::eq(*other, item)) {
+                found.push(item);
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn print_results(format: OutputFormat, results: &[SwitchResult]) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+    match format {
+        OutputFormat::Table => {
+            let mut table = crate::list::create_table();
+            table.set_titles(Row::new(vec![
+                Cell::new_align("id", format::Alignment::CENTER),
+                Cell::new_align("name", format::Alignment::CENTER),
+                Cell::new_align("result", format::Alignment::CENTER),
+            ]));
+            for result in results {
+                let id = result.id.to_string();
+                table.add_row(Row::new(vec![
+                    Cell::new(&id),
+                    Cell::new(&result.name),
+                    Cell::new(&result.outcome.to_string()),
+                ]));
+            }
+            table.print(&mut out)?;
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, results)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => output::print_ndjson(&mut out, results)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, results)?,
+        OutputFormat::Csv => output::print_csv(&mut out, results.iter().map(SwitchRecord::from))?,
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes() {
+        let ain: Ain = "11630 0123723".parse().unwrap();
+        assert_eq!(
+            SwitchOutcome::from(Ok(true)),
+            SwitchOutcome::Ok { on: true }
+        );
+        assert_eq!(SwitchOutcome::from(Ok(())), SwitchOutcome::Done);
+        assert_eq!(
+            SwitchOutcome::from(Err::<bool, _>(FritzError::DeviceLocked(ain.clone()))),
+            SwitchOutcome::Locked
+        );
+        assert_eq!(
+            SwitchOutcome::from(Err::<(), _>(FritzError::DeviceNotPresent(ain.clone()))),
+            SwitchOutcome::NotPresent
+        );
+
+        let result = SwitchResult {
+            id: ain,
+            name: "Laufband".to_string(),
+            outcome: SwitchOutcome::Locked,
+        };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["id"], "11630 0123723");
+        assert_eq!(json["result"], "locked");
+
+        let mut out = Vec::new();
+        output::print_csv(&mut out, [SwitchRecord::from(&result)]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,name,result,on,error\n11630 0123723,Laufband,locked,,\n"
+        );
+    }
+
+    #[test]
+    fn actions() {
+        for input in [
+            "on",
+            "toggle",
+            "temperature 21.5",
+            "temperature off",
+            "brightness 40%",
+            "color 35,214",
+            "color-temperature 2700K",
+            "blind stop",
+            "apply",
+            "disable",
+        ] {
+            let action: SwitchAction = input.parse().unwrap();
+            assert_eq!(action.to_string(), input);
+        }
+        assert_eq!(
+            "Brightness 40".parse::<SwitchAction>().unwrap(),
+            SwitchAction::Brightness(40)
+        );
+        assert_eq!(SwitchAction::Off.state(), Some(false));
+        assert_eq!(SwitchAction::Toggle.state(), None);
+        assert_eq!(SwitchAction::Toggle.kind(), SwitchAction::On.kind());
+        assert_ne!(SwitchAction::Brightness(40).kind(), SwitchAction::On.kind());
+        assert!("on 5".parse::<SwitchAction>().is_err());
+        assert!("color 35".parse::<SwitchAction>().is_err());
+        assert!("color-temperature 9000K".parse::<SwitchAction>().is_err());
+        assert!("blind half".parse::<SwitchAction>().is_err());
+    }
+}
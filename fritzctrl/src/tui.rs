@@ -0,0 +1,785 @@
+//! `fritzctrl tui`: full-screen interface to browse devices, groups and
+//! templates, look at the recent power / temperature history and switch
+//! devices with the keyboard.
+
+use crate::config::Profile;
+use crate::output::StatSample;
+use clap::ArgMatches;
+use fritzapi::{
+    AVMDevice, Ain, DeviceGroup, DeviceStatsKind, FritzClient, Template, ThermostatTarget,
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Sparkline, Table, TableState, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// Step for changing the thermostat target with `+` / `-`.
+const THERMOSTAT_STEP: f32 = 0.5;
+
+pub fn tui(args: &ArgMatches) -> anyhow::Result<()> {
+    let profile = Profile::from_args(args)?;
+    let interval = args
+        .get_one::<chrono::Duration>("interval")
+        .unwrap()
+        .to_std()
+        .map_err(|_| anyhow::anyhow!("--interval must be positive"))?;
+
+    let mut client = profile.client(args)?;
+    let mut app = App::default();
+    app.refresh(&mut client);
+    client.persist();
+
+    let mut terminal = ratatui::try_init()?;
+    let result = run(&mut terminal, &mut app, &mut client, interval);
+    ratatui::try_restore()?;
+    result
+}
+
+fn run(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    client: &mut FritzClient,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    let mut last_refresh = Instant::now();
+    loop {
+        app.load_history(client);
+        terminal.draw(|frame| app.render(frame))?;
+
+        let timeout = interval.saturating_sub(last_refresh.elapsed());
+        let action = if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => app.handle_key(key),
+                _ => None,
+            }
+        } else {
+            // periodic refresh, the statistics change rarely and are kept
+            app.refresh(client);
+            last_refresh = Instant::now();
+            continue;
+        };
+
+        match action {
+            None => {}
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Refresh) => {
+                app.history.clear();
+                app.refresh(client);
+                last_refresh = Instant::now();
+            }
+            Some(action) => {
+                app.perform(client, action);
+                app.refresh(client);
+                last_refresh = Instant::now();
+            }
+        }
+    }
+}
+
+/// What a key press asks for.
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Toggle(Ain),
+    SetThermostat(Ain, ThermostatTarget),
+    ApplyTemplate(Ain),
+    Refresh,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Pane {
+    #[default]
+    Devices,
+    Groups,
+    Templates,
+}
+
+impl Pane {
+    const ALL: [Pane; 3] = [Pane::Devices, Pane::Groups, Pane::Templates];
+
+    fn title(self) -> &'static str {
+        match self {
+            Pane::Devices => "Devices",
+            Pane::Groups => "Groups",
+            Pane::Templates => "Templates",
+        }
+    }
+
+    fn index(self) -> usize {
+        Pane::ALL.iter().position(|pane| *pane == self).unwrap()
+    }
+}
+
+/// Power and temperature readings of a device, the oldest first.
+#[derive(Debug, Default)]
+struct History {
+    power: Vec<f32>,
+    temperature: Vec<f32>,
+}
+
+#[derive(Default)]
+struct App {
+    devices: Vec<AVMDevice>,
+    groups: Vec<DeviceGroup>,
+    templates: Vec<Template>,
+    pane: Pane,
+    selected: [TableState; 3],
+    history: HashMap<Ain, History>,
+    status: String,
+}
+
+impl App {
+    fn refresh(&mut self, client: &mut FritzClient) {
+        let result = (|| -> anyhow::Result<()> {
+            (self.devices, self.groups) = client.list_devices_and_groups()?;
+            // older boxes don't know templates, that's no reason to fail
+            self.templates = client.list_templates().unwrap_or_default();
+            Ok(())
+        })();
+        for pane in Pane::ALL {
+            self.clamp_selection(pane);
+        }
+        if let Err(err) = result {
+            self.status = format!("cannot fetch devices: {}", err);
+        }
+    }
+
+    /// Fetches the history of the selected device unless it is known already.
+    fn load_history(&mut self, client: &mut FritzClient) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+        if !device.is_present() || self.history.contains_key(device.id()) {
+            return;
+        }
+        let kinds = HashSet::from([DeviceStatsKind::Power, DeviceStatsKind::Temperature]);
+        let history = match crate::list::device_stat_samples(client, &device, &Some(kinds), None) {
+            Ok(samples) => History::from_samples(&samples),
+            Err(err) => {
+                self.status = format!("cannot fetch statistics of {}: {}", device.name(), err);
+                History::default()
+            }
+        };
+        self.history.insert(device.id().clone(), history);
+    }
+
+    fn perform(&mut self, client: &mut FritzClient, action: Action) {
+        let result = match &action {
+            Action::Toggle(ain) => client
+                .toggle(ain)
+                .map(|on| format!("{} is {}", self.name_of(ain), if on { "on" } else { "off" })),
+            Action::SetThermostat(ain, target) => client
+                .set_thermostat_target(ain, *target)
+                .map(|_| format!("{} set to {}", self.name_of(ain), target)),
+            Action::ApplyTemplate(ain) => client
+                .apply_template(ain)
+                .map(|_| format!("applied template {}", self.name_of(ain))),
+            Action::Refresh | Action::Quit => return,
+        };
+        self.status = match result {
+            Ok(message) => message,
+            Err(err) => format!("{}: {}", self.name_of(action_ain(&action)), err),
+        };
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(Action::Quit)
+            }
+            KeyCode::Char('r') => return Some(Action::Refresh),
+            KeyCode::Tab | KeyCode::Right => {
+                self.pane = Pane::ALL[(self.pane.index() + 1) % Pane::ALL.len()];
+            }
+            KeyCode::BackTab | KeyCode::Left => {
+                self.pane = Pane::ALL[(self.pane.index() + Pane::ALL.len() - 1) % Pane::ALL.len()];
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Char(' ') | KeyCode::Char('t') | KeyCode::Enter => {
+                return match self.pane {
+                    Pane::Devices => self
+                        .selected_device()
+                        .filter(|device| device.switch_mode().is_some())
+                        .map(|device| Action::Toggle(device.id().clone())),
+                    Pane::Groups => self
+                        .selected_group()
+                        .map(|group| Action::Toggle(group.identifier.clone())),
+                    Pane::Templates => self
+                        .selected_template()
+                        .map(|template| Action::ApplyTemplate(template.identifier.clone())),
+                };
+            }
+            KeyCode::Char('a') if self.pane == Pane::Templates => {
+                return self
+                    .selected_template()
+                    .map(|template| Action::ApplyTemplate(template.identifier.clone()));
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                return self.adjust_thermostat(THERMOSTAT_STEP)
+            }
+            KeyCode::Char('-') => return self.adjust_thermostat(-THERMOSTAT_STEP),
+            _ => {}
+        }
+        None
+    }
+
+    fn adjust_thermostat(&self, delta: f32) -> Option<Action> {
+        if self.pane != Pane::Devices {
+            return None;
+        }
+        let device = self.selected_device()?;
+        let target = device.thermostat()?.target()?;
+        Some(Action::SetThermostat(
+            device.id().clone(),
+            target.adjust(delta),
+        ))
+    }
+
+    // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+    fn len(&self, pane: Pane) -> usize {
+        match pane {
+            Pane::Devices => self.devices.len(),
+            Pane::Groups => self.groups.len(),
+            Pane::Templates => self.templates.len(),
+        }
+    }
+
+    fn selection(&self, pane: Pane) -> Option<usize> {
+        self.selected[pane.index()].selected()
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.len(self.pane);
+        if len == 0 {
+            return;
+        }
+        let current = self.selection(self.pane).unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, len as isize - 1) as usize;
+        self.selected[self.pane.index()].select(Some(next));
+    }
+
+    fn clamp_selection(&mut self, pane: Pane) {
+        let len = self.len(pane);
+        let state = &mut self.selected[pane.index()];
+        match (len, state.selected()) {
+            (0, _) => state.select(None),
+            (_, None) => state.select(Some(0)),
+            (len, Some(i)) if i >= len => state.select(Some(len - 1)),
+            _ => {}
+        }
+    }
+
+    fn selected_device(&self) -> Option<&AVMDevice> {
+        self.devices.get(self.selection(Pane::Devices)?)
+    }
+
+    fn selected_group(&self) -> Option<&DeviceGroup> {
+        self.groups.get(self.selection(Pane::Groups)?)
+    }
+
+    fn selected_template(&self) -> Option<&Template> {
+        self.templates.get(self.selection(Pane::Templates)?)
+    }
+
+    fn name_of(&self, ain: &Ain) -> String {
+        self.devices
+            .iter()
+            .find(|device| device.id() == ain)
+            .map(|device| device.name())
+            .or_else(|| {
+                self.groups
+                    .iter()
+                    .find(|group| &group.identifier == ain)
+                    .map(|group| group.name.as_str())
+            })
+            .or_else(|| {
+                self.templates
+                    .iter()
+                    .find(|template| &template.identifier == ain)
+                    .map(|template| template.name.as_str())
+            })
+            .map(str::to_string)
+            .unwrap_or_else(|| ain.to_string())
+    }
+
+    // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [tabs, main, status] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [list, details] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(main);
+
+        frame.render_widget(
+            Tabs::new(Pane::ALL.map(Pane::title))
+                .select(self.pane.index())
+                .highlight_style(Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED)),
+            tabs,
+        );
+
+        let table = match self.pane {
+            Pane::Devices => self.device_table(),
+            Pane::Groups => self.group_table(),
+            Pane::Templates => self.template_table(),
+        };
+        let table = table
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(self.pane.title()),
+            )
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, list, &mut self.selected[self.pane.index()]);
+
+        match self.pane {
+            Pane::Devices => self.render_device_details(frame, details),
+            Pane::Groups => self.render_group_details(frame, details),
+            Pane::Templates => self.render_template_details(frame, details),
+        }
+
+        let help = match self.pane {
+            Pane::Devices => {
+                "q quit  tab next pane  ↑↓ select  space toggle  +/- thermostat  r refresh"
+            }
+            Pane::Groups => "q quit  tab next pane  ↑↓ select  space toggle  r refresh",
+            Pane::Templates => "q quit  tab next pane  ↑↓ select  enter apply  r refresh",
+        };
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(self.status.as_str()),
+                Line::styled(help, Style::new().fg(Color::DarkGray)),
+            ]),
+            status,
+        );
+    }
+
+    fn device_table(&self) -> Table<'static> {
+        let rows = self.devices.iter().map(|device| {
+            Row::new([
+                device.name().to_string(),
+                device_state(device),
+                device_watts(device),
+                device_celsius(device),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(12),
+                Constraint::Length(8),
+                Constraint::Length(7),
+            ],
+        )
+        .header(
+            Row::new(["name", "state", "W", "°C"]).style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+    }
+
+    fn group_table(&self) -> Table<'static> {
+        let rows = self.groups.iter().map(|group| {
+            let state = match &group.switch {
+                _ if !group.present => "not present",
+                Some(switch) if switch.state => "on",
+                Some(_) => "off",
+                None => "",
+            };
+            Row::new([
+                group.name.clone(),
+                state.to_string(),
+                group.member_ids().len().to_string(),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(12),
+                Constraint::Length(7),
+            ],
+        )
+        .header(
+            Row::new(["name", "state", "members"]).style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+    }
+
+    fn template_table(&self) -> Table<'static> {
+        let rows = self
+            .templates
+            .iter()
+            .map(|template| Row::new([template.name.clone(), template.identifier.to_string()]));
+        Table::new(rows, [Constraint::Fill(1), Constraint::Fill(1)])
+            .header(Row::new(["name", "id"]).style(Style::new().add_modifier(Modifier::BOLD)))
+    }
+
+    fn render_device_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title("Details");
+        let Some(device) = self.selected_device() else {
+            frame.render_widget(block, area);
+            return;
+        };
+        let [info, power, temperature] = Layout::vertical([
+            Constraint::Min(6),
+            Constraint::Length(6),
+            Constraint::Length(6),
+        ])
+        .areas(area);
+
+        let mut lines = vec![
+            Line::from(device.name().to_string()).style(Style::new().add_modifier(Modifier::BOLD)),
+            Line::from(format!("{}  {}", device.id(), device.productname())),
+            Line::from(format!("state: {}", device_state(device))),
+        ];
+        if let AVMDevice::FritzDect2XX(dev) = device {
+            lines.push(Line::from(format!(
+                "{:.2} W  {:.1} V  {:.1} °C  {} Wh",
+                dev.milliwatts as f64 / 1000.0,
+                dev.millivolts as f64 / 1000.0,
+                dev.celsius,
+                dev.energy_in_watt_h
+            )));
+        }
+        if let Some(hkr) = device.thermostat() {
+            let show = |target: Option<ThermostatTarget>| {
+                target.map(|target| target.to_string()).unwrap_or_default()
+            };
+            lines.push(Line::from(format!(
+                "thermostat: {} → {}",
+                hkr.current_celsius()
+                    .map(|celsius| format!("{:.1} °C", celsius))
+                    .unwrap_or_default(),
+                show(hkr.target())
+            )));
+            lines.push(Line::from(format!(
+                "comfort {}, reduced {}",
+                show(hkr.comfort()),
+                show(hkr.reduced())
+            )));
+        }
+        if device.is_locked() || device.is_device_locked() {
+            lines.push(Line::from("locked").style(Style::new().fg(Color::Red)));
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), info);
+
+        let history = self.history.get(device.id());
+        render_history(
+            frame,
+            power,
+            "power",
+            "W",
+            history.map(|history| history.power.as_slice()),
+            Color::Yellow,
+        );
+        render_history(
+            frame,
+            temperature,
+            "temperature",
+            "°C",
+            history.map(|history| history.temperature.as_slice()),
+            Color::Cyan,
+        );
+    }
+
+    fn render_group_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title("Members");
+        let Some(group) = self.selected_group() else {
+            frame.render_widget(block, area);
+            return;
+        };
+        let lines: Vec<Line> = self
+            .devices
+            .iter()
+            .filter(|device| group.contains(device))
+            .map(|device| Line::from(format!("{:<12} {}", device_state(device), device.name())))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn render_template_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title("Template");
+        let lines = match self.selected_template() {
+            Some(template) => vec![
+                Line::from(template.name.clone()).style(Style::new().add_modifier(Modifier::BOLD)),
+                Line::from(format!("{}  (id {})", template.identifier, template.id)),
+            ],
+            None => Vec::new(),
+        };
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+fn action_ain(action: &Action) -> &Ain {
+    match action {
+        Action::Toggle(ain) | Action::SetThermostat(ain, _) | Action::ApplyTemplate(ain) => ain,
+        Action::Refresh | Action::Quit => unreachable!("no device involved"),
+    }
+}
+
+impl History {
+    fn from_samples(samples: &[StatSample]) -> Self {
+        // the samples are the newest first
+        let series = |kind| {
+            let mut values: Vec<f32> = samples
+                .iter()
+                .filter(|sample| sample.kind == kind)
+                .map(|sample| sample.value)
+                .collect();
+            values.reverse();
+            values
+        };
+        History {
+            power: series(DeviceStatsKind::Power),
+            temperature: series(DeviceStatsKind::Temperature),
+        }
+    }
+}
+
+/// Draws `values` as sparkline. Bars start at the minimum so that small
+/// changes of e.g. the temperature remain visible.
+fn render_history(
+    frame: &mut Frame,
+    area: Rect,
+    name: &str,
+    unit: &str,
+    values: Option<&[f32]>,
+    color: Color,
+) {
+    let values = values.unwrap_or_default();
+    let title = match (values.last(), min_max(values)) {
+        (Some(last), Some((min, max))) => format!(
+            "{} {:.1} {} (min {:.1}, max {:.1})",
+            name, last, unit, min, max
+        ),
+        _ => format!("{} (no data)", name),
+    };
+    // show the most recent values that fit
+    let width = area.width.saturating_sub(2) as usize;
+    let values = &values[values.len().saturating_sub(width)..];
+    let min = min_max(values).map(|(min, _)| min).unwrap_or_default();
+    let data: Vec<u64> = values
+        .iter()
+        .map(|value| ((value - min) * 10.0).round() as u64 + 1)
+        .collect();
+    frame.render_widget(
+        Sparkline::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .data(&data)
+            .style(Style::new().fg(color)),
+        area,
+    );
+}
+
+fn min_max(values: &[f32]) -> Option<(f32, f32)> {
+    values.iter().fold(None, |acc, value| match acc {
+        None => Some((*value, *value)),
+        Some((min, max)) => Some((min.min(*value), max.max(*value))),
+    })
+}
+
+fn device_state(device: &AVMDevice) -> String {
+    match device.thermostat() {
+        Some(hkr) if device.is_present() => hkr
+            .target()
+            .map(|target| target.to_string())
+            .unwrap_or_default(),
+        _ if device.is_tx_busy() => format!("{} (pending)", device.state()),
+        _ => device.state().to_string(),
+    }
+}
+
+fn device_watts(device: &AVMDevice) -> String {
+    match device {
+        AVMDevice::FritzDect2XX(dev) if dev.present => {
+            format!("{:.2}", dev.milliwatts as f64 / 1000.0)
+        }
+        _ => String::new(),
+    }
+}
+
+fn device_celsius(device: &AVMDevice) -> String {
+    match device {
+        AVMDevice::FritzDect2XX(dev) if dev.present => format!("{:.1}", dev.celsius),
+        AVMDevice::Other(_) => device
+            .thermostat()
+            .and_then(|hkr| hkr.current_celsius())
+            .map(|celsius| format!("{:.1}", celsius))
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fritzapi::devices::{Device, Hkr};
+    use fritzapi::{FritzDect2XX, SwitchMode};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn app() -> App {
+        let mut app = App {
+            devices: vec![
+                AVMDevice::FritzDect2XX(FritzDect2XX {
+                    identifier: "11630 0123723".parse().unwrap(),
+                    id: "20".to_string(),
+                    name: "Laufband".to_string(),
+                    productname: "FRITZ!DECT 200".to_string(),
+                    present: true,
+                    txbusy: false,
+                    on: true,
+                    lock: false,
+                    devicelock: false,
+                    mode: SwitchMode::Manual,
+                    millivolts: 234877,
+                    milliwatts: 4570,
+                    energy_in_watt_h: 43714,
+                    celsius: 19.5,
+                }),
+                AVMDevice::Other(Device {
+                    identifier: "09995 0335100".parse().unwrap(),
+                    id: "19".to_string(),
+                    functionbitmask: "320".to_string(),
+                    fwversion: "05.08".to_string(),
+                    manufacturer: "AVM".to_string(),
+                    productname: "FRITZ!DECT 301".to_string(),
+                    present: true,
+                    txbusy: false,
+                    name: "Heizung".to_string(),
+                    battery: Some(80),
+                    batterylow: Some(false),
+                    switch: None,
+                    simpleonoff: None,
+                    powermeter: None,
+                    temperature: None,
+                    hkr: Some(Hkr {
+                        tist: 41,
+                        tsoll: 40,
+                        absenk: 34,
+                        komfort: 42,
+                        lock: false,
+                        devicelock: false,
+                    }),
+                }),
+            ],
+            templates: vec![Template {
+                identifier: "tmp6F0093-391363146".parse().unwrap(),
+                id: "60000".to_string(),
+                functionbitmask: "6784".to_string(),
+                name: "Heizung aus".to_string(),
+            }],
+            ..App::default()
+        };
+        for pane in Pane::ALL {
+            app.clamp_selection(pane);
+        }
+        app.history.insert(
+            "11630 0123723".parse().unwrap(),
+            History {
+                power: vec![4.5, 4.6, 120.0, 4.5],
+                temperature: vec![19.0, 19.5],
+            },
+        );
+        app
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn keys() {
+        let mut app = app();
+        let plug: Ain = "11630 0123723".parse().unwrap();
+        let thermostat: Ain = "09995 0335100".parse().unwrap();
+
+        assert_eq!(
+            press(&mut app, KeyCode::Char(' ')),
+            Some(Action::Toggle(plug))
+        );
+        // the plug has no thermostat
+        assert_eq!(press(&mut app, KeyCode::Char('+')), None);
+
+        assert_eq!(press(&mut app, KeyCode::Down), None);
+        assert_eq!(press(&mut app, KeyCode::Down), None);
+        assert_eq!(app.selection(Pane::Devices), Some(1));
+        assert_eq!(
+            press(&mut app, KeyCode::Char('+')),
+            Some(Action::SetThermostat(
+                thermostat.clone(),
+                ThermostatTarget::Celsius(20.5)
+            ))
+        );
+        assert_eq!(
+            press(&mut app, KeyCode::Char('-')),
+            Some(Action::SetThermostat(
+                thermostat,
+                ThermostatTarget::Celsius(19.5)
+            ))
+        );
+        // a thermostat can't be toggled
+        assert_eq!(press(&mut app, KeyCode::Char(' ')), None);
+
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.pane, Pane::Groups);
+        assert_eq!(press(&mut app, KeyCode::Enter), None);
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Some(Action::ApplyTemplate(
+                "tmp6F0093-391363146".parse().unwrap()
+            ))
+        );
+        press(&mut app, KeyCode::BackTab);
+        assert_eq!(app.pane, Pane::Groups);
+
+        assert_eq!(press(&mut app, KeyCode::Char('r')), Some(Action::Refresh));
+        assert_eq!(press(&mut app, KeyCode::Char('q')), Some(Action::Quit));
+    }
+
+    #[test]
+    fn render() {
+        let mut app = app();
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Laufband"));
+        assert!(screen.contains("Heizung"));
+        assert!(screen.contains("20.0 °C"));
+        assert!(screen.contains("power 4.5 W (min 4.5, max 120.0)"));
+        assert!(screen.contains("temperature 19.5 °C"));
+    }
+
+    #[test]
+    fn history() {
+        let device = &app().devices[0];
+        let now = chrono::Local::now();
+        let stats = fritzapi::DeviceStats {
+            kind: DeviceStatsKind::Power,
+            values: vec![fritzapi::stats::DeviceStatValues {
+                values: vec![3.0, 2.0, 1.0],
+                grid: 600,
+            }],
+        };
+        let history = History::from_samples(&StatSample::from_stats(device, &stats, None, now));
+        assert_eq!(history.power, vec![1.0, 2.0, 3.0]);
+        assert!(history.temperature.is_empty());
+    }
+}
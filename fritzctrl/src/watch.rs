@@ -0,0 +1,330 @@
+//! `fritzctrl watch`: polls the devices and shows their state and readings
+//! as a continuously refreshed table, or prints the changes for other tools.
+
+use crate::config::Profile;
+use crate::output::{self, DeviceRecord, OutputFormat};
+use crate::selector::{DeviceSelector, Inventory};
+use chrono::prelude::*;
+use clap::ArgMatches;
+use fritzapi::{AVMDevice, Ain, FritzClient};
+use prettytable::{format, Cell, Row};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+
+/// How often the high refresh rate is triggered, it lasts for 1-2 minutes.
+const HIGH_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+pub fn watch(args: &ArgMatches) -> anyhow::Result<()> {
+    let profile = Profile::from_args(args)?;
+    let selectors = profile.expand_aliases(crate::selector::from_args(args));
+    let interval = args
+        .get_one::<chrono::Duration>("interval")
+        .unwrap()
+        .to_std()
+        .map_err(|_| anyhow::anyhow!("--interval must be positive"))?;
+    let format = *args.get_one::<OutputFormat>("output").unwrap();
+    let live = format == OutputFormat::Table
+        && !args.get_flag("once-on-change")
+        && std::io::stdout().is_terminal();
+
+    let mut client = profile.client(args)?;
+
+    if args.get_flag("high-refresh") {
+        let mut client = FritzClient::clone(&client);
+        std::thread::spawn(move || loop {
+            if let Err(err) = client.trigger_high_refresh_rate() {
+                eprintln!("[watch] cannot trigger high refresh rate: {}", err);
+            }
+            std::thread::sleep(HIGH_REFRESH_INTERVAL);
+        });
+    }
+
+    let mut printer = Printer::new(format, live);
+    let mut previous = HashMap::new();
+    loop {
+        let result = fetch(&mut client, &selectors);
+        client.persist();
+        match result {
+            Ok(devices) => {
+                let now = Local::now();
+                let changed = changed_devices(&previous, &devices);
+                match printer.print(now, interval, &devices, &previous, &changed) {
+                    // the reading end of the pipe is gone, e.g. `| head`
+                    Err(err) if is_broken_pipe(&err) => return Ok(()),
+                    result => result?,
+                }
+                previous = devices
+                    .iter()
+                    .map(|device| (device.id().clone(), Readings::from(device)))
+                    .collect();
+            }
+            Err(err) => eprintln!("[watch] {}", err),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn fetch(client: &mut FritzClient, selectors: &[DeviceSelector]) -> anyhow::Result<Vec<AVMDevice>> {
+    if selectors.is_empty() {
+        return Ok(client.list_devices()?);
+    }
+    let inventory = Inventory::fetch(client, selectors)?;
+    Ok(inventory
+        .resolve_all(selectors)?
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+/// The values shown for a device, formatted so that changes below the
+/// displayed precision don't count.
+#[derive(Debug, Clone, PartialEq)]
+struct Readings {
+    state: String,
+    watts: String,
+    volts: String,
+    celsius: String,
+    energy: String,
+}
+
+impl From<&AVMDevice> for Readings {
+    fn from(device: &AVMDevice) -> Self {
+        let state = if device.is_tx_busy() {
+            format!("{} (pending)", device.state())
+        } else {
+            device.state().to_string()
+        };
+        match device {
+            AVMDevice::FritzDect2XX(dev) if dev.present => Readings {
+                state,
+                watts: format!("{:.2}", dev.milliwatts as f64 / 1000.0),
+                volts: format!("{:.1}", dev.millivolts as f64 / 1000.0),
+                celsius: format!("{:.1}", dev.celsius),
+                energy: dev.energy_in_watt_h.to_string(),
+            },
+            _ => Readings {
+                state,
+                watts: String::new(),
+                volts: String::new(),
+                celsius: String::new(),
+                energy: String::new(),
+            },
+        }
+    }
+}
+
+impl Readings {
+    fn columns(&self) -> [&str; 5] {
+        [
+            &self.state,
+            &self.watts,
+            &self.volts,
+            &self.celsius,
+            &self.energy,
+        ]
+    }
+}
+
+/// Devices that are new or whose readings differ from `previous`.
+fn changed_devices<'a>(
+    previous: &HashMap<Ain, Readings>,
+    devices: &'a [AVMDevice],
+) -> Vec<&'a AVMDevice> {
+    devices
+        .iter()
+        .filter(|device| previous.get(device.id()) != Some(&Readings::from(*device)))
+        .collect()
+}
+
+// -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
+/// A changed device, as printed for the machine readable formats.
+#[derive(Debug, Serialize)]
+struct WatchRecord<'a> {
+    time: DateTime<Local>,
+    #[serde(flatten)]
+    device: DeviceRecord<'a>,
+}
+
+struct Printer {
+    format: OutputFormat,
+    live: bool,
+    csv: Option<csv::Writer<std::io::Stdout>>,
+}
+
+impl Printer {
+    fn new(format: OutputFormat, live: bool) -> Self {
+        Printer {
+            format,
+            live,
+            csv: None,
+        }
+    }
+
+    fn print(
+        &mut self,
+        time: DateTime<Local>,
+        interval: std::time::Duration,
+        devices: &[AVMDevice],
+        previous: &HashMap<Ain, Readings>,
+        changed: &[&AVMDevice],
+    ) -> anyhow::Result<()> {
+        if self.live {
+            // clear the screen and move the cursor to the top
+            print!("\x1b[2J\x1b[H");
+            println!(
+                "{}, refreshing every {}s",
+                time.format("%Y-%m-%d %H:%M:%S"),
+                interval.as_secs_f32()
+            );
+            print_table(devices.iter(), previous)?;
+            return Ok(());
+        }
+
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let mut out = std::io::stdout().lock();
+        let records = changed.iter().map(|device| WatchRecord {
+            time,
+            device: DeviceRecord::from(*device),
+        });
+        match self.format {
+            OutputFormat::Table => {
+                writeln!(out, "{}", time.format("%Y-%m-%d %H:%M:%S"))?;
+                drop(out);
+                print_table(changed.iter().copied(), previous)?;
+            }
+            OutputFormat::Json | OutputFormat::Ndjson => {
+                for record in records {
+                    serde_json::to_writer(&mut out, &record)?;
+                    writeln!(out)?;
+                }
+            }
+            OutputFormat::Yaml => {
+                writeln!(out, "---")?;
+                serde_yaml::to_writer(&mut out, &records.collect::<Vec<_>>())?;
+            }
+            OutputFormat::Csv => {
+                drop(out);
+                let writer = match &mut self.csv {
+                    Some(writer) => writer,
+                    None => {
+                        let mut writer = csv::WriterBuilder::new()
+                            .has_headers(false)
+                            .from_writer(std::io::stdout());
+                        writer.write_record(
+                            std::iter::once("time").chain(output::DEVICE_RECORD_HEADER),
+                        )?;
+                        self.csv.insert(writer)
+                    }
+                };
+                for record in records {
+                    writer.serialize((record.time.to_rfc3339(), record.device))?;
+                }
+                writer.flush()?;
+            }
+        }
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+}
+
+/// Prints `devices`, values that changed since `previous` are highlighted.
+fn print_table<'a>(
+    devices: impl Iterator<Item = &'a AVMDevice>,
+    previous: &HashMap<Ain, Readings>,
+) -> std::io::Result<()> {
+    let mut table = crate::list::create_table();
+    table.set_titles(Row::new(
+        ["id", "name", "state", "W", "V", "°C", "Wh"]
+            .into_iter()
+            .map(|title| Cell::new_align(title, format::Alignment::CENTER))
+            .collect(),
+    ));
+
+    for device in devices {
+        let id = device.id().to_string();
+        let readings = Readings::from(device);
+        let before = previous.get(device.id());
+        let mut cells = vec![Cell::new(&id), Cell::new(device.name())];
+        for (i, value) in readings.columns().into_iter().enumerate() {
+            let mut cell = Cell::new(value);
+            if i > 0 {
+                cell.align(format::Alignment::RIGHT);
+            }
+            if before.is_some_and(|before| before.columns()[i] != value) {
+                cell.style(prettytable::Attr::Bold);
+                cell.style(prettytable::Attr::ForegroundColor(
+                    prettytable::color::YELLOW,
+                ));
+            }
+            cells.push(cell);
+        }
+        table.add_row(Row::new(cells));
+    }
+    table.print_tty(false)?;
+    Ok(())
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    let kind = if let Some(err) = err.downcast_ref::<std::io::Error>() {
+        Some(err.kind())
+    } else if let Some(err) = err.downcast_ref::<serde_json::Error>() {
+        err.io_error_kind()
+    } else if let Some(err) = err.downcast_ref::<csv::Error>() {
+        match err.kind() {
+            csv::ErrorKind::Io(err) => Some(err.kind()),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    kind == Some(std::io::ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fritzapi::{FritzDect2XX, SwitchMode};
+
+    fn device(milliwatts: u32) -> AVMDevice {
+        AVMDevice::FritzDect2XX(FritzDect2XX {
+            identifier: "11630 0123723".parse().unwrap(),
+            id: "20".to_string(),
+            name: "Laufband".to_string(),
+            productname: "FRITZ!DECT 200".to_string(),
+            present: true,
+            txbusy: false,
+            on: true,
+            lock: false,
+            devicelock: false,
+            mode: SwitchMode::Manual,
+            millivolts: 234877,
+            milliwatts,
+            energy_in_watt_h: 43714,
+            celsius: 19.5,
+        })
+    }
+
+    #[test]
+    fn readings() {
+        let readings = Readings::from(&device(4570));
+        assert_eq!(readings.columns(), ["on", "4.57", "234.9", "19.5", "43714"]);
+    }
+
+    #[test]
+    fn changes() {
+        let devices = vec![device(4570)];
+        let mut previous = HashMap::new();
+        assert_eq!(changed_devices(&previous, &devices).len(), 1);
+
+        previous.insert(devices[0].id().clone(), Readings::from(&devices[0]));
+        assert!(changed_devices(&previous, &devices).is_empty());
+        // below the displayed precision
+        assert!(changed_devices(&previous, &[device(4571)]).is_empty());
+        assert_eq!(changed_devices(&previous, &[device(4600)]).len(), 1);
+    }
+}